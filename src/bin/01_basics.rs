@@ -1,5 +1,9 @@
 use std::fmt;
 
+// `classify` 用 `match` + 区间模式把分数映射成等级，注释和实现见库里的
+// `src/student/grade.rs`；`19_demo` 的成绩报表也用它（以及可配置的 `GradeBands`）。
+use rust_notes::student::classify;
+
 const MAX_RETRY: u32 = 3;
 
 // `macro_rules!` 用来定义“声明式宏”。
@@ -100,21 +104,6 @@ fn add(a: i32, b: i32) -> i32 {
     a + b
 }
 
-// Rust 没有 `switch` 关键字；这里用 `match` 做分支匹配。
-// `90..=100` 是闭区间（包含 100），`_` 是兜底分支。
-// `..=` 必须连写成一个语法符号，不能拆成 `.. =`。
-// 对照：`a..b` 是右开区间 `[a, b)`，`a..=b` 是闭区间 `[a, b]`。
-// 返回类型是 `&'static str`：`"A"` 这类字符串字面量存放在静态区，
-// 生命周期覆盖整个程序运行期，因此是 `'static`。
-fn classify(score: i32) -> &'static str {
-    match score {
-        90..=100 => "A",
-        75..=89 => "B",
-        60..=74 => "C",
-        _ => "D",
-    }
-}
-
 // `Option<T>` 是标准库核心类型（完整路径 `std::option::Option`，底层在 `core`）。
 // 它在 prelude 里自动导入，所以这里可直接写 `Option<u64>`。
 // `Some` / `None` 是 `Option` 的两个枚举变体：
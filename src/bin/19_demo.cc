@@ -185,7 +185,9 @@ static bool ParseUint32(const std::string& raw, const char* label,
 
 static bool ParseAge(const std::string& raw, std::uint8_t* out) {
   std::uint32_t v = 0;
-  if (!ParseUint32(raw, "age", &v) || v > UINT8_MAX) {
+  // ParseUint32 失败时已经打印过错误，这里只补上超出 u8 的情况。
+  if (!ParseUint32(raw, "age", &v)) return false;
+  if (v > UINT8_MAX) {
     std::cout << "error: invalid age `" << raw << "`\n";
     return false;
   }
@@ -1,395 +1,3865 @@
 //! 19_demo: 通过一个 CLI 小练习串起基础业务逻辑（CRUD + 查询 + 排序）。
 //!
+//! 存储、索引、持久化与查询都在库 `rust_notes::student`（`src/student.rs`）里；
+//! 这个文件只是一层薄 REPL：解析命令、调用库、打印结果。
+//!
 //! 运行：
 //! cargo run --bin 19_demo
+//! cargo run --bin 19_demo -- --data /tmp/students.sms
+//! cargo run --bin 19_demo -- --backend paged --data /tmp/students.pages
+//! cargo run --bin 19_demo -- --listen 127.0.0.1:7878 --data /tmp/students.sms
+//! cargo run --bin 19_demo -- --http 127.0.0.1:8080
+//! cargo run --bin 19_demo -- --script init.sms --strict
+//! cargo run --bin 19_demo -- --data /tmp/students.sms --format csv < query.sms
+//!
+//! `--data <path>`：启动时加载该快照（不存在则从空开始）并回放 `<path>.wal`；
+//! 之后每次 add/remove/mod 都先追加到日志并 fsync，再改内存；正常退出时 checkpoint。
+//! `--history <n>`：undo 最多保留 n 步（默认 100，0 表示关闭）。
+//! `--grade-bands <spec>`：成绩分档，如 `A=90,B=75,C=60,D`（默认值），最后一段是兜底等级。
+//! `--backend <memory|paged>`：默认 memory；paged 把记录按页存在 `--data` 文件里、不常驻内存，
+//! 只支持 add/remove/mod/list/search id/search name/export 这几个基本命令。
+//! `--listen <addr>`：不读 stdin 命令，而是在 TCP 上提供同样的逐行命令协议（可用 `nc` 连接），
+//! 每个连接一个线程，共享同一个 store；不支持事务和 undo / redo，也不支持读写服务端文件的
+//! save / load / checkpoint / import / export。服务端 stdin 输入 `quit` 或 EOF 时关停。
+//! `--http <addr>`：同样的服务模式，但协议是 HTTP/1.1 + JSON 的 REST API（`/students`），见下方 `route`。
+//! `--max-conns <n>`：最多同时服务 n 个连接（默认 8），多出的连接收到 `error: server busy`
+//! （HTTP 下是 503）后断开。
+//! `--script <file>`：从文件读命令。stdin 不是终端（管道、重定向）时同样按脚本处理：
+//! 不打印横幅和提示符，`#` 开头的整行是注释。`--strict`：脚本里第一条失败的命令就停下。
+//!
+//! 交互模式（stdin 是终端）用行编辑器读命令：←/→ 移动光标、↑/↓ 翻历史、Ctrl-R 搜索历史、
+//! Tab 补全命令名、字段和已有的学生名字。`--history-file <path>`：历史文件，默认 `$HOME/.sms_history`。
+//!
+//! `--format <table|json|csv|tsv>`：列表类命令（list / search / order / query）的初始输出格式，
+//! 默认 table；会话里可以用 `format` 命令随时切换。json 是一行数组，csv 按 RFC 4180。
+//!
+//! 退出码（只在非交互时有意义，按第一条失败的命令算）：0 全部成功；1 其它运行时错误
+//! （磁盘读写、事务状态、班级或属性不存在等，也包括打不开数据文件）；2 命令行参数错误；
+//! 3 命令写错了（未知命令、用法、值解析、query 语法）；4 id 不存在。
 //!
 //! 命令：
+//! - class add <name>
+//! - class remove <name> [--force]
+//! - class list
+//! - class rename <old> <new>
 //! - add <name> <age> <class>
-//! - list
+//! - list [<id range>]
 //! - remove <id>
 //! - mod <id> <name> <age> <class>
+//! - set <id> <key> <value>
+//! - unset <id> <key>
 //! - search id <id>
 //! - search name <name>
+//! - search class <class>
+//! - search age <age|range>
+//! - search prefix <prefix>
+//! - search fuzzy <name> [maxdist]
+//! - search attr <key> <value>
 //! - order <id|name|age|class> <asc|desc>
+//! - query [where <cond>] [order by <field> [asc|desc], ...] [limit <n>] [offset <n>]
+//! - grade <id> <course> <score>
+//! - report <id>
+//! - ranking <course>
+//! - stats
+//! - group <class|age>
+//! - save <path>
+//! - load <path>
+//! - import <file.csv> [atomic]
+//! - export <file.csv>
+//! - checkpoint
+//! - check / repair
+//! - undo / redo
+//! - begin / commit / rollback
+//! - format [table|json|csv|tsv]
 //! - help
 //! - quit / exit
+//!
+//! 内存后端里班级要先 `class add` 才能被 add / mod / import 引用；分页后端的班级仍是自由文本。
+//!
+//! 区间写法与 Rust 一致：`10..18` 不含 18，`10..=18` 含 18，`10..`、`..18` 单边。
+//! 参数的引号和 shell 类似：`add "Mary Ann" 12 'Grade 3'`、`search name Mary\ Ann`。
 
-use std::cmp::Ordering;
-use std::collections::{BTreeMap, BTreeSet, HashMap};
-use std::io::{self, Write};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
-struct Student {
-    id: u32,
-    name: String,
-    age: u8,
-    class_name: String,
+use rust_notes::csv;
+use rust_notes::editor::{Completer, LineEditor};
+use rust_notes::http::{HttpError, Request, Response};
+use rust_notes::json::Json;
+use rust_notes::student::{
+    AttrValue, Attrs, ClassStats, CmpOp, DEFAULT_FUZZY_DISTANCE, Expr, GradeBands, GroupField,
+    MAX_SCORE, PagedStore, Query, QueryError, QueryParser, SortDirection, SortField, StoreError,
+    Student, StudentRepository, StudentStore, Value, parse_range, run_query, validate_attr_key,
+};
+use rust_notes::table::{Table, display_width};
+
+/// 一条命令失败的类别，非交互模式下决定进程退出码。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// 命令写错了：未知命令、参数个数不对、值解析失败、query 语法错误。
+    Parse,
+    /// 要操作的 id 不存在。
+    NotFound,
+    /// 其它运行时错误：磁盘读写失败、事务状态不对、没有可撤销的修改、引用的班级不存在、
+    /// `unset` 的属性不存在等。
+    Other,
 }
 
-#[derive(Debug, Default)]
-// 存储设计总览：
-// - 主数据只放在 `by_id`（HashMap）里，这是唯一完整记录存储。
-// - `ids`/`name_index` 是轻量索引层，只保存 id（主键）来加速查询/排序。
-// - 可以理解为“一份主存 + 多份索引”，而不是复制多份 Student 全量数据。
-// - 思路上有点像 arena 的“句柄化访问”（用 id 回主存取值），
-//   但这里本质是索引化存储，不是 arena allocator。
-struct StudentStore {
-    // 主索引：按 id O(1) 查找。
-    by_id: HashMap<u32, Student>,
-    // 有序 id 索引：便于稳定 list（默认按 id 升序）。
-    ids: BTreeSet<u32>,
-    // 名称索引：便于 `search name <name>` 精确匹配。
-    name_index: BTreeMap<String, BTreeSet<u32>>,
-    next_id: u32,
-}
-
-impl StudentStore {
-    fn new() -> Self {
-        Self {
-            by_id: HashMap::new(),
-            ids: BTreeSet::new(),
-            name_index: BTreeMap::new(),
-            next_id: 1,
+impl Failure {
+    fn exit_code(self) -> i32 {
+        match self {
+            Failure::Other => 1,
+            Failure::Parse => 3,
+            Failure::NotFound => 4,
         }
     }
+}
 
-    fn add(&mut self, name: &str, age: u8, class_name: &str) -> u32 {
-        let id = self.next_id;
-        self.next_id += 1;
+/// 学生列表的输出格式，由 `format` 命令 / `--format` 选择，每个会话（连接）各自一份。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum OutputFormat {
+    /// 对齐的文本表格，给人看。
+    #[default]
+    Table,
+    /// 一行 JSON 数组，元素和 HTTP 接口的学生对象一样。
+    Json,
+    /// RFC 4180 CSV：带表头，CRLF 换行，需要时加引号。
+    Csv,
+    /// TSV：带表头，字段里的 `\`、TAB、换行写成转义序列。
+    Tsv,
+}
 
-        let student = Student {
-            id,
-            name: name.to_string(),
-            age,
-            class_name: class_name.to_string(),
-        };
+impl OutputFormat {
+    fn from_name(raw: &str) -> Option<Self> {
+        match raw {
+            "table" => Some(Self::Table),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "tsv" => Some(Self::Tsv),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Table => "table",
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+        }
+    }
+}
 
-        self.by_id.insert(id, student.clone());
-        self.ids.insert(id);
-        self.name_index
-            .entry(student.name.clone())
-            .or_default()
-            .insert(id);
+// 一条命令的输出：写到终端 / 连接，同时记下这条命令有没有失败、算哪一类。
+// 普通输出照常 `writeln!(out, ..)`；错误一律走 `usage` / `error` / `store_error`，
+// 这样文本格式和失败分类不会对不上。`format` 是当前会话的列表格式：
+// 会话循环每条命令前放进来，`format` 命令改了之后再取回去。
+struct Reply<'a> {
+    out: &'a mut dyn Write,
+    failure: Option<Failure>,
+    format: OutputFormat,
+}
 
-        id
+impl<'a> Reply<'a> {
+    fn new(out: &'a mut dyn Write, format: OutputFormat) -> Self {
+        Self {
+            out,
+            failure: None,
+            format,
+        }
     }
 
-    fn get_by_id(&self, id: u32) -> Option<&Student> {
-        self.by_id.get(&id)
+    // 一条命令可能打印多行错误，只记第一个。
+    fn fail(&mut self, failure: Failure) {
+        self.failure.get_or_insert(failure);
     }
 
-    fn list_by_id(&self) -> Vec<&Student> {
-        self.ids
-            .iter()
-            .filter_map(|id| self.by_id.get(id))
-            .collect::<Vec<&Student>>()
+    fn usage(&mut self, text: &str) -> io::Result<()> {
+        self.fail(Failure::Parse);
+        writeln!(self.out, "usage: {text}")
     }
 
-    fn search_by_name_exact(&self, name: &str) -> Vec<&Student> {
-        match self.name_index.get(name) {
-            Some(id_set) => id_set
-                .iter()
-                .filter_map(|id| self.by_id.get(id))
-                .collect::<Vec<&Student>>(),
-            None => Vec::new(),
-        }
+    fn error(&mut self, failure: Failure, message: impl fmt::Display) -> io::Result<()> {
+        self.fail(failure);
+        writeln!(self.out, "error: {message}")
     }
 
-    fn remove(&mut self, id: u32) -> bool {
-        let removed = match self.by_id.remove(&id) {
-            Some(v) => v,
-            None => return false,
+    fn store_error(&mut self, e: &StoreError) -> io::Result<()> {
+        let failure = match e {
+            StoreError::NotFound(_) => Failure::NotFound,
+            _ => Failure::Other,
         };
-
-        self.ids.remove(&id);
-        self.remove_name_index(&removed.name, id);
-        true
+        self.error(failure, e)
     }
 
-    fn modify(&mut self, id: u32, name: &str, age: u8, class_name: &str) -> bool {
-        let old_name = match self.by_id.get(&id) {
-            Some(v) => v.name.clone(),
-            None => return false,
-        };
-        if old_name != name {
-            self.remove_name_index(&old_name, id);
-            self.name_index
-                .entry(name.to_string())
-                .or_default()
-                .insert(id);
-        }
+    fn unknown_command(&mut self) -> io::Result<()> {
+        self.fail(Failure::Parse);
+        writeln!(self.out, "unknown command. type `help`")
+    }
+}
 
-        let student = self
-            .by_id
-            .get_mut(&id)
-            .expect("id checked above, get_mut must succeed");
-        student.name = name.to_string();
-        student.age = age;
-        student.class_name = class_name.to_string();
-        true
+impl Write for Reply<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.write(buf)
     }
 
-    fn remove_name_index(&mut self, name: &str, id: u32) {
-        if let Some(set) = self.name_index.get_mut(name) {
-            set.remove(&id);
-            if set.is_empty() {
-                self.name_index.remove(name);
-            }
-        }
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum SortField {
-    Id,
-    Name,
-    Age,
-    Class,
+fn print_help(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "commands:")?;
+    writeln!(
+        out,
+        "  class add <name>                      create a class (add/mod need it first)"
+    )?;
+    writeln!(
+        out,
+        "  class remove <name> [--force]         delete a class; --force also removes its students"
+    )?;
+    writeln!(
+        out,
+        "  class list                            classes and their student counts"
+    )?;
+    writeln!(
+        out,
+        "  class rename <old> <new>              rename a class and move all its students"
+    )?;
+    writeln!(out, "  add <name> <age> <class>              add a student")?;
+    writeln!(
+        out,
+        "  list [<id range>]                     list students by id, e.g. list 100..200"
+    )?;
+    writeln!(out, "  remove <id>                           remove by id")?;
+    writeln!(out, "  mod <id> <name> <age> <class>         modify by id")?;
+    writeln!(
+        out,
+        "  set <id> <key> <value>                set a custom attribute (int/float/bool/text)"
+    )?;
+    writeln!(
+        out,
+        "  unset <id> <key>                      remove a custom attribute"
+    )?;
+    writeln!(
+        out,
+        "  search id <id>                        search by id (O(1) index)"
+    )?;
+    writeln!(
+        out,
+        "  search name <name>                    search by exact name"
+    )?;
+    writeln!(
+        out,
+        "  search class <class>                  search by class (index)"
+    )?;
+    writeln!(
+        out,
+        "  search age <age|range>                search by age, e.g. 10..18 / 10..=18"
+    )?;
+    writeln!(
+        out,
+        "  search prefix <prefix>                name prefix, case-insensitive"
+    )?;
+    writeln!(
+        out,
+        "  search fuzzy <name> [maxdist]         names within edit distance (default 2)"
+    )?;
+    writeln!(
+        out,
+        "  search attr <key> <value>             search by custom attribute (full scan)"
+    )?;
+    writeln!(out, "  order <id|name|age|class> <asc|desc>  ordered view")?;
+    writeln!(out, "  query [where ..] [order by ..] [limit n] [offset n]")?;
+    writeln!(
+        out,
+        "                                        filter/sort/page, see notes"
+    )?;
+    writeln!(
+        out,
+        "  stats                                 count and age min/max/avg per class"
+    )?;
+    writeln!(
+        out,
+        "  group <class|age>                     students grouped by class or age"
+    )?;
+    writeln!(
+        out,
+        "  grade <id> <course> <score>           record a score (0..=100)"
+    )?;
+    writeln!(
+        out,
+        "  report <id>                           scores and letters of one student"
+    )?;
+    writeln!(
+        out,
+        "  ranking <course>                      students ranked by score in a course"
+    )?;
+    writeln!(
+        out,
+        "  save <path>                           save all students to file"
+    )?;
+    writeln!(
+        out,
+        "  load <path>                           replace students from file"
+    )?;
+    writeln!(
+        out,
+        "  import <file.csv> [atomic]            add students from csv (header name,age,class)"
+    )?;
+    writeln!(
+        out,
+        "  export <file.csv>                     write all students as csv"
+    )?;
+    writeln!(
+        out,
+        "  checkpoint                            snapshot --data file, truncate log"
+    )?;
+    writeln!(
+        out,
+        "  check                                 verify indexes against the records"
+    )?;
+    writeln!(
+        out,
+        "  repair                                rebuild all indexes from the records"
+    )?;
+    writeln!(
+        out,
+        "  undo                                  revert last add/remove/mod"
+    )?;
+    writeln!(
+        out,
+        "  redo                                  re-apply last undone change"
+    )?;
+    writeln!(
+        out,
+        "  begin                                 start a transaction"
+    )?;
+    writeln!(
+        out,
+        "  commit                                apply all staged changes at once"
+    )?;
+    writeln!(
+        out,
+        "  rollback                              discard all staged changes"
+    )?;
+    writeln!(
+        out,
+        "  format [table|json|csv|tsv]           show or set the output format of listings"
+    )?;
+    writeln!(out, "  help                                  show help")?;
+    writeln!(out, "  quit | exit                           leave repl")
+}
+
+fn print_paged_help(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "commands (paged backend):")?;
+    writeln!(out, "  add <name> <age> <class>              add a student")?;
+    writeln!(
+        out,
+        "  list                                  list students by id"
+    )?;
+    writeln!(out, "  remove <id>                           remove by id")?;
+    writeln!(out, "  mod <id> <name> <age> <class>         modify by id")?;
+    writeln!(out, "  search id <id>                        search by id")?;
+    writeln!(
+        out,
+        "  search name <name>                    search by exact name (full scan)"
+    )?;
+    writeln!(
+        out,
+        "  export <file.csv>                     write all students as csv"
+    )?;
+    writeln!(
+        out,
+        "  format [table|json|csv|tsv]           show or set the output format of listings"
+    )?;
+    writeln!(out, "  help                                  show help")?;
+    writeln!(out, "  quit | exit                           leave repl")
 }
 
-#[derive(Debug, Clone, Copy)]
-enum SortDirection {
-    Asc,
-    Desc,
+// 按会话的输出格式打印一组学生。JSON / CSV / TSV 在没有结果时也是合法输出
+// （`[]` 或只有表头），方便脚本直接解析。
+fn print_students(out: &mut Reply, students: &[&Student]) -> io::Result<()> {
+    match out.format {
+        OutputFormat::Table => print_table(out, students),
+        OutputFormat::Json => {
+            let rows = students.iter().map(|s| student_json(s)).collect();
+            writeln!(out, "{}", Json::Array(rows))
+        }
+        OutputFormat::Csv => write_records(out, students, csv::write_csv_record),
+        OutputFormat::Tsv => write_records(out, students, csv::write_tsv_record),
+    }
 }
 
-fn print_help() {
-    println!("commands:");
-    println!("  add <name> <age> <class>              add a student");
-    println!("  list                                  list all students by id");
-    println!("  remove <id>                           remove by id");
-    println!("  mod <id> <name> <age> <class>         modify by id");
-    println!("  search id <id>                        search by id (O(1) index)");
-    println!("  search name <name>                    search by exact name");
-    println!("  order <id|name|age|class> <asc|desc>  ordered view");
-    println!("  help                                  show help");
-    println!("  quit | exit                           leave repl");
+// CSV / TSV 的记录：固定的四列，后面每个用到的属性 key 一列（按字典序），没有这个属性的格子留空。
+// 属性值写成 `AttrValue` 的 `Display`，import 时再按同样的规则推断回类型。
+fn write_records<W: Write + ?Sized>(
+    w: &mut W,
+    students: &[&Student],
+    write_record: fn(&mut W, &[&str]) -> io::Result<()>,
+) -> io::Result<()> {
+    let keys = students
+        .iter()
+        .flat_map(|s| s.attrs.keys().map(String::as_str))
+        .collect::<BTreeSet<&str>>();
+    let header = CSV_HEADER.iter().copied().chain(keys.iter().copied());
+    write_record(w, &header.collect::<Vec<&str>>())?;
+    for s in students {
+        let mut fields = vec![s.id.to_string(), s.name.clone(), s.age.to_string()];
+        fields.push(s.class_name.clone());
+        for key in &keys {
+            fields.push(s.attrs.get(*key).map_or(String::new(), |v| v.to_string()));
+        }
+        write_record(w, &fields.iter().map(String::as_str).collect::<Vec<&str>>())?;
+    }
+    Ok(())
 }
 
-fn print_students(students: &[&Student]) {
+// 表格单元格最多占多少列，更长的名字 / 班级截断成 `…` 结尾；属性列放宽一些。
+const MAX_CELL_WIDTH: usize = 24;
+const MAX_ATTRS_WIDTH: usize = 60;
+
+// 文本表格，列宽按内容和显示宽度自动决定（中文占两列，见 `rust_notes::table`）。
+// 含空格、引号的值按命令里的写法加上引号（见 `quote_arg`），否则 `Mary Ann` 看起来像两列。
+fn print_table(out: &mut dyn Write, students: &[&Student]) -> io::Result<()> {
     if students.is_empty() {
-        println!("(empty)");
-        return;
+        writeln!(out, "(empty)")?;
+        return Ok(());
     }
 
-    println!("{:<4} {:<12} {:<4} {:<12}", "id", "name", "age", "class");
+    // 只要有一个学生带自定义属性，就多一列 `attrs`：`key=value`，空格分隔。
+    let with_attrs = students.iter().any(|s| !s.attrs.is_empty());
+    let header: &[&str] = if with_attrs {
+        &["id", "name", "age", "class", "attrs"]
+    } else {
+        &["id", "name", "age", "class"]
+    };
+    let mut table = Table::new(header)
+        .max_cell_width(MAX_CELL_WIDTH)
+        .column_max_width(4, MAX_ATTRS_WIDTH);
     for s in students {
-        println!(
-            "{:<4} {:<12} {:<4} {:<12}",
-            s.id, s.name, s.age, s.class_name
-        );
+        let mut row = vec![
+            s.id.to_string(),
+            quote_arg(&s.name).into_owned(),
+            s.age.to_string(),
+            quote_arg(&s.class_name).into_owned(),
+        ];
+        if with_attrs {
+            row.push(format_attrs(&s.attrs));
+        }
+        table.push_row(row);
+    }
+    write!(out, "{table}")
+}
+
+// 属性的表格写法：`phone=0123 note="two words"`，值按命令参数的写法加引号。
+fn format_attrs(attrs: &Attrs) -> String {
+    attrs
+        .iter()
+        .map(|(key, value)| format!("{key}={}", quote_arg(&value.to_string())))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn print_stats(out: &mut dyn Write, stats: &ClassStats) -> io::Result<()> {
+    let total = stats.total();
+    writeln!(out, "total: {}", total.count())?;
+    if total.count() == 0 {
+        return Ok(());
+    }
+    let mut table =
+        Table::new(&["class", "count", "min", "max", "avg"]).max_cell_width(MAX_CELL_WIDTH);
+    let rows = stats.classes().chain([("(all)", total)]);
+    for (class_name, summary) in rows {
+        table.push_row(vec![
+            quote_arg(class_name).into_owned(),
+            summary.count().to_string(),
+            summary.min().unwrap_or(0).to_string(),
+            summary.max().unwrap_or(0).to_string(),
+            format!("{:.2}", summary.average().unwrap_or(0.0)),
+        ]);
     }
+    write!(out, "{table}")
 }
 
-fn parse_id(raw: &str) -> Option<u32> {
-    match raw.parse::<u32>() {
-        Ok(v) => Some(v),
-        Err(_) => {
-            println!("error: invalid id `{raw}`");
-            None
+fn print_groups(
+    out: &mut dyn Write,
+    field: GroupField,
+    groups: &[(String, Vec<&Student>)],
+) -> io::Result<()> {
+    if groups.is_empty() {
+        writeln!(out, "(empty)")?;
+        return Ok(());
+    }
+    let label = match field {
+        GroupField::Class => "class",
+        GroupField::Age => "age",
+    };
+    for (i, (key, rows)) in groups.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
         }
+        writeln!(out, "[{label} {}] {} students", quote_arg(key), rows.len())?;
+        print_table(out, rows)?;
+    }
+    Ok(())
+}
+
+// 一个学生的成绩单：每门课的分数和等级，最后是平均分（等级按四舍五入后的平均分算）。
+fn print_report(
+    out: &mut dyn Write,
+    student: &Student,
+    grades: &[(&str, u8)],
+    bands: &GradeBands,
+) -> io::Result<()> {
+    writeln!(out, "report id={} {}", student.id, quote_arg(&student.name))?;
+    if grades.is_empty() {
+        return writeln!(out, "(no grades)");
+    }
+    let mut table = Table::new(&["course", "score", "grade"]).max_cell_width(MAX_CELL_WIDTH);
+    for (course, score) in grades {
+        table.push_row(vec![
+            quote_arg(course).into_owned(),
+            score.to_string(),
+            bands.classify(i32::from(*score)).to_string(),
+        ]);
     }
+    write!(out, "{table}")?;
+    let total = grades
+        .iter()
+        .map(|(_, score)| u32::from(*score))
+        .sum::<u32>();
+    let average = f64::from(total) / grades.len() as f64;
+    writeln!(
+        out,
+        "average: {average:.2} ({})",
+        bands.classify(average.round() as i32)
+    )
 }
 
-fn parse_age(raw: &str) -> Option<u8> {
-    match raw.parse::<u8>() {
-        Ok(v) => Some(v),
-        Err(_) => {
-            println!("error: invalid age `{raw}`");
-            None
+// 一门课的排名：同分同名次，下一个名次跳过（1, 1, 3）。
+fn print_ranking(
+    out: &mut dyn Write,
+    rows: &[(&Student, u8)],
+    bands: &GradeBands,
+) -> io::Result<()> {
+    if rows.is_empty() {
+        return writeln!(out, "(empty)");
+    }
+    let mut table =
+        Table::new(&["rank", "id", "name", "score", "grade"]).max_cell_width(MAX_CELL_WIDTH);
+    let mut rank = 0;
+    for (i, (s, score)) in rows.iter().enumerate() {
+        if i == 0 || rows[i - 1].1 != *score {
+            rank = i + 1;
         }
+        table.push_row(vec![
+            rank.to_string(),
+            s.id.to_string(),
+            quote_arg(&s.name).into_owned(),
+            score.to_string(),
+            bands.classify(i32::from(*score)).to_string(),
+        ]);
+    }
+    write!(out, "{table}")
+}
+
+// 班级表：每个班级的人数，包括还没有学生的班级。
+fn print_classes(out: &mut dyn Write, classes: &[(&str, usize)]) -> io::Result<()> {
+    if classes.is_empty() {
+        return writeln!(out, "(empty)");
     }
+    let mut table = Table::new(&["class", "students"]).max_cell_width(MAX_CELL_WIDTH);
+    for (name, students) in classes {
+        table.push_row(vec![quote_arg(name).into_owned(), students.to_string()]);
+    }
+    write!(out, "{table}")
+}
+
+fn print_owned(out: &mut Reply, rows: &[Student]) -> io::Result<()> {
+    print_students(out, &rows.iter().collect::<Vec<_>>())
+}
+
+// ---- 命令解析 ----
+//
+// 一行输入先由 `Command::parse` 变成 `Command`：只看文本，不碰 store，出错返回 `CommandError`；
+// 再交给各后端的执行函数（`execute` / `execute_paged` / `execute_shared`）。
+// 解析错误统一由 `report_command_error` 打印，执行函数只处理运行时错误。
+
+type IdRange = (Bound<u32>, Bound<u32>);
+type AgeRange = (Bound<u8>, Bound<u8>);
+
+/// `search` 的各种查法。
+#[derive(Debug, PartialEq)]
+enum Search {
+    Id(u32),
+    Name(String),
+    Class(String),
+    Age(AgeRange),
+    Prefix(String),
+    Fuzzy {
+        name: String,
+        max_dist: usize,
+    },
+    /// `search attr <key> <value>`，值按 `AttrValue::infer` 推断类型。
+    Attr {
+        key: String,
+        value: AttrValue,
+    },
 }
 
-fn parse_sort_field(raw: &str) -> Option<SortField> {
-    match raw {
-        "id" => Some(SortField::Id),
-        "name" => Some(SortField::Name),
-        "age" => Some(SortField::Age),
-        "class" => Some(SortField::Class),
-        _ => {
-            println!("error: invalid field `{raw}`");
-            None
+impl Search {
+    fn kind(&self) -> &'static str {
+        match self {
+            Search::Id(_) => "id",
+            Search::Name(_) => "name",
+            Search::Class(_) => "class",
+            Search::Age(_) => "age",
+            Search::Prefix(_) => "prefix",
+            Search::Fuzzy { .. } => "fuzzy",
+            Search::Attr { .. } => "attr",
         }
     }
 }
 
-fn parse_sort_direction(raw: &str) -> Option<SortDirection> {
-    match raw {
-        "asc" => Some(SortDirection::Asc),
-        "desc" => Some(SortDirection::Desc),
-        _ => {
-            println!("error: invalid direction `{raw}`");
-            None
+/// `class` 的各个子命令。
+#[derive(Debug, PartialEq)]
+enum ClassCmd {
+    Add(String),
+    /// `class remove <name> [--force]`。
+    Remove {
+        name: String,
+        force: bool,
+    },
+    List,
+    Rename {
+        from: String,
+        to: String,
+    },
+}
+
+/// 一条解析好的命令，参数都已校验、转换成目标类型。
+#[derive(Debug, PartialEq)]
+enum Command {
+    Class(ClassCmd),
+    Add {
+        name: String,
+        age: u8,
+        class: String,
+    },
+    Remove(u32),
+    Modify {
+        id: u32,
+        name: String,
+        age: u8,
+        class: String,
+    },
+    Set {
+        id: u32,
+        key: String,
+        value: AttrValue,
+    },
+    Unset {
+        id: u32,
+        key: String,
+    },
+    /// `list`，可带 id 区间。
+    List(Option<IdRange>),
+    Search(Search),
+    Order(SortField, SortDirection),
+    Query(Query),
+    Stats,
+    Group(GroupField),
+    Grade {
+        id: u32,
+        course: String,
+        score: u8,
+    },
+    Report(u32),
+    Ranking(String),
+    Save(PathBuf),
+    Load(PathBuf),
+    Import {
+        path: PathBuf,
+        atomic: bool,
+    },
+    Export(PathBuf),
+    Checkpoint,
+    Check,
+    Repair,
+    Undo,
+    Redo,
+    Begin,
+    Commit,
+    Rollback,
+    /// `format`，不带参数时为 `None`（只显示当前格式）。
+    Format(Option<OutputFormat>),
+    Help,
+    /// `quit` 或 `exit`：记下用户敲的是哪个，提示里原样回显。
+    Quit(&'static str),
+}
+
+/// 一行命令解析失败的原因。
+#[derive(Debug, PartialEq)]
+enum CommandError {
+    /// 空行。
+    Empty,
+    /// 不认识的命令名。
+    Unknown(String),
+    /// 参数个数不对，带着这条命令的用法。
+    Usage(&'static str),
+    /// 参数值不合法：`what` 是参数的含义（id、age、field、direction 等），`token` 是原样的输入。
+    Invalid { what: &'static str, token: String },
+    /// 引号没闭合之类的切分错误，列号从 1 开始、按字符计。
+    Syntax {
+        column: usize,
+        message: &'static str,
+    },
+    /// `query` 语法错误，列号相对整行。
+    Query(QueryError),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "empty command"),
+            CommandError::Unknown(name) => write!(f, "unknown command `{name}`"),
+            CommandError::Usage(text) => write!(f, "usage: {text}"),
+            CommandError::Invalid { what, token } => write!(f, "invalid {what} `{token}`"),
+            CommandError::Syntax { column, message } => {
+                write!(f, "parse failed at column {column}: {message}")
+            }
+            CommandError::Query(e) => write!(f, "query parse failed at {e}"),
         }
     }
 }
 
-fn order_students(store: &StudentStore, field: SortField, direction: SortDirection) {
-    let mut rows = store.by_id.values().collect::<Vec<&Student>>();
-    rows.sort_by(|a, b| {
-        let ord = match field {
-            SortField::Id => a.id.cmp(&b.id),
-            SortField::Name => a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)),
-            SortField::Age => a.age.cmp(&b.age).then_with(|| a.id.cmp(&b.id)),
-            SortField::Class => a
-                .class_name
-                .cmp(&b.class_name)
-                .then_with(|| a.id.cmp(&b.id)),
-        };
-        match direction {
-            SortDirection::Asc => ord,
-            SortDirection::Desc => match ord {
-                Ordering::Less => Ordering::Greater,
-                Ordering::Equal => Ordering::Equal,
-                Ordering::Greater => Ordering::Less,
+fn invalid(what: &'static str, token: &str) -> CommandError {
+    CommandError::Invalid {
+        what,
+        token: token.to_string(),
+    }
+}
+
+// 年龄的校验规则（0..=255 的整数）。命令参数和 import 的每一行共用这一条。
+fn validate_age(raw: &str) -> Result<u8, String> {
+    raw.parse::<u8>()
+        .map_err(|_| format!("invalid age `{raw}`"))
+}
+
+// name / class 的校验规则：非空、不含控制字符（换行、TAB 等会把表格和逐行协议搅乱）。
+// 空格可以有，命令里用引号写：`add "Mary Ann" 12 "Grade 3"`。add / mod 和 import 共用。
+fn validate_field(field: &str, raw: &str) -> Result<(), String> {
+    if raw.is_empty() {
+        Err(format!("empty {field}"))
+    } else if raw.contains(char::is_control) {
+        Err(format!(
+            "{field} `{}` contains control characters",
+            raw.escape_debug()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_score(raw: &str) -> Result<u8, CommandError> {
+    raw.parse::<u8>()
+        .ok()
+        .filter(|score| *score <= MAX_SCORE)
+        .ok_or_else(|| invalid("score", raw))
+}
+
+fn parse_text(what: &'static str, raw: &str) -> Result<String, CommandError> {
+    validate_field(what, raw).map_err(|_| invalid(what, raw))?;
+    Ok(raw.to_string())
+}
+
+// 把一行切成参数，规则和 shell 类似：
+// - 空白分隔参数；引号和反斜杠能把空白留在参数里，相邻的几段拼成一个参数（`Mary' 'Ann`）。
+// - `'...'` 里全部原样，没有转义；`"..."` 里 `\"`、`\\` 是转义，其它反斜杠原样保留。
+// - 引号外 `\x` 就是字符 x（`Mary\ Ann`）。
+// 引号没闭合、行尾是单独的 `\` 时返回 `CommandError::Syntax`，列号指向那个引号 / 反斜杠。
+fn tokenize(line: &str) -> Result<Vec<String>, CommandError> {
+    let syntax = |column, message| CommandError::Syntax { column, message };
+    let mut tokens = Vec::new();
+    // 正在拼的参数；`Some("")` 表示已经有一个空参数（如 `""`）。
+    let mut current: Option<String> = None;
+    let mut chars = line.chars().zip(1..).peekable();
+    while let Some((c, column)) = chars.next() {
+        if c.is_whitespace() {
+            tokens.extend(current.take());
+            continue;
+        }
+        let token = current.get_or_insert_with(String::new);
+        match c {
+            '\'' => loop {
+                match chars.next() {
+                    Some(('\'', _)) => break,
+                    Some((c, _)) => token.push(c),
+                    None => return Err(syntax(column, "unterminated `'`")),
+                }
+            },
+            '"' => loop {
+                match chars.next() {
+                    Some(('"', _)) => break,
+                    Some(('\\', _)) => match chars.next_if(|&(c, _)| c == '"' || c == '\\') {
+                        Some((c, _)) => token.push(c),
+                        None => token.push('\\'),
+                    },
+                    Some((c, _)) => token.push(c),
+                    None => return Err(syntax(column, "unterminated `\"`")),
+                }
             },
+            '\\' => match chars.next() {
+                Some((c, _)) => token.push(c),
+                None => return Err(syntax(column, "trailing `\\`")),
+            },
+            c => token.push(c),
         }
-    });
-    print_students(&rows);
+    }
+    tokens.extend(current);
+    Ok(tokens)
 }
 
-// 返回值：true 表示继续循环；false 表示退出。
-fn handle_command(line: &str, store: &mut StudentStore) -> bool {
-    let parts = line.split_whitespace().collect::<Vec<&str>>();
-    if parts.is_empty() {
-        return true;
+// tokenize 的反方向：需要时把值加上双引号，保证原样贴回命令里能得到同一个参数。
+fn quote_arg(raw: &str) -> Cow<'_, str> {
+    let plain = !raw.is_empty()
+        && !raw.contains(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '\\'));
+    if plain {
+        return Cow::Borrowed(raw);
+    }
+    let mut out = String::with_capacity(raw.len() + 2);
+    out.push('"');
+    for c in raw.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
     }
+    out.push('"');
+    Cow::Owned(out)
+}
 
-    match parts[0] {
-        "add" => {
-            if parts.len() != 4 {
-                println!("usage: add <name> <age> <class>");
-                return true;
-            }
-            if let Some(age) = parse_age(parts[2]) {
-                let id = store.add(parts[1], age, parts[3]);
-                println!("ok: added id={id}");
-            }
+fn parse_id(raw: &str) -> Result<u32, CommandError> {
+    raw.parse::<u32>().map_err(|_| invalid("id", raw))
+}
+
+fn parse_age(raw: &str) -> Result<u8, CommandError> {
+    validate_age(raw).map_err(|_| invalid("age", raw))
+}
+
+// 字段名、排序方向、分组字段：库里的 `FromStr`，错误换成带原样输入的 `Invalid`。
+fn parse_value<T: FromStr>(what: &'static str, raw: &str) -> Result<T, CommandError> {
+    raw.parse::<T>().map_err(|_| invalid(what, raw))
+}
+
+fn parse_attr_key(raw: &str) -> Result<String, CommandError> {
+    validate_attr_key(raw).map_err(|_| invalid("attribute key", raw))?;
+    Ok(raw.to_string())
+}
+
+// 属性值和 name / class 一样不能含控制字符，类型按字面推断（`0123` 仍是文本）。
+fn parse_attr_value(raw: &str) -> Result<AttrValue, CommandError> {
+    validate_field("value", raw).map_err(|_| invalid("value", raw))?;
+    Ok(AttrValue::infer(raw))
+}
+
+fn parse_search(args: &[&str]) -> Result<Search, CommandError> {
+    const USAGE: &str = "search <id|name|class|age|prefix|fuzzy|attr> <value>";
+    let [kind, rest @ ..] = args else {
+        return Err(CommandError::Usage(USAGE));
+    };
+    if rest.is_empty() {
+        return Err(CommandError::Usage(USAGE));
+    }
+    let one = |usage: &'static str| match rest {
+        [value] => Ok(*value),
+        _ => Err(CommandError::Usage(usage)),
+    };
+    let search = match *kind {
+        "id" => Search::Id(parse_id(one("search id <id>")?)?),
+        "name" => Search::Name(one("search name <name>")?.to_string()),
+        "class" => Search::Class(one("search class <class>")?.to_string()),
+        "age" => {
+            let raw = one("search age <age|range>")?;
+            Search::Age(parse_range(raw, "age").map_err(|_| invalid("age range", raw))?)
         }
-        "list" => {
-            if parts.len() != 1 {
-                println!("usage: list");
-                return true;
+        "prefix" => Search::Prefix(one("search prefix <prefix>")?.to_string()),
+        "fuzzy" => {
+            let (name, max_dist) = match rest {
+                [name] => (name, DEFAULT_FUZZY_DISTANCE),
+                [name, raw] => (name, parse_value("maxdist", raw)?),
+                _ => return Err(CommandError::Usage("search fuzzy <name> [maxdist]")),
+            };
+            Search::Fuzzy {
+                name: name.to_string(),
+                max_dist,
             }
-            let rows = store.list_by_id();
-            print_students(&rows);
         }
-        "remove" => {
-            if parts.len() != 2 {
-                println!("usage: remove <id>");
-                return true;
+        "attr" => match rest {
+            [key, value] => Search::Attr {
+                key: parse_attr_key(key)?,
+                value: parse_attr_value(value)?,
+            },
+            _ => return Err(CommandError::Usage("search attr <key> <value>")),
+        },
+        _ => return Err(CommandError::Usage(USAGE)),
+    };
+    Ok(search)
+}
+
+fn parse_class(args: &[&str]) -> Result<ClassCmd, CommandError> {
+    let class = |raw: &str| parse_text("class", raw);
+    let cmd = match args {
+        ["add", name] => ClassCmd::Add(class(name)?),
+        ["add", ..] => return Err(CommandError::Usage("class add <name>")),
+        ["remove", name] => ClassCmd::Remove {
+            name: class(name)?,
+            force: false,
+        },
+        ["remove", name, "--force"] => ClassCmd::Remove {
+            name: class(name)?,
+            force: true,
+        },
+        ["remove", ..] => return Err(CommandError::Usage("class remove <name> [--force]")),
+        ["list"] => ClassCmd::List,
+        ["list", ..] => return Err(CommandError::Usage("class list")),
+        ["rename", from, to] => ClassCmd::Rename {
+            from: class(from)?,
+            to: class(to)?,
+        },
+        ["rename", ..] => return Err(CommandError::Usage("class rename <old> <new>")),
+        _ => return Err(CommandError::Usage("class <add|remove|list|rename> ...")),
+    };
+    Ok(cmd)
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Command, CommandError> {
+        // query 后面是另一门小语言，有自己的引号规则：直接拿原始行交给 QueryParser，
+        // 不走 tokenize，这样列号也能对应到用户输入。
+        let trimmed = line.trim_start();
+        if let Some(text) = trimmed.strip_prefix("query")
+            && (text.is_empty() || text.starts_with(char::is_whitespace))
+        {
+            let start = line.len() - text.len();
+            let base_col = line[..start].chars().count();
+            let query = QueryParser::parse(text, base_col).map_err(CommandError::Query)?;
+            return Ok(Command::Query(query));
+        }
+
+        let owned = tokenize(line)?;
+        let tokens = owned.iter().map(String::as_str).collect::<Vec<&str>>();
+        let Some((&name, args)) = tokens.split_first() else {
+            return Err(CommandError::Empty);
+        };
+        // 参数个数必须正好是 n，否则报这条命令的用法。
+        let arity = |n: usize, usage: &'static str| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(CommandError::Usage(usage))
             }
-            if let Some(id) = parse_id(parts[1]) {
-                if store.remove(id) {
-                    println!("ok: removed id={id}");
-                } else {
-                    println!("error: id={id} not found");
+        };
+        let path = |usage: &'static str| arity(1, usage).map(|()| PathBuf::from(args[0]));
+
+        let cmd = match name {
+            "class" => Command::Class(parse_class(args)?),
+            "add" => {
+                arity(3, "add <name> <age> <class>")?;
+                Command::Add {
+                    name: parse_text("name", args[0])?,
+                    age: parse_age(args[1])?,
+                    class: parse_text("class", args[2])?,
                 }
             }
-        }
-        // 这里命令名写 `mod`，仅是字符串命令，和 Rust 关键字不冲突。
-        "mod" => {
-            if parts.len() != 5 {
-                println!("usage: mod <id> <name> <age> <class>");
-                return true;
+            "remove" => {
+                arity(1, "remove <id>")?;
+                Command::Remove(parse_id(args[0])?)
             }
-            let id = match parse_id(parts[1]) {
-                Some(v) => v,
-                None => return true,
-            };
-            let age = match parse_age(parts[3]) {
-                Some(v) => v,
-                None => return true,
-            };
-            if store.modify(id, parts[2], age, parts[4]) {
-                println!("ok: modified id={id}");
-            } else {
-                println!("error: id={id} not found");
+            // 这里命令名写 `mod`，仅是字符串命令，和 Rust 关键字不冲突。
+            "mod" => {
+                arity(4, "mod <id> <name> <age> <class>")?;
+                Command::Modify {
+                    id: parse_id(args[0])?,
+                    name: parse_text("name", args[1])?,
+                    age: parse_age(args[2])?,
+                    class: parse_text("class", args[3])?,
+                }
             }
-        }
-        "search" => {
-            if parts.len() < 3 {
-                println!("usage: search id <id> | search name <name>");
-                return true;
+            "set" => {
+                arity(3, "set <id> <key> <value>")?;
+                Command::Set {
+                    id: parse_id(args[0])?,
+                    key: parse_attr_key(args[1])?,
+                    value: parse_attr_value(args[2])?,
+                }
             }
-            match parts[1] {
-                "id" => {
-                    if parts.len() != 3 {
-                        println!("usage: search id <id>");
-                        return true;
-                    }
-                    let id = match parse_id(parts[2]) {
-                        Some(v) => v,
-                        None => return true,
-                    };
-                    match store.get_by_id(id) {
-                        Some(s) => print_students(&[s]),
-                        None => println!("(empty)"),
+            "unset" => {
+                arity(2, "unset <id> <key>")?;
+                Command::Unset {
+                    id: parse_id(args[0])?,
+                    key: parse_attr_key(args[1])?,
+                }
+            }
+            "list" => match args {
+                [] => Command::List(None),
+                [raw] => Command::List(Some(
+                    parse_range(raw, "id").map_err(|_| invalid("id range", raw))?,
+                )),
+                _ => return Err(CommandError::Usage("list [<id range>]")),
+            },
+            "search" => Command::Search(parse_search(args)?),
+            "order" => {
+                arity(2, "order <id|name|age|class> <asc|desc>")?;
+                Command::Order(
+                    parse_value("field", args[0])?,
+                    parse_value("direction", args[1])?,
+                )
+            }
+            "stats" => {
+                arity(0, "stats")?;
+                Command::Stats
+            }
+            "group" => {
+                arity(1, "group <class|age>")?;
+                Command::Group(parse_value("group field", args[0])?)
+            }
+            "grade" => {
+                arity(3, "grade <id> <course> <score>")?;
+                Command::Grade {
+                    id: parse_id(args[0])?,
+                    course: parse_text("course", args[1])?,
+                    score: parse_score(args[2])?,
+                }
+            }
+            "report" => {
+                arity(1, "report <id>")?;
+                Command::Report(parse_id(args[0])?)
+            }
+            "ranking" => {
+                arity(1, "ranking <course>")?;
+                Command::Ranking(parse_text("course", args[0])?)
+            }
+            "save" => Command::Save(path("save <path>")?),
+            "load" => Command::Load(path("load <path>")?),
+            "import" => match args {
+                [path] => Command::Import {
+                    path: PathBuf::from(path),
+                    atomic: false,
+                },
+                [path, "atomic"] => Command::Import {
+                    path: PathBuf::from(path),
+                    atomic: true,
+                },
+                _ => return Err(CommandError::Usage("import <file.csv> [atomic]")),
+            },
+            "export" => Command::Export(path("export <file.csv>")?),
+            "checkpoint" => {
+                arity(0, "checkpoint")?;
+                Command::Checkpoint
+            }
+            "check" => {
+                arity(0, "check")?;
+                Command::Check
+            }
+            "repair" => {
+                arity(0, "repair")?;
+                Command::Repair
+            }
+            "undo" => {
+                arity(0, "undo")?;
+                Command::Undo
+            }
+            "redo" => {
+                arity(0, "redo")?;
+                Command::Redo
+            }
+            "begin" => {
+                arity(0, "begin")?;
+                Command::Begin
+            }
+            "commit" => {
+                arity(0, "commit")?;
+                Command::Commit
+            }
+            "rollback" => {
+                arity(0, "rollback")?;
+                Command::Rollback
+            }
+            "format" => match args {
+                [] => Command::Format(None),
+                [raw] => Command::Format(Some(
+                    OutputFormat::from_name(raw).ok_or_else(|| invalid("format", raw))?,
+                )),
+                _ => return Err(CommandError::Usage("format [table|json|csv|tsv]")),
+            },
+            // help / quit / exit 一直不检查多余的参数。
+            "help" => Command::Help,
+            "quit" => Command::Quit("quit"),
+            "exit" => Command::Quit("exit"),
+            _ => return Err(CommandError::Unknown(name.to_string())),
+        };
+        Ok(cmd)
+    }
+
+    // 命令名，用在 "`X` is not allowed / not supported" 之类的提示里。
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Class(_) => "class",
+            Command::Add { .. } => "add",
+            Command::Remove(_) => "remove",
+            Command::Modify { .. } => "mod",
+            Command::Set { .. } => "set",
+            Command::Unset { .. } => "unset",
+            Command::List(_) => "list",
+            Command::Search(_) => "search",
+            Command::Order(..) => "order",
+            Command::Query(_) => "query",
+            Command::Stats => "stats",
+            Command::Group(_) => "group",
+            Command::Grade { .. } => "grade",
+            Command::Report(_) => "report",
+            Command::Ranking(_) => "ranking",
+            Command::Save(_) => "save",
+            Command::Load(_) => "load",
+            Command::Import { .. } => "import",
+            Command::Export(_) => "export",
+            Command::Checkpoint => "checkpoint",
+            Command::Check => "check",
+            Command::Repair => "repair",
+            Command::Undo => "undo",
+            Command::Redo => "redo",
+            Command::Begin => "begin",
+            Command::Commit => "commit",
+            Command::Rollback => "rollback",
+            Command::Format(_) => "format",
+            Command::Help => "help",
+            Command::Quit(word) => word,
+        }
+    }
+
+    // 只需要 `&StudentStore` 的命令（`--listen` 下只拿读锁）。
+    // help / format / quit 不碰 store，由各执行函数先处理，这里不算。
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::Class(ClassCmd::List)
+                | Command::List(_)
+                | Command::Search(_)
+                | Command::Order(..)
+                | Command::Query(_)
+                | Command::Stats
+                | Command::Group(_)
+                | Command::Check
+                | Command::Report(_)
+                | Command::Ranking(_)
+                | Command::Save(_)
+                | Command::Export(_)
+        )
+    }
+
+    // 两个后端都支持的基本命令，只通过 StudentRepository 访问数据。
+    fn is_basic(&self) -> bool {
+        matches!(
+            self,
+            Command::Add { .. }
+                | Command::Remove(_)
+                | Command::Modify { .. }
+                | Command::List(None)
+                | Command::Search(Search::Id(_) | Search::Name(_))
+                | Command::Export(_)
+        )
+    }
+
+    // 这些命令会越过事务直接读写磁盘或历史，事务打开期间一律拒绝。
+    fn bypasses_transaction(&self) -> bool {
+        matches!(
+            self,
+            Command::Save(_)
+                | Command::Load(_)
+                | Command::Import { .. }
+                | Command::Checkpoint
+                | Command::Repair
+                | Command::Undo
+                | Command::Redo
+        )
+    }
+}
+
+// 打印解析错误，失败类别都是 `Failure::Parse`。
+// `line` 是传给 `Command::parse` 的那一行，query 出错时在它下面用 `^` 标出位置。
+fn report_command_error(out: &mut Reply, line: &str, e: &CommandError) -> io::Result<()> {
+    match e {
+        CommandError::Empty => Ok(()),
+        CommandError::Unknown(_) => out.unknown_command(),
+        CommandError::Usage(text) => out.usage(text),
+        CommandError::Invalid { .. } => out.error(Failure::Parse, e),
+        CommandError::Syntax { column, .. } | CommandError::Query(QueryError { column, .. }) => {
+            out.error(Failure::Parse, e)?;
+            // column 按字符数算；中文等宽字符占两列，按显示宽度补空格 `^` 才对得上。
+            let prefix = line.chars().take(column - 1).collect::<String>();
+            writeln!(out, "  {line}")?;
+            writeln!(out, "  {}^", " ".repeat(display_width(&prefix)))
+        }
+    }
+}
+
+// ---- CSV 导入 / 导出 ----
+
+const CSV_HEADER: [&str; 4] = ["id", "name", "age", "class"];
+
+// import 文件表头里 name / age / class 各在第几列。列顺序随意；
+// `id` 列允许出现（export 的文件可以直接导回来），但会被忽略：导入的学生拿新 id。
+// 其余的列名只要是合法的属性 key 就当作自定义属性列。
+struct ImportColumns {
+    width: usize,
+    name: usize,
+    age: usize,
+    class: usize,
+    attrs: Vec<(usize, String)>,
+}
+
+// 通过校验的一行：name / age / class，加上非空的属性格子。
+type ImportRow<'r> = (&'r str, u8, &'r str, Attrs);
+
+impl ImportColumns {
+    fn from_header(header: &[String]) -> Result<Self, String> {
+        let mut found = [None; 4];
+        let mut attrs = Vec::new();
+        for (i, column) in header.iter().enumerate() {
+            let duplicate = || format!("duplicate column `{column}` in header");
+            match CSV_HEADER.iter().position(|c| c == column) {
+                Some(slot) => {
+                    if found[slot].replace(i).is_some() {
+                        return Err(duplicate());
                     }
                 }
-                "name" => {
-                    if parts.len() != 3 {
-                        println!("usage: search name <name>");
-                        return true;
+                None => {
+                    validate_attr_key(column)
+                        .map_err(|_| format!("unknown column `{column}` in header"))?;
+                    if attrs.iter().any(|(_, key)| key == column) {
+                        return Err(duplicate());
                     }
-                    let rows = store.search_by_name_exact(parts[2]);
-                    print_students(&rows);
+                    attrs.push((i, column.clone()));
                 }
-                _ => println!("usage: search id <id> | search name <name>"),
             }
         }
-        "order" => {
-            if parts.len() != 3 {
-                println!("usage: order <id|name|age|class> <asc|desc>");
-                return true;
+        let column = |slot: usize| {
+            found[slot].ok_or_else(|| format!("header is missing column `{}`", CSV_HEADER[slot]))
+        };
+        Ok(Self {
+            width: header.len(),
+            name: column(1)?,
+            age: column(2)?,
+            class: column(3)?,
+            attrs,
+        })
+    }
+
+    // 校验一行数据，或者返回拒绝的原因。属性格子为空表示这个学生没有该属性。
+    fn row<'r>(&self, fields: &'r [String]) -> Result<ImportRow<'r>, String> {
+        if fields.len() != self.width {
+            return Err(format!(
+                "expected {} fields, got {}",
+                self.width,
+                fields.len()
+            ));
+        }
+        let (name, class) = (fields[self.name].as_str(), fields[self.class].as_str());
+        validate_field("name", name)?;
+        let age = validate_age(&fields[self.age])?;
+        validate_field("class", class)?;
+        let mut attrs = Attrs::new();
+        for (i, key) in &self.attrs {
+            let raw = fields[*i].as_str();
+            if raw.is_empty() {
+                continue;
+            }
+            validate_field(key, raw)?;
+            attrs.insert(key.clone(), AttrValue::infer(raw));
+        }
+        Ok((name, age, class, attrs))
+    }
+}
+
+// `import <file.csv> [atomic]`：每条被拒绝的行打印一行 `error: line N: 原因`。
+// 默认导入其余合法的行；`atomic` 时只要有一行被拒绝就一条都不导入。
+// 合法的行在一个事务里加进去：整次导入只写一组日志，`undo` 一步撤销。
+fn import_students(
+    out: &mut Reply,
+    store: &mut StudentStore,
+    path: &Path,
+    atomic: bool,
+) -> io::Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => return out.error(Failure::Other, format_args!("import failed: {e}")),
+    };
+    let mut records = csv::records(&text);
+    let header = match records.next() {
+        Some(Ok(header)) => header,
+        Some(Err(e)) => return out.error(Failure::Parse, format_args!("import failed: {e}")),
+        None => return out.error(Failure::Parse, "import failed: empty file"),
+    };
+    let columns = match ImportColumns::from_header(&header.fields) {
+        Ok(v) => v,
+        Err(message) => {
+            return out.error(
+                Failure::Parse,
+                format_args!("import failed: line {}: {message}", header.line),
+            );
+        }
+    };
+
+    let records = records.collect::<Vec<_>>();
+    let mut rows = Vec::new();
+    let mut rejected = 0;
+    for record in &records {
+        let row = match record {
+            Ok(r) => columns
+                .row(&r.fields)
+                .and_then(|row| {
+                    if store.has_class(row.2) {
+                        Ok(row)
+                    } else {
+                        Err(StoreError::UnknownClass(row.2.to_string()).to_string())
+                    }
+                })
+                .map_err(|message| (r.line, message)),
+            Err(e) => Err((e.line, e.message.to_string())),
+        };
+        match row {
+            Ok(row) => rows.push(row),
+            Err((line, message)) => {
+                rejected += 1;
+                out.error(Failure::Parse, format_args!("line {line}: {message}"))?;
             }
-            let field = match parse_sort_field(parts[1]) {
-                Some(v) => v,
-                None => return true,
-            };
-            let direction = match parse_sort_direction(parts[2]) {
-                Some(v) => v,
-                None => return true,
-            };
-            order_students(store, field, direction);
         }
-        "help" => print_help(),
-        "quit" | "exit" => return false,
-        _ => println!("unknown command. type `help`"),
     }
+    if atomic && rejected > 0 {
+        return out.error(
+            Failure::Parse,
+            format_args!("import aborted: {rejected} lines rejected, nothing imported"),
+        );
+    }
+
+    if let Err(e) = store.begin() {
+        return out.store_error(&e);
+    }
+    for (name, age, class, attrs) in rows {
+        if let Err(e) = store.add_with_attrs(name, age, class, attrs) {
+            let _ = store.rollback();
+            return out.store_error(&e);
+        }
+    }
+    match store.commit_txn() {
+        Ok(n) if rejected > 0 => writeln!(
+            out,
+            "ok: imported {n} students from {}, {rejected} lines rejected",
+            path.display()
+        ),
+        Ok(n) => writeln!(out, "ok: imported {n} students from {}", path.display()),
+        Err(e) => {
+            let _ = store.rollback();
+            out.error(Failure::Other, format_args!("import failed: {e}"))
+        }
+    }
+}
 
-    true
+// `export <file.csv>`：按 id 升序写出全部学生，格式和 `format csv` 的输出相同，
+// 不受当前会话格式影响。
+fn export_students(out: &mut Reply, repo: &dyn StudentRepository, path: &Path) -> io::Result<()> {
+    let rows = match repo.list_by_id() {
+        Ok(v) => v,
+        Err(e) => return out.store_error(&e),
+    };
+    let written = File::create(path).and_then(|file| {
+        let mut w = BufWriter::new(file);
+        write_records(
+            &mut w,
+            &rows.iter().collect::<Vec<_>>(),
+            csv::write_csv_record,
+        )?;
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()
+    });
+    match written {
+        Ok(()) => writeln!(
+            out,
+            "ok: exported {} students to {}",
+            rows.len(),
+            path.display()
+        ),
+        Err(e) => out.error(Failure::Other, format_args!("export failed: {e}")),
+    }
 }
 
-fn main() -> io::Result<()> {
-    // 内存态 demo：不落盘，退出后数据清空。
-    let mut store = StudentStore::new();
+// ---- 命令执行 ----
 
-    println!("student-cli demo");
-    println!("type `help` to see commands");
+// `format`：改的是 Reply 上的会话格式，会话循环在命令结束后把它存回去。
+fn set_format(out: &mut Reply, format: Option<OutputFormat>) -> io::Result<()> {
+    match format {
+        None => writeln!(out, "format: {}", out.format.name()),
+        Some(format) => {
+            out.format = format;
+            writeln!(out, "ok: format {}", format.name())
+        }
+    }
+}
 
-    let stdin = io::stdin();
-    loop {
-        print!("sms> ");
-        io::stdout().flush()?;
+// 两个后端都支持的只读基本命令，只通过 StudentRepository 访问数据。
+fn exec_basic_read(out: &mut Reply, cmd: &Command, repo: &dyn StudentRepository) -> io::Result<()> {
+    match cmd {
+        Command::List(None) => match repo.list_by_id() {
+            Ok(rows) => print_owned(out, &rows),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Search(Search::Id(id)) => match repo.get_by_id(*id) {
+            Ok(Some(s)) => print_students(out, &[&s]),
+            Ok(None) => print_students(out, &[]),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Search(Search::Name(name)) => match repo.search_by_name_exact(name) {
+            Ok(rows) => print_owned(out, &rows),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Export(path) => export_students(out, repo, path),
+        _ => unreachable!("`{}` is not a basic read command", cmd.name()),
+    }
+}
 
-        let mut line = String::new();
-        if stdin.read_line(&mut line)? == 0 {
-            // EOF（如 Ctrl-D）时退出。
-            println!();
-            break;
+// 两个后端都支持的写命令：add / remove / mod。
+fn exec_basic_write(
+    out: &mut Reply,
+    cmd: Command,
+    repo: &mut dyn StudentRepository,
+) -> io::Result<()> {
+    match cmd {
+        Command::Add { name, age, class } => match repo.add(&name, age, &class) {
+            Ok(id) => writeln!(out, "ok: added id={id}"),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Remove(id) => match repo.remove(id) {
+            Ok(()) => writeln!(out, "ok: removed id={id}"),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Modify {
+            id,
+            name,
+            age,
+            class,
+        } => match repo.modify(id, &name, age, &class) {
+            Ok(()) => writeln!(out, "ok: modified id={id}"),
+            Err(e) => out.store_error(&e),
+        },
+        _ => unreachable!("`{}` is not a basic write command", cmd.name()),
+    }
+}
+
+// 只需要 `&StudentStore` 的命令（查询、排序、统计、save、export）。
+// 分开写是为了 `--listen` 模式下这些命令只拿读锁，多个连接可以同时查。
+fn exec_read(out: &mut Reply, cmd: &Command, store: &StudentStore) -> io::Result<()> {
+    match cmd {
+        Command::Class(ClassCmd::List) => print_classes(out, &store.classes()),
+        Command::List(Some(range)) => print_students(out, &store.list_id_range(*range)),
+        Command::Search(Search::Class(class)) => print_students(out, &store.search_by_class(class)),
+        Command::Search(Search::Age(range)) => print_students(out, &store.search_by_age(*range)),
+        Command::Search(Search::Prefix(prefix)) => {
+            print_students(out, &store.search_by_name_prefix(prefix))
         }
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
+        Command::Search(Search::Fuzzy { name, max_dist }) => {
+            print_students(out, &store.search_by_name_fuzzy(name, *max_dist))
+        }
+        Command::Search(Search::Attr { key, value }) => {
+            print_students(out, &store.search_by_attr(key, value))
+        }
+        Command::Order(field, direction) => print_students(out, &store.ordered(*field, *direction)),
+        Command::Query(query) => print_students(out, &run_query(store, query)),
+        Command::Stats => print_stats(out, store.stats()),
+        Command::Group(field) => print_groups(out, *field, &store.groups(*field)),
+        Command::Report(id) => match store.get_by_id(*id) {
+            Some(s) => print_report(out, s, &store.grades_of(*id), store.grade_bands()),
+            None => out.store_error(&StoreError::NotFound(*id)),
+        },
+        Command::Ranking(course) => print_ranking(out, &store.ranking(course), store.grade_bands()),
+        Command::Check => {
+            let found = store.verify();
+            for issue in &found {
+                writeln!(out, "problem: {issue}")?;
+            }
+            if found.is_empty() {
+                writeln!(out, "ok: {} students, indexes consistent", store.len())
+            } else {
+                out.error(
+                    Failure::Other,
+                    format_args!("{} problems found; run `repair`", found.len()),
+                )
+            }
         }
+        Command::Save(path) => match store.save(path) {
+            Ok(()) => writeln!(
+                out,
+                "ok: saved {} students to {}",
+                store.len(),
+                path.display()
+            ),
+            Err(e) => out.error(Failure::Other, format_args!("save failed: {e}")),
+        },
+        _ => exec_basic_read(out, cmd, store),
+    }
+}
 
-        if !handle_command(line, &mut store) {
-            break;
+// 会修改 store 或历史的命令，需要 `&mut StudentStore`（`--listen` 下拿写锁）。
+fn exec_write(out: &mut Reply, cmd: Command, store: &mut StudentStore) -> io::Result<()> {
+    match cmd {
+        Command::Load(path) => {
+            // 失败时不动当前 store：read_snapshot 要么返回完整新 store，要么 Err。
+            let result = StudentStore::load(&path).and_then(|loaded| store.replace_data(loaded));
+            match result {
+                Ok(()) => writeln!(
+                    out,
+                    "ok: loaded {} students from {}",
+                    store.len(),
+                    path.display()
+                ),
+                Err(e) => out.error(Failure::Other, format_args!("load failed: {e}")),
+            }
+        }
+        Command::Import { path, atomic } => import_students(out, store, &path, atomic),
+        Command::Class(ClassCmd::Add(name)) => match store.add_class(&name) {
+            Ok(()) => writeln!(out, "ok: class added {}", quote_arg(&name)),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Class(ClassCmd::Remove { name, force }) => {
+            match store.remove_class(&name, force) {
+                Ok(0) => writeln!(out, "ok: class removed {}", quote_arg(&name)),
+                Ok(n) => writeln!(
+                    out,
+                    "ok: class removed {} ({n} students removed)",
+                    quote_arg(&name)
+                ),
+                Err(e @ StoreError::ClassInUse { .. }) => out.error(
+                    Failure::Other,
+                    format_args!("{e}; use `class remove {} --force`", quote_arg(&name)),
+                ),
+                Err(e) => out.store_error(&e),
+            }
+        }
+        Command::Class(ClassCmd::Rename { from, to }) => match store.rename_class(&from, &to) {
+            Ok(n) => writeln!(
+                out,
+                "ok: class renamed {} -> {} ({n} students)",
+                quote_arg(&from),
+                quote_arg(&to)
+            ),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Set { id, key, value } => {
+            let shown = format!(
+                "{key}={} ({})",
+                quote_arg(&value.to_string()),
+                value.type_name()
+            );
+            match store.set_attr(id, &key, value) {
+                Ok(()) => writeln!(out, "ok: set id={id} {shown}"),
+                Err(e) => out.store_error(&e),
+            }
+        }
+        Command::Unset { id, key } => match store.unset_attr(id, &key) {
+            Ok(_) => writeln!(out, "ok: unset id={id} {key}"),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Grade { id, course, score } => match store.set_grade(id, &course, score) {
+            Ok(old) => {
+                let letter = store.grade_bands().classify(i32::from(score));
+                let shown = quote_arg(&course);
+                match old {
+                    Some(old) => writeln!(
+                        out,
+                        "ok: grade id={id} {shown}={score} ({letter}), was {old}"
+                    ),
+                    None => writeln!(out, "ok: grade id={id} {shown}={score} ({letter})"),
+                }
+            }
+            Err(e) => out.store_error(&e),
+        },
+        Command::Undo | Command::Redo => {
+            let result = if cmd == Command::Undo {
+                store.undo()
+            } else {
+                store.redo()
+            };
+            match result {
+                Ok(ops) => {
+                    for op in ops {
+                        writeln!(out, "ok: {} applied {op}", cmd.name())?;
+                    }
+                    Ok(())
+                }
+                Err(e) => out.store_error(&e),
+            }
         }
+        Command::Repair => match store.repair() {
+            Ok(found) if found.is_empty() => writeln!(out, "ok: nothing to repair"),
+            Ok(found) => {
+                for issue in &found {
+                    writeln!(out, "fixed: {issue}")?;
+                }
+                writeln!(
+                    out,
+                    "ok: rebuilt indexes for {} students, {} problems fixed",
+                    store.len(),
+                    found.len()
+                )
+            }
+            Err(e) => out.store_error(&e),
+        },
+        Command::Checkpoint => match store.checkpoint() {
+            Ok(()) => writeln!(out, "ok: checkpoint at lsn={}", store.lsn()),
+            Err(e) => out.error(Failure::Other, format_args!("checkpoint failed: {e}")),
+        },
+        Command::Begin => match store.begin() {
+            Ok(()) => writeln!(out, "ok: transaction started"),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Commit => match store.commit_txn() {
+            Ok(n) => writeln!(out, "ok: committed {n} changes"),
+            Err(StoreError::Io(e)) => out.error(
+                Failure::Other,
+                format_args!("log write failed, transaction still open: {e}"),
+            ),
+            Err(e) => out.store_error(&e),
+        },
+        Command::Rollback => match store.rollback() {
+            Ok(n) => writeln!(out, "ok: rolled back {n} changes"),
+            Err(e) => out.store_error(&e),
+        },
+        _ => exec_basic_write(out, cmd, store),
     }
+}
 
-    println!("bye");
-    Ok(())
+// 内存后端执行一条命令。返回值：Ok(true) 表示继续循环；Ok(false) 表示退出。
+// quit_warned：事务未结束时第一次 quit 只提醒，第二次才真正丢弃退出。
+fn execute(
+    out: &mut Reply,
+    cmd: Command,
+    store: &mut StudentStore,
+    quit_warned: &mut bool,
+) -> io::Result<bool> {
+    if store.in_transaction() && cmd.bypasses_transaction() {
+        out.error(
+            Failure::Other,
+            format_args!("`{}` is not allowed inside a transaction", cmd.name()),
+        )?;
+        return Ok(true);
+    }
+
+    match cmd {
+        Command::Help => print_help(out)?,
+        Command::Format(format) => set_format(out, format)?,
+        Command::Quit(word) => {
+            if let Some(n) = store.pending_changes()
+                && !*quit_warned
+            {
+                *quit_warned = true;
+                writeln!(
+                    out,
+                    "warning: transaction has {n} uncommitted changes; `commit`/`rollback` first, or `{word}` again to discard them"
+                )?;
+                return Ok(true);
+            }
+            return Ok(false);
+        }
+        cmd if cmd.is_read_only() => exec_read(out, &cmd, store)?,
+        cmd => exec_write(out, cmd, store)?,
+    }
+    Ok(true)
+}
+
+// 分页后端执行一条命令：只有基本命令，没有事务，所以 quit 不需要确认。
+// 内存版独有的命令给出明确提示，而不是 "unknown command"。
+fn execute_paged(out: &mut Reply, cmd: Command, store: &mut PagedStore) -> io::Result<bool> {
+    match cmd {
+        Command::Help => print_paged_help(out)?,
+        Command::Format(format) => set_format(out, format)?,
+        Command::Quit(_) => return Ok(false),
+        cmd if !cmd.is_basic() => {
+            let shown = match &cmd {
+                Command::Search(search) => format!("search {}", search.kind()),
+                Command::List(Some(_)) => "list <id range>".to_string(),
+                _ => cmd.name().to_string(),
+            };
+            out.error(
+                Failure::Parse,
+                format_args!(
+                    "`{shown}` is not supported by the paged backend; use --backend memory"
+                ),
+            )?;
+        }
+        cmd if cmd.is_read_only() => exec_basic_read(out, &cmd, store)?,
+        cmd => exec_basic_write(out, cmd, store)?,
+    }
+    Ok(true)
+}
+
+// ---- `--listen`：多客户端 TCP 服务 ----
+//
+// 协议就是 REPL 本身：客户端每发一行命令，服务端回命令输出，再回一个提示符 `sms#<n>> `
+// （n 是连接编号）。所有连接共享同一个 `Arc<RwLock<StudentStore>>`：
+// 查询类命令拿读锁并发执行，修改类命令拿写锁串行执行。
+
+// 默认最多同时服务多少个连接；超出的连接收到一行错误（HTTP 下是 503）后直接断开。
+const DEFAULT_MAX_CONNS: usize = 8;
+// accept 轮询间隔：监听 socket 是非阻塞的，每隔这么久看一次是否要关停。
+const ACCEPT_POLL: Duration = Duration::from_millis(20);
+
+// 连接上跑什么协议：`--listen` 是逐行 REPL，`--http` 是 REST API。
+// accept 循环、连接上限和关停两者共用。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Repl,
+    Http,
+}
+
+struct Server {
+    listener: TcpListener,
+    store: Arc<RwLock<StudentStore>>,
+    max_conns: usize,
+    protocol: Protocol,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl Server {
+    fn bind(
+        addr: &str,
+        store: Arc<RwLock<StudentStore>>,
+        max_conns: usize,
+        protocol: Protocol,
+    ) -> io::Result<Server> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server {
+            listener,
+            store,
+            max_conns,
+            protocol,
+            shutdown: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // 置位后 `run` 不再接新连接，通知并断开已有连接，等所有连接线程结束后返回。
+    fn shutdown_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.shutdown)
+    }
+
+    fn run(self) -> io::Result<()> {
+        // 每个连接记下一份 socket 句柄，关停时用它唤醒阻塞在 read 上的连接线程。
+        let mut conns: Vec<(TcpStream, thread::JoinHandle<()>)> = Vec::new();
+        let mut next_conn = 1;
+
+        while !self.shutdown.load(Ordering::SeqCst) {
+            conns.retain(|(_, handle)| !handle.is_finished());
+
+            let mut stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_POLL);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            // 有的平台上 accept 出来的 socket 会继承非阻塞标志。
+            stream.set_nonblocking(false)?;
+
+            if conns.len() >= self.max_conns {
+                let message = format!("server busy ({} connections max)", self.max_conns);
+                let _ = match self.protocol {
+                    Protocol::Repl => writeln!(stream, "error: {message}"),
+                    Protocol::Http => error_response(503, &message).write_to(&mut stream),
+                };
+                continue;
+            }
+
+            let conn_id = next_conn;
+            next_conn += 1;
+            let handle_copy = stream.try_clone()?;
+            let store = Arc::clone(&self.store);
+            let shutdown = Arc::clone(&self.shutdown);
+            let protocol = self.protocol;
+            let handle = thread::spawn(move || {
+                // 客户端中途断开只影响这一个连接，不用报给服务端。
+                let _ = match protocol {
+                    Protocol::Repl => serve_connection(conn_id, stream, &store, &shutdown),
+                    Protocol::Http => serve_http(stream, &store),
+                };
+            });
+            conns.push((handle_copy, handle));
+        }
+
+        // 只关读方向：连接线程的 read 立即返回 EOF，它还能把告别信息写回去。
+        for (stream, _) in &conns {
+            let _ = stream.shutdown(Shutdown::Read);
+        }
+        for (_, handle) in conns {
+            let _ = handle.join();
+        }
+        Ok(())
+    }
+}
+
+fn serve_connection(
+    conn_id: usize,
+    stream: TcpStream,
+    store: &RwLock<StudentStore>,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    // 命令输出和提示符攒成一次 write 发出去，避免小包被 Nagle 算法拖慢。
+    let mut writer = BufWriter::new(stream);
+    writeln!(writer, "student-cli demo (connection #{conn_id})")?;
+    writeln!(writer, "type `help` to see commands")?;
+    let mut format = OutputFormat::Table;
+
+    loop {
+        write!(writer, "sms#{conn_id}> ")?;
+        writer.flush()?;
+
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            if shutdown.load(Ordering::SeqCst) {
+                writeln!(writer)?;
+                writeln!(writer, "server shutting down")?;
+            }
+            break;
+        }
+
+        // 先把输出攒在内存里，放掉锁之后再写 socket：
+        // 慢客户端不会因为迟迟不读而一直占着锁。
+        let mut out = Vec::new();
+        let mut reply = Reply::new(&mut out, format);
+        let line = line.trim();
+        let keep_going = match Command::parse(line) {
+            Ok(cmd) => execute_shared(&mut reply, cmd, store)?,
+            Err(e) => {
+                report_command_error(&mut reply, line, &e)?;
+                true
+            }
+        };
+        format = reply.format;
+        writer.write_all(&out)?;
+        if !keep_going {
+            break;
+        }
+    }
+
+    writeln!(writer, "bye")?;
+    writer.flush()
+}
+
+// 服务模式下所有连接共用一把锁。某个线程持锁时 panic 会毒化它；直接 expect 的话，
+// 之后每个连接都会跟着 panic，服务整个瘫掉。所以拿写锁时清掉毒化标记，
+// 再 repair 一遍：主存是唯一的真相，半途而废的修改最多让索引和主存对不上，repair 能重建。
+fn write_store(store: &RwLock<StudentStore>) -> RwLockWriteGuard<'_, StudentStore> {
+    store.write().unwrap_or_else(|poisoned| {
+        store.clear_poison();
+        let mut guard = poisoned.into_inner();
+        match guard.repair() {
+            Ok(found) => eprintln!(
+                "warning: a connection panicked while holding the store; repaired {} problems",
+                found.len()
+            ),
+            Err(e) => eprintln!("warning: a connection panicked while holding the store: {e}"),
+        }
+        guard
+    })
+}
+
+// 读锁没法修数据：发现被毒化就先走一遍 write_store 收拾好，再重新拿读锁。
+fn read_store(store: &RwLock<StudentStore>) -> RwLockReadGuard<'_, StudentStore> {
+    if store.is_poisoned() {
+        drop(write_store(store));
+    }
+    store.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+// `--listen` 模式下执行一条命令。返回值同 execute。
+fn execute_shared(out: &mut Reply, cmd: Command, store: &RwLock<StudentStore>) -> io::Result<bool> {
+    match cmd {
+        Command::Help => print_help(out)?,
+        Command::Format(format) => set_format(out, format)?,
+        Command::Quit(_) => return Ok(false),
+        // 事务状态和 undo 历史都挂在 store 上，是全局的：一个连接 begin 之后，别的连接的修改
+        // 也会被卷进去；undo 撤的是全局最近一步，可能悄悄撤掉别人的修改。所以服务模式下都不提供。
+        // 路径是客户端给的，文件却在服务端读写：远端客户端不能借此读写服务器上的任意文件。
+        // 落盘由服务端自己负责（`--data` 的 WAL，关停时 checkpoint）。
+        Command::Begin
+        | Command::Commit
+        | Command::Rollback
+        | Command::Undo
+        | Command::Redo
+        | Command::Save(_)
+        | Command::Load(_)
+        | Command::Checkpoint
+        | Command::Import { .. }
+        | Command::Export(_) => out.error(
+            Failure::Other,
+            format_args!("`{}` is not supported in server mode", cmd.name()),
+        )?,
+        cmd if cmd.is_read_only() => {
+            let store = read_store(store);
+            exec_read(out, &cmd, &store)?;
+        }
+        cmd => {
+            let mut store = write_store(store);
+            exec_write(out, cmd, &mut store)?;
+        }
+    }
+    Ok(true)
+}
+
+// ---- `--http`：REST API ----
+//
+// 每个连接只处理一个请求（响应带 `Connection: close`），路由：
+//   GET    /students[?name=&order=&dir=&limit=&offset=]   列表（可过滤、排序、分页）
+//   POST   /students                                      新增，201 + Location
+//   GET    /students/{id}                                 单条，不存在 404
+//   PUT    /students/{id}                                 整条修改，不存在 404
+//   DELETE /students/{id}                                 删除，204；不存在 404
+// 请求 / 响应体都是 JSON，学生对象形如 `{"id":1,"name":"alice","age":12,"class":"A3"}`。
+
+// 请求体最多多少字节；一条学生记录的 JSON 远小于这个数。
+const MAX_HTTP_BODY: usize = 64 * 1024;
+
+fn serve_http(stream: TcpStream, store: &RwLock<StudentStore>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let response = match Request::read_from(&mut reader, MAX_HTTP_BODY) {
+        Ok(Some(req)) => route(&req, store),
+        // 没发请求就断开了（包括关停时被唤醒）。
+        Ok(None) => return Ok(()),
+        Err(HttpError::Io(e)) => return Err(e),
+        Err(e) => error_response(e.status().unwrap_or(400), &e.to_string()),
+    };
+    response.write_to(&mut writer)
+}
+
+fn error_response(status: u16, message: &str) -> Response {
+    Response::json(
+        status,
+        &Json::Object(vec![("error".into(), message.into())]),
+    )
+}
+
+fn store_error_response(e: StoreError) -> Response {
+    let status = match e {
+        StoreError::NotFound(_) | StoreError::AttrNotFound { .. } => 404,
+        StoreError::FieldTooLong { .. } | StoreError::UnknownClass(_) => 400,
+        _ => 500,
+    };
+    error_response(status, &e.to_string())
+}
+
+// 有自定义属性时多一个 `attrs` 对象，值按类型写成 JSON 数字 / 布尔 / 字符串；
+// 没有属性的记录保持原来的四个字段。
+fn student_json(s: &Student) -> Json {
+    let mut fields = vec![
+        ("id".into(), s.id.into()),
+        ("name".into(), s.name.as_str().into()),
+        ("age".into(), s.age.into()),
+        ("class".into(), s.class_name.as_str().into()),
+    ];
+    if !s.attrs.is_empty() {
+        let attrs = s.attrs.iter().map(|(key, value)| {
+            let value = match value {
+                AttrValue::Int(n) => Json::Int(*n),
+                AttrValue::Float(x) => Json::Number(*x),
+                AttrValue::Bool(b) => Json::Bool(*b),
+                AttrValue::Text(text) => text.as_str().into(),
+            };
+            (key.clone(), value)
+        });
+        fields.push(("attrs".into(), Json::Object(attrs.collect())));
+    }
+    Json::Object(fields)
+}
+
+fn route(req: &Request, store: &RwLock<StudentStore>) -> Response {
+    let not_found = || error_response(404, &format!("no route for `{}`", req.path));
+    let rest = match req.path.strip_prefix("/students") {
+        Some(rest) => rest,
+        None => return not_found(),
+    };
+    if rest.is_empty() {
+        return match req.method.as_str() {
+            "GET" => list_students(req, &read_store(store)),
+            "POST" => create_student(req, &mut write_store(store)),
+            _ => error_response(405, "method not allowed").with_header("Allow", "GET, POST"),
+        };
+    }
+
+    let raw_id = match rest.strip_prefix('/').filter(|r| !r.contains('/')) {
+        Some(v) => v,
+        None => return not_found(),
+    };
+    let id = match raw_id.parse::<u32>() {
+        Ok(v) => v,
+        Err(_) => return error_response(400, &format!("invalid id `{raw_id}`")),
+    };
+    match req.method.as_str() {
+        "GET" => match read_store(store).get_by_id(id) {
+            Some(s) => Response::json(200, &student_json(s)),
+            None => store_error_response(StoreError::NotFound(id)),
+        },
+        "PUT" => {
+            let (name, age, class_name) = match parse_student_body(&req.body) {
+                Ok(v) => v,
+                Err(e) => return error_response(400, &e),
+            };
+            let mut store = write_store(store);
+            match store.modify(id, &name, age, &class_name) {
+                Ok(()) => Response::json(
+                    200,
+                    &student_json(store.get_by_id(id).expect("just modified")),
+                ),
+                Err(e) => store_error_response(e),
+            }
+        }
+        "DELETE" => match write_store(store).remove(id) {
+            Ok(()) => Response::new(204),
+            Err(e) => store_error_response(e),
+        },
+        _ => error_response(405, "method not allowed").with_header("Allow", "GET, PUT, DELETE"),
+    }
+}
+
+// 查询参数组装成一条 `Query`，交给和 `query` 命令同一个规划器执行（`name` 会走名字索引）。
+fn list_students(req: &Request, store: &StudentStore) -> Response {
+    let mut query = Query::default();
+    for (key, value) in &req.query {
+        let parsed = match key.as_str() {
+            "name" => {
+                let name = Value::Text(value.clone());
+                query.filter = Some(Expr::Cmp(SortField::Name, CmpOp::Eq, name));
+                Ok(())
+            }
+            "order" => value
+                .parse::<SortField>()
+                .map(|field| {
+                    let direction = query.order.first().map_or(SortDirection::Asc, |o| o.1);
+                    query.order = vec![(field, direction)];
+                })
+                .map_err(|e| e.to_string()),
+            "dir" => value
+                .parse::<SortDirection>()
+                .map(|direction| {
+                    // `dir` 可以写在 `order` 前后任意位置；没有 `order` 时按 id 排。
+                    match query.order.first_mut() {
+                        Some(o) => o.1 = direction,
+                        None => query.order = vec![(SortField::Id, direction)],
+                    }
+                })
+                .map_err(|e| e.to_string()),
+            "limit" | "offset" => match value.parse::<usize>() {
+                Ok(n) if key == "limit" => {
+                    query.limit = Some(n);
+                    Ok(())
+                }
+                Ok(n) => {
+                    query.offset = n;
+                    Ok(())
+                }
+                Err(_) => Err(format!("invalid {key} `{value}`")),
+            },
+            _ => Err(format!("unknown query parameter `{key}`")),
+        };
+        if let Err(e) = parsed {
+            return error_response(400, &e);
+        }
+    }
+    let rows = run_query(store, &query);
+    Response::json(
+        200,
+        &Json::Array(rows.into_iter().map(student_json).collect()),
+    )
+}
+
+fn create_student(req: &Request, store: &mut StudentStore) -> Response {
+    let (name, age, class_name) = match parse_student_body(&req.body) {
+        Ok(v) => v,
+        Err(e) => return error_response(400, &e),
+    };
+    match store.add(&name, age, &class_name) {
+        Ok(id) => {
+            let created = Student {
+                id,
+                name,
+                age,
+                class_name,
+                attrs: Attrs::new(),
+            };
+            Response::json(201, &student_json(&created))
+                .with_header("Location", &format!("/students/{id}"))
+        }
+        Err(e) => store_error_response(e),
+    }
+}
+
+// 请求体 `{"name": .., "age": .., "class": ..}`：三个字段都必填，多出来的字段当作拼写错误拒绝。
+fn parse_student_body(body: &[u8]) -> Result<(String, u8, String), String> {
+    let text = std::str::from_utf8(body).map_err(|_| "body is not UTF-8".to_string())?;
+    let json = Json::parse(text).map_err(|e| format!("invalid JSON {e}"))?;
+    let fields = match &json {
+        Json::Object(fields) => fields,
+        _ => return Err("body must be a JSON object".to_string()),
+    };
+    if let Some((key, _)) = fields
+        .iter()
+        .find(|(k, _)| !matches!(k.as_str(), "name" | "age" | "class"))
+    {
+        return Err(format!("unknown field `{key}`"));
+    }
+    let text_field = |key: &str| match json.get(key) {
+        Some(Json::String(v)) if !v.is_empty() => Ok(v.clone()),
+        Some(v) => Err(format!("invalid {key} `{v}`")),
+        None => Err(format!("missing field `{key}`")),
+    };
+    let name = text_field("name")?;
+    let class_name = text_field("class")?;
+    let age = match json.get("age") {
+        Some(v) => v
+            .as_u64()
+            .and_then(|n| u8::try_from(n).ok())
+            .ok_or_else(|| format!("invalid age `{v}`"))?,
+        None => return Err("missing field `age`".to_string()),
+    };
+    Ok((name, age, class_name))
+}
+
+// 服务端主线程：后台跑 accept 循环，前台读 stdin，`quit` / `exit` 或 EOF 时关停。
+fn run_server(
+    addr: &str,
+    store: StudentStore,
+    max_conns: usize,
+    protocol: Protocol,
+) -> io::Result<()> {
+    let store = Arc::new(RwLock::new(store));
+    let server = match Server::bind(addr, Arc::clone(&store), max_conns, protocol) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("error: listen on {addr} failed: {e}");
+            process::exit(1);
+        }
+    };
+    let scheme = match protocol {
+        Protocol::Repl => "",
+        Protocol::Http => "http://",
+    };
+    println!(
+        "listening on {scheme}{} (max {max_conns} connections)",
+        server.local_addr()?
+    );
+    println!("type `quit` to stop the server");
+
+    let shutdown = server.shutdown_flag();
+    let accept_thread = thread::spawn(move || server.run());
+
+    let stdin = io::stdin();
+    loop {
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            break;
+        }
+        match line.trim() {
+            "quit" | "exit" => break,
+            "" => {}
+            _ => println!("server console only understands `quit`"),
+        }
+    }
+
+    println!("shutting down...");
+    shutdown.store(true, Ordering::SeqCst);
+    match accept_thread.join() {
+        Ok(result) => result?,
+        Err(_) => eprintln!("error: accept thread panicked"),
+    }
+
+    let mut store = write_store(&store);
+    if store.is_persistent()
+        && let Err(e) = store.checkpoint()
+    {
+        eprintln!("error: checkpoint failed: {e}");
+    }
+    println!("bye");
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum BackendKind {
+    #[default]
+    Memory,
+    Paged,
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    data_path: Option<PathBuf>,
+    history_limit: Option<usize>,
+    grade_bands: Option<GradeBands>,
+    backend: BackendKind,
+    listen: Option<(Protocol, String)>,
+    max_conns: Option<usize>,
+    script: Option<PathBuf>,
+    strict: bool,
+    format: OutputFormat,
+    history_file: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data" => {
+                let path = args.next().ok_or("`--data` needs a <path>")?;
+                opts.data_path = Some(PathBuf::from(path));
+            }
+            "--history" => {
+                let raw = args.next().ok_or("`--history` needs a <n>")?;
+                let n = raw
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid history depth `{raw}`"))?;
+                opts.history_limit = Some(n);
+            }
+            "--grade-bands" => {
+                let raw = args.next().ok_or("`--grade-bands` needs a <spec>")?;
+                let bands = raw.parse::<GradeBands>().map_err(|e| e.to_string())?;
+                opts.grade_bands = Some(bands);
+            }
+            "--backend" => {
+                let raw = args.next().ok_or("`--backend` needs <memory|paged>")?;
+                opts.backend = match raw.as_str() {
+                    "memory" => BackendKind::Memory,
+                    "paged" => BackendKind::Paged,
+                    _ => return Err(format!("invalid backend `{raw}`")),
+                };
+            }
+            "--listen" | "--http" => {
+                let addr = args
+                    .next()
+                    .ok_or_else(|| format!("`{arg}` needs an <addr>"))?;
+                if opts.listen.is_some() {
+                    return Err("`--listen` and `--http` cannot be combined".to_string());
+                }
+                let protocol = if arg == "--listen" {
+                    Protocol::Repl
+                } else {
+                    Protocol::Http
+                };
+                opts.listen = Some((protocol, addr));
+            }
+            "--max-conns" => {
+                let raw = args.next().ok_or("`--max-conns` needs a <n>")?;
+                let n = raw
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("invalid connection limit `{raw}`"))?;
+                opts.max_conns = Some(n);
+            }
+            "--script" => {
+                let path = args.next().ok_or("`--script` needs a <file>")?;
+                opts.script = Some(PathBuf::from(path));
+            }
+            "--strict" => opts.strict = true,
+            "--history-file" => {
+                let path = args.next().ok_or("`--history-file` needs a <path>")?;
+                opts.history_file = Some(PathBuf::from(path));
+            }
+            "--format" => {
+                let raw = args.next().ok_or("`--format` needs <table|json|csv|tsv>")?;
+                opts.format = OutputFormat::from_name(&raw)
+                    .ok_or_else(|| format!("invalid format `{raw}`"))?;
+            }
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+    if opts.script.is_some() && opts.listen.is_some() {
+        return Err("`--script` cannot be combined with `--listen` / `--http`".to_string());
+    }
+    // 服务模式下格式按连接各自用 `format` 命令选；HTTP 永远是 JSON。
+    if opts.format != OutputFormat::Table && opts.listen.is_some() {
+        return Err("`--format` cannot be combined with `--listen` / `--http`".to_string());
+    }
+    if opts.max_conns.is_some() && opts.listen.is_none() {
+        return Err("`--max-conns` needs `--listen <addr>` or `--http <addr>`".to_string());
+    }
+    if opts.backend == BackendKind::Paged {
+        if opts.data_path.is_none() {
+            return Err("`--backend paged` needs `--data <path>`".to_string());
+        }
+        if opts.history_limit.is_some() {
+            return Err("`--history` is not supported by `--backend paged`".to_string());
+        }
+        if opts.grade_bands.is_some() {
+            return Err("`--grade-bands` is not supported by `--backend paged`".to_string());
+        }
+        if opts.listen.is_some() {
+            return Err("`--listen` / `--http` are not supported by `--backend paged`".to_string());
+        }
+    }
+    Ok(opts)
+}
+
+// ---- 终端 / 脚本会话 ----
+
+// 一个后端在 stdin 或脚本上的会话：给出提示符，处理一行命令。
+trait Session {
+    fn prompt(&self) -> &'static str;
+
+    // Tab 补全学生名字 / 属性 key 时读这里。
+    fn repository(&self) -> &dyn StudentRepository;
+
+    // Tab 补全班级时的候选，按名字排序。
+    fn class_names(&self) -> Vec<String>;
+
+    // 返回值同 execute：Ok(false) 表示退出。
+    fn handle(&mut self, out: &mut Reply, cmd: Command) -> io::Result<bool>;
+}
+
+struct MemorySession {
+    store: StudentStore,
+    quit_warned: bool,
+}
+
+impl Session for MemorySession {
+    fn prompt(&self) -> &'static str {
+        if self.store.in_transaction() {
+            "sms(txn)> "
+        } else {
+            "sms> "
+        }
+    }
+
+    fn repository(&self) -> &dyn StudentRepository {
+        &self.store
+    }
+
+    // 班级表里的全部班级，包括还没有学生的。
+    fn class_names(&self) -> Vec<String> {
+        let classes = self.store.classes();
+        classes
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    fn handle(&mut self, out: &mut Reply, cmd: Command) -> io::Result<bool> {
+        // 事务结束后，下一个事务重新要求确认一次。
+        if !self.store.in_transaction() {
+            self.quit_warned = false;
+        }
+        execute(out, cmd, &mut self.store, &mut self.quit_warned)
+    }
+}
+
+impl Session for PagedStore {
+    fn prompt(&self) -> &'static str {
+        "sms(paged)> "
+    }
+
+    fn repository(&self) -> &dyn StudentRepository {
+        self
+    }
+
+    // 分页后端没有班级表，班级就是记录里出现过的值；补全只是辅助，读失败时没有候选。
+    fn class_names(&self) -> Vec<String> {
+        let rows = StudentRepository::list_by_id(self).unwrap_or_default();
+        let classes = rows.into_iter().map(|s| s.class_name);
+        classes.collect::<BTreeSet<_>>().into_iter().collect()
+    }
+
+    fn handle(&mut self, out: &mut Reply, cmd: Command) -> io::Result<bool> {
+        execute_paged(out, cmd, self)
+    }
+}
+
+// 所有命令名，Tab 补全第一个词时用。
+const COMMAND_NAMES: &[&str] = &[
+    "add",
+    "begin",
+    "check",
+    "checkpoint",
+    "class",
+    "commit",
+    "exit",
+    "export",
+    "format",
+    "grade",
+    "group",
+    "help",
+    "import",
+    "list",
+    "load",
+    "mod",
+    "order",
+    "query",
+    "quit",
+    "ranking",
+    "redo",
+    "remove",
+    "repair",
+    "report",
+    "rollback",
+    "save",
+    "search",
+    "set",
+    "stats",
+    "undo",
+    "unset",
+];
+
+// 交互会话的 Tab 补全：第一个词补命令名；之后按命令和参数位置补关键字
+// （search 种类、排序字段和方向、分组字段、格式），以及库里已有的学生名字、班级和属性 key。
+struct CommandCompleter<'a> {
+    session: &'a dyn Session,
+}
+
+impl CommandCompleter<'_> {
+    // 第 `index` 个参数（0 是命令名）可以填什么；`args` 是前面已经写完的参数。
+    fn candidates(&self, args: &[String], index: usize) -> Vec<String> {
+        let words = |list: &[&str]| list.iter().map(|w| w.to_string()).collect();
+        let command = args.first().map_or("", String::as_str);
+        let kind = args.get(1).map_or("", String::as_str);
+        match (command, index) {
+            (_, 0) => words(COMMAND_NAMES),
+            ("class", 1) => words(&["add", "remove", "list", "rename"]),
+            ("class", 2) if matches!(kind, "remove" | "rename") => self.classes(),
+            ("class", 3) if kind == "remove" => words(&["--force"]),
+            ("search", 1) => words(&["id", "name", "class", "age", "prefix", "fuzzy", "attr"]),
+            ("search", 2) if kind == "attr" => self.attr_keys(),
+            ("unset", 2) | ("set", 2) => self.attr_keys(),
+            ("search", 2) if matches!(kind, "name" | "prefix" | "fuzzy") => self.names(),
+            ("search", 2) if kind == "class" => self.classes(),
+            ("order", 1) => words(&["id", "name", "age", "class"]),
+            ("order", 2) => words(&["asc", "desc"]),
+            ("group", 1) => words(&["class", "age"]),
+            ("format", 1) => words(&["table", "json", "csv", "tsv"]),
+            ("import", 2) => words(&["atomic"]),
+            ("add", 1) | ("mod", 2) => self.names(),
+            ("add", 3) | ("mod", 4) => self.classes(),
+            _ => Vec::new(),
+        }
+    }
+
+    // 补全只是辅助，读库失败时就当没有候选。
+    fn names(&self) -> Vec<String> {
+        let rows = self.session.repository().list_by_id().unwrap_or_default();
+        let names = rows.into_iter().map(|s| s.name);
+        names.collect::<BTreeSet<_>>().into_iter().collect()
+    }
+
+    fn classes(&self) -> Vec<String> {
+        self.session.class_names()
+    }
+
+    fn attr_keys(&self) -> Vec<String> {
+        let rows = self.session.repository().list_by_id().unwrap_or_default();
+        let keys = rows.into_iter().flat_map(|s| s.attrs.into_keys());
+        keys.collect::<BTreeSet<_>>().into_iter().collect()
+    }
+}
+
+// `line` 里最后一个参数从哪个字节开始：在引号里或者紧跟 `\` 的空白不算分隔。
+fn last_word_start(line: &str) -> usize {
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (None, '\\') | (Some('"'), '\\') => {
+                chars.next();
+            }
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, c) if c.is_whitespace() => start = i + c.len_utf8(),
+            _ => {}
+        }
+    }
+    start
+}
+
+impl Completer for CommandCompleter<'_> {
+    fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let line = &line[..pos];
+        // query 后面是另一门语法，不补全。
+        if line.trim_start().starts_with("query ") {
+            return (pos, Vec::new());
+        }
+        let start = last_word_start(line);
+        let Ok(args) = tokenize(&line[..start]) else {
+            return (pos, Vec::new());
+        };
+        // 正在写的这个参数去掉引号 / 转义之后的值；引号还没闭合时去掉开头的引号。
+        let word = &line[start..];
+        let typed = match tokenize(word) {
+            Ok(mut tokens) if tokens.len() == 1 => tokens.remove(0),
+            _ => word.trim_start_matches(['\'', '"']).to_string(),
+        };
+        let candidates = self
+            .candidates(&args, args.len())
+            .into_iter()
+            .filter(|c| c.starts_with(&typed))
+            .map(|c| quote_arg(&c).into_owned())
+            .collect();
+        (start, candidates)
+    }
+}
+
+// 命令从哪来：`--script` 文件或 stdin。`interactive` 只在 stdin 是终端时为真，
+// 这时才打印横幅和提示符，并用行编辑器读命令（光标移动、历史、Tab 补全）；
+// 管道和脚本的输出里只有命令本身的结果。
+struct Input {
+    source: Source,
+    name: String,
+    interactive: bool,
+    strict: bool,
+    // 当前的列表格式：初值来自 `--format`，之后随 `format` 命令变化。
+    format: OutputFormat,
+}
+
+enum Source {
+    Reader(Box<dyn BufRead>),
+    Editor(LineEditor),
+}
+
+impl Input {
+    fn open(opts: &Options) -> Input {
+        let (source, name, interactive) = match &opts.script {
+            Some(path) => match File::open(path) {
+                Ok(file) => (
+                    Source::Reader(Box::new(BufReader::new(file))),
+                    path.display().to_string(),
+                    false,
+                ),
+                Err(e) => {
+                    eprintln!("error: open script {} failed: {e}", path.display());
+                    process::exit(Failure::Other.exit_code());
+                }
+            },
+            None if io::stdin().is_terminal() => (
+                Source::Editor(open_editor(opts)),
+                "<stdin>".to_string(),
+                true,
+            ),
+            None => (
+                Source::Reader(Box::new(io::stdin().lock())),
+                "<stdin>".to_string(),
+                false,
+            ),
+        };
+        Input {
+            source,
+            name,
+            interactive,
+            strict: opts.strict,
+            format: opts.format,
+        }
+    }
+
+    // 读一行命令（去掉换行符），EOF 时返回 None。
+    fn read_line(
+        &mut self,
+        prompt: &str,
+        completer: &dyn Completer,
+        out: &mut dyn Write,
+    ) -> io::Result<Option<String>> {
+        match &mut self.source {
+            Source::Reader(reader) => {
+                if self.interactive {
+                    write!(out, "{prompt}")?;
+                    out.flush()?;
+                }
+                let mut line = String::new();
+                if reader.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                Ok(Some(line))
+            }
+            Source::Editor(editor) => {
+                // 编辑器直接写终端，先把之前的输出刷出去。
+                out.flush()?;
+                let line = editor.read_line(prompt, completer)?;
+                if let Some(line) = &line
+                    && !line.trim_start().starts_with('#')
+                    && let Err(e) = editor.add_history(line)
+                {
+                    eprintln!("warning: history file not written: {e}");
+                }
+                Ok(line)
+            }
+        }
+    }
+}
+
+// `--history-file` 或 `$HOME/.sms_history`；历史文件读不了时只在内存里记历史。
+fn open_editor(opts: &Options) -> LineEditor {
+    let path = opts
+        .history_file
+        .clone()
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".sms_history")));
+    let Some(path) = path else {
+        return LineEditor::new();
+    };
+    LineEditor::with_history_file(&path).unwrap_or_else(|e| {
+        eprintln!("warning: history file {} not loaded: {e}", path.display());
+        LineEditor::new()
+    })
+}
+
+// 逐行执行命令，直到 quit / EOF，或者非交互 + `--strict` 时第一条失败的命令。
+// 返回第一条失败命令的类别。
+fn run_session(
+    session: &mut dyn Session,
+    input: &mut Input,
+    out: &mut dyn Write,
+) -> io::Result<Option<Failure>> {
+    let mut first_failure = None;
+    let mut line_no = 0;
+    loop {
+        let completer = CommandCompleter { session: &*session };
+        let Some(line) = input.read_line(session.prompt(), &completer, out)? else {
+            // EOF（如 Ctrl-D）时退出。
+            if input.interactive {
+                writeln!(out)?;
+            }
+            break;
+        };
+        line_no += 1;
+        let line = line.trim();
+        // 只有整行注释：名字里可以有 `#`，行内的 `#` 照常当参数。
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut reply = Reply::new(out, input.format);
+        let keep_going = match Command::parse(line) {
+            Ok(cmd) => session.handle(&mut reply, cmd)?,
+            Err(e) => {
+                report_command_error(&mut reply, line, &e)?;
+                true
+            }
+        };
+        input.format = reply.format;
+        if let Some(failure) = reply.failure {
+            first_failure.get_or_insert(failure);
+            if input.strict && !input.interactive {
+                eprintln!(
+                    "{}:{line_no}: command failed, stopping (--strict)",
+                    input.name
+                );
+                break;
+            }
+        }
+        if !keep_going {
+            break;
+        }
+    }
+    Ok(first_failure)
+}
+
+fn main() -> io::Result<()> {
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("error: {e}");
+            eprintln!(
+                "usage: 19_demo [--backend <memory|paged>] [--data <path>] [--history <n>] [--grade-bands <spec>] [--listen <addr> | --http <addr>] [--max-conns <n>] [--script <file>] [--strict] [--format <table|json|csv|tsv>] [--history-file <path>]"
+            );
+            process::exit(2);
+        }
+    };
+
+    let failure = if opts.backend == BackendKind::Paged {
+        // parse_args 已经保证 paged 一定带了 --data。
+        let path = opts.data_path.as_deref().expect("checked in parse_args");
+        let mut store = match PagedStore::open(path) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("error: open {} failed: {e}", path.display());
+                process::exit(1);
+            }
+        };
+        let mut input = Input::open(&opts);
+        if input.interactive {
+            println!("student-cli demo (paged backend)");
+            println!("type `help` to see commands");
+        }
+        // 分页后端每条命令直接写文件，退出时没有要回滚或 checkpoint 的东西。
+        let failure = run_session(&mut store, &mut input, &mut io::stdout())?;
+        if input.interactive {
+            println!("bye");
+            return Ok(());
+        }
+        failure
+    } else {
+        // 默认是内存态：不带 `--data` 时不落盘，退出后数据清空。
+        let mut store = match &opts.data_path {
+            Some(path) => match StudentStore::open(path) {
+                Ok(v) => v,
+                Err(e) => {
+                    // 损坏的数据文件不能继续用：否则后续 checkpoint 会覆盖掉原文件。
+                    eprintln!("error: open {} failed: {e}", path.display());
+                    process::exit(1);
+                }
+            },
+            None => StudentStore::new(),
+        };
+        if let Some(n) = opts.history_limit {
+            store.set_history_limit(n);
+        }
+        if let Some(bands) = &opts.grade_bands {
+            store.set_grade_bands(bands.clone());
+        }
+
+        if let Some((protocol, addr)) = &opts.listen {
+            let max_conns = opts.max_conns.unwrap_or(DEFAULT_MAX_CONNS);
+            return run_server(addr, store, max_conns, *protocol);
+        }
+
+        let mut input = Input::open(&opts);
+        if input.interactive {
+            println!("student-cli demo");
+            println!("type `help` to see commands");
+        }
+        let mut session = MemorySession {
+            store,
+            quit_warned: false,
+        };
+        let mut failure = run_session(&mut session, &mut input, &mut io::stdout())?;
+        let store = &mut session.store;
+
+        // 未提交的事务不能进快照：先回滚，再 checkpoint。
+        if let Ok(n) = store.rollback() {
+            eprintln!("warning: rolled back {n} uncommitted changes");
+        }
+
+        // 正常退出时顺手 checkpoint；即使这里失败，日志里也已经有全部修改。
+        if store.is_persistent()
+            && let Err(e) = store.checkpoint()
+        {
+            eprintln!("error: checkpoint failed: {e}");
+            failure.get_or_insert(Failure::Other);
+        }
+
+        if input.interactive {
+            println!("bye");
+            return Ok(());
+        }
+        failure
+    };
+
+    // 非交互模式：有命令失败时按第一条失败的类别退出，脚本可以据此判断。
+    if let Some(failure) = failure {
+        process::exit(failure.exit_code());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ClassCmd, Command, CommandCompleter, CommandError, Failure, Input, MemorySession,
+        OutputFormat, Protocol, Search, Server, Source, StudentStore, quote_arg, read_store,
+        run_session, tokenize, write_store,
+    };
+    use rust_notes::editor::Completer;
+    use rust_notes::student::{
+        AttrValue, DEFAULT_FUZZY_DISTANCE, GroupField, QueryParser, SortDirection, SortField,
+    };
+    use std::io::{BufRead, BufReader, Cursor, Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::ops::Bound;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+
+    // 回环地址上的测试客户端：发一行，读到下一个提示符为止。
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Client {
+            let writer = TcpStream::connect(addr).unwrap();
+            let reader = BufReader::new(writer.try_clone().unwrap());
+            Client { reader, writer }
+        }
+
+        // 提示符形如 `sms#3> `，命令输出里不会出现 "> " 结尾。
+        fn read_until_prompt(&mut self) -> String {
+            let mut buf = Vec::new();
+            loop {
+                let n = self.reader.read_until(b' ', &mut buf).unwrap();
+                if n == 0 || buf.ends_with(b"> ") {
+                    return String::from_utf8(buf).unwrap();
+                }
+            }
+        }
+
+        fn send(&mut self, line: &str) -> String {
+            // 一次 write 发完整行：writeln! 会分两次写，小包会被 Nagle 算法拖慢。
+            self.writer
+                .write_all(format!("{line}\n").as_bytes())
+                .unwrap();
+            self.read_until_prompt()
+        }
+
+        fn read_rest(&mut self) -> String {
+            let mut rest = String::new();
+            self.reader.read_to_string(&mut rest).unwrap();
+            rest
+        }
+    }
+
+    fn start(
+        max_conns: usize,
+        protocol: Protocol,
+    ) -> (SocketAddr, Arc<AtomicBool>, thread::JoinHandle<()>) {
+        let mut store = StudentStore::new();
+        for class in ["A1", "A2", "A3", "B1"] {
+            store.add_class(class).unwrap();
+        }
+        let store = Arc::new(RwLock::new(store));
+        let server = Server::bind("127.0.0.1:0", store, max_conns, protocol).unwrap();
+        let addr = server.local_addr().unwrap();
+        let shutdown = server.shutdown_flag();
+        let handle = thread::spawn(move || server.run().unwrap());
+        (addr, shutdown, handle)
+    }
+
+    #[test]
+    fn test_clients_share_one_store() {
+        let (addr, shutdown, handle) = start(8, Protocol::Repl);
+        let mut a = Client::connect(addr);
+        let mut b = Client::connect(addr);
+        assert!(a.read_until_prompt().ends_with("sms#1> "));
+        assert!(b.read_until_prompt().ends_with("sms#2> "));
+
+        assert!(a.send("add alice 12 A3").starts_with("ok: added id=1"));
+        assert!(b.send("search name alice").contains("alice  12"));
+        assert!(
+            b.send("begin")
+                .starts_with("error: `begin` is not supported in server mode")
+        );
+        // undo 历史是全局的：b 不能撤掉 a 刚加的 alice。
+        assert!(
+            b.send("undo")
+                .starts_with("error: `undo` is not supported in server mode")
+        );
+        assert!(
+            b.send("redo")
+                .starts_with("error: `redo` is not supported in server mode")
+        );
+        assert!(a.send("search id 1").contains("alice"));
+        assert!(b.send("quit").starts_with("bye"));
+
+        // 并发 add：写锁保证 id 不重复、不丢。
+        let workers = (0..4)
+            .map(|_| {
+                thread::spawn(move || {
+                    let mut c = Client::connect(addr);
+                    c.read_until_prompt();
+                    for i in 0..25 {
+                        assert!(c.send(&format!("add s{i} 10 B1")).starts_with("ok: added"));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for w in workers {
+            w.join().unwrap();
+        }
+        let stats = a.send("stats");
+        assert!(stats.starts_with("total: 101\n"), "{stats}");
+        assert!(a.send("search id 101").contains("101"));
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_poisoned_store_lock_keeps_serving() {
+        let mut store = StudentStore::new();
+        store.add_class("A1").unwrap();
+        store.add("alice", 12, "A1").unwrap();
+        let store = RwLock::new(store);
+        let _ = thread::scope(|s| {
+            s.spawn(|| {
+                let _guard = store.write().unwrap();
+                panic!("connection thread dies while holding the lock");
+            })
+            .join()
+        });
+        assert!(store.is_poisoned());
+
+        assert_eq!(read_store(&store).len(), 1);
+        assert!(!store.is_poisoned());
+        assert_eq!(write_store(&store).add("bob", 13, "A1").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_server_refuses_file_commands() {
+        let dir = TempDir::new("server_files");
+        let (addr, shutdown, handle) = start(8, Protocol::Repl);
+        let mut c = Client::connect(addr);
+        c.read_until_prompt();
+        assert!(c.send("add alice 12 A3").starts_with("ok: added id=1"));
+
+        for (cmd, file) in [
+            ("save", "pwned.sms"),
+            ("load", "pwned.sms"),
+            ("import", "pwned.csv"),
+            ("export", "pwned.csv"),
+        ] {
+            let path = dir.join(file);
+            let reply = c.send(&format!("{cmd} {}", path.display()));
+            let expected = format!("error: `{cmd}` is not supported in server mode");
+            assert!(reply.starts_with(&expected), "{reply}");
+            assert!(!path.exists(), "{cmd} touched {}", path.display());
+        }
+        let reply = c.send("checkpoint");
+        assert!(
+            reply.starts_with("error: `checkpoint` is not supported in server mode"),
+            "{reply}"
+        );
+        // 被拒绝的命令不影响连接和数据。
+        assert!(c.send("search id 1").contains("alice"));
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_connection_limit_and_shutdown() {
+        let (addr, shutdown, handle) = start(1, Protocol::Repl);
+        let mut a = Client::connect(addr);
+        a.read_until_prompt();
+
+        let mut b = Client::connect(addr);
+        assert_eq!(b.read_rest(), "error: server busy (1 connections max)\n");
+
+        // 关停时已连上的客户端收到通知并被断开，run 返回。
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+        assert_eq!(a.read_rest(), "\nserver shutting down\nbye\n");
+    }
+
+    // 发一个完整的 HTTP 请求，返回状态码、头部和响应体。
+    fn http(addr: SocketAddr, method: &str, target: &str, body: &str) -> (u16, String, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let request = format!(
+            "{method} {target} HTTP/1.1\r\nHost: test\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head[9..12].parse().unwrap();
+        (status, head.to_string(), body.trim_end().to_string())
+    }
+
+    #[test]
+    fn test_http_crud() {
+        let (addr, shutdown, handle) = start(8, Protocol::Http);
+        let (status, head, body) = http(
+            addr,
+            "POST",
+            "/students",
+            r#"{"name":"alice","age":12,"class":"A3"}"#,
+        );
+        assert_eq!(status, 201);
+        assert!(head.contains("Location: /students/1\r\n"));
+        assert_eq!(body, r#"{"id":1,"name":"alice","age":12,"class":"A3"}"#);
+        for body in [
+            r#"{"name":"bob","age":9,"class":"A1"}"#,
+            r#"{"name":"张 三","age":12,"class":"A3"}"#,
+        ] {
+            assert_eq!(http(addr, "POST", "/students", body).0, 201);
+        }
+
+        let (status, _, body) = http(addr, "GET", "/students/3", "");
+        assert_eq!(
+            (status, body.as_str()),
+            (200, r#"{"id":3,"name":"张 三","age":12,"class":"A3"}"#)
+        );
+        let (status, _, body) = http(addr, "GET", "/students?order=age&dir=desc&limit=2", "");
+        assert_eq!(status, 200);
+        assert!(
+            body.starts_with(r#"[{"id":1,"#) && body.contains(r#"{"id":3,"#),
+            "{body}"
+        );
+        let (_, _, body) = http(addr, "GET", "/students?name=%E5%BC%A0+%E4%B8%89", "");
+        assert!(
+            body.starts_with(r#"[{"id":3,"#) && !body.contains(r#""id":1"#),
+            "{body}"
+        );
+
+        let put = r#"{"name":"bobby","age":10,"class":"A2"}"#;
+        let (status, _, body) = http(addr, "PUT", "/students/2", put);
+        assert_eq!(
+            (status, body.as_str()),
+            (200, r#"{"id":2,"name":"bobby","age":10,"class":"A2"}"#)
+        );
+        assert_eq!(http(addr, "DELETE", "/students/2", "").0, 204);
+        let (status, _, body) = http(addr, "GET", "/students/2", "");
+        assert_eq!(
+            (status, body.as_str()),
+            (404, r#"{"error":"id=2 not found"}"#)
+        );
+        assert_eq!(http(addr, "PUT", "/students/2", put).0, 404);
+        let (status, _, body) = http(
+            addr,
+            "POST",
+            "/students",
+            r#"{"name":"carol","age":9,"class":"Z9"}"#,
+        );
+        assert_eq!(
+            (status, body.as_str()),
+            (400, r#"{"error":"class `Z9` does not exist"}"#)
+        );
+        assert_eq!(http(addr, "DELETE", "/students/2", "").0, 404);
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_http_rejects_bad_requests() {
+        let (addr, shutdown, handle) = start(8, Protocol::Http);
+        for (body, error) in [
+            (r#"{"name":"a","age":300,"class":"x"}"#, "invalid age `300`"),
+            (
+                r#"{"name":"a","age":"12","class":"x"}"#,
+                "invalid age `\\\"12\\\"`",
+            ),
+            (r#"{"name":"a","class":"x"}"#, "missing field `age`"),
+            (
+                r#"{"name":"a","age":1,"class":"x","x":1}"#,
+                "unknown field `x`",
+            ),
+            (
+                r#"{"name":"a""#,
+                "invalid JSON at byte 11: expected ',' or '}'",
+            ),
+        ] {
+            let (status, _, got) = http(addr, "POST", "/students", body);
+            assert_eq!(status, 400, "{body}");
+            assert_eq!(got, format!(r#"{{"error":"{error}"}}"#), "{body}");
+        }
+        assert_eq!(http(addr, "GET", "/students/abc", "").0, 400);
+        assert_eq!(http(addr, "GET", "/students?order=height", "").0, 400);
+        assert_eq!(http(addr, "GET", "/students?sort=age", "").0, 400);
+        assert_eq!(http(addr, "GET", "/teachers", "").0, 404);
+        assert_eq!(http(addr, "GET", "/students/1/x", "").0, 404);
+        let (status, head, _) = http(addr, "PATCH", "/students/1", "");
+        assert_eq!(status, 405);
+        assert!(head.contains("Allow: GET, PUT, DELETE\r\n"));
+        // 列表里什么都没加进去。
+        assert_eq!(http(addr, "GET", "/students", "").2, "[]");
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    fn run_script(script: &str, strict: bool) -> (Option<Failure>, String) {
+        let mut session = MemorySession {
+            store: StudentStore::new(),
+            quit_warned: false,
+        };
+        let mut input = Input {
+            source: Source::Reader(Box::new(Cursor::new(script.to_string()))),
+            name: "test.sms".to_string(),
+            interactive: false,
+            strict,
+            format: OutputFormat::Table,
+        };
+        let mut out = Vec::new();
+        let failure = run_session(&mut session, &mut input, &mut out).unwrap();
+        (failure, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_script_skips_comments_and_records_first_failure() {
+        let script = "# 注释\n\n  # 缩进的注释\nclass add A1\nadd bob#2 9 A1\nremove 9\nadd x y z\nsearch name bob#2\n";
+        let (failure, out) = run_script(script, false);
+        assert_eq!(failure, Some(Failure::NotFound));
+        assert!(!out.contains("sms>"));
+        assert!(out.starts_with(
+            "ok: class added A1\nok: added id=1\nerror: id=9 not found\nerror: invalid age `y`\n"
+        ));
+        assert!(out.ends_with("1   bob#2  9    A1\n"), "{out}");
+
+        let (failure, _) = run_script("add a 1\nremove 9\n", false);
+        assert_eq!(failure, Some(Failure::Parse));
+        let (failure, _) = run_script("undo\n", false);
+        assert_eq!(failure, Some(Failure::Other));
+        // 班级不存在不是 id 不存在：退出码 1 而不是 4。
+        let (failure, _) = run_script("add a 1 b\nsearch id 5\n", false);
+        assert_eq!(failure, Some(Failure::Other));
+        let (failure, _) = run_script("class add b\nadd a 1 b\nsearch id 5\nquit\nbogus\n", false);
+        assert_eq!(failure, None);
+    }
+
+    #[test]
+    fn test_format_switches_listing_output() {
+        let script = concat!(
+            "class add A,1\n",
+            "class add B2\n",
+            "add '\"x\"\\y' 9 A,1\n",
+            "add 张三 10 B2\n",
+            "format json\n",
+            "list\n",
+            "search id 9\n",
+            "format csv\n",
+            "order age desc\n",
+            "format tsv\n",
+            "search name '\"x\"\\y'\n",
+            "format\n",
+            "format xml\n",
+        );
+        let (failure, out) = run_script(script, false);
+        assert_eq!(failure, Some(Failure::Parse));
+        let expected = concat!(
+            "ok: class added A,1\n",
+            "ok: class added B2\n",
+            "ok: added id=1\n",
+            "ok: added id=2\n",
+            "ok: format json\n",
+            r#"[{"id":1,"name":"\"x\"\\y","age":9,"class":"A,1"},"#,
+            r#"{"id":2,"name":"张三","age":10,"class":"B2"}]"#,
+            "\n[]\n",
+            "ok: format csv\n",
+            "id,name,age,class\r\n",
+            "2,张三,10,B2\r\n",
+            "1,\"\"\"x\"\"\\y\",9,\"A,1\"\r\n",
+            "ok: format tsv\n",
+            "id\tname\tage\tclass\n",
+            "1\t\"x\"\\\\y\t9\tA,1\n",
+            "format: tsv\n",
+            "error: invalid format `xml`\n",
+        );
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_parse_every_command() {
+        let add = Command::Add {
+            name: "alice".to_string(),
+            age: 12,
+            class: "A3".to_string(),
+        };
+        let modify = Command::Modify {
+            id: 7,
+            name: "bob".to_string(),
+            age: 0,
+            class: "A1".to_string(),
+        };
+        let fuzzy = |max_dist| {
+            Command::Search(Search::Fuzzy {
+                name: "alise".to_string(),
+                max_dist,
+            })
+        };
+        let import = |atomic| Command::Import {
+            path: PathBuf::from("in.csv"),
+            atomic,
+        };
+        let remove_class = |force| {
+            Command::Class(ClassCmd::Remove {
+                name: "A3".to_string(),
+                force,
+            })
+        };
+        let cases = [
+            (
+                "class add 'Grade 3'",
+                Command::Class(ClassCmd::Add("Grade 3".to_string())),
+            ),
+            ("class remove A3", remove_class(false)),
+            ("class remove A3 --force", remove_class(true)),
+            ("class list", Command::Class(ClassCmd::List)),
+            (
+                "class rename A3 B3",
+                Command::Class(ClassCmd::Rename {
+                    from: "A3".to_string(),
+                    to: "B3".to_string(),
+                }),
+            ),
+            ("add alice 12 A3", add),
+            ("  mod 7 bob 0 A1  ", modify),
+            ("remove 7", Command::Remove(7)),
+            ("list", Command::List(None)),
+            (
+                "list 100..=200",
+                Command::List(Some((Bound::Included(100), Bound::Included(200)))),
+            ),
+            ("search id 3", Command::Search(Search::Id(3))),
+            (
+                "search name 张三",
+                Command::Search(Search::Name("张三".to_string())),
+            ),
+            (
+                "search class A1",
+                Command::Search(Search::Class("A1".to_string())),
+            ),
+            (
+                "search age 10..",
+                Command::Search(Search::Age((Bound::Included(10), Bound::Unbounded))),
+            ),
+            (
+                "search prefix al",
+                Command::Search(Search::Prefix("al".to_string())),
+            ),
+            ("search fuzzy alise", fuzzy(DEFAULT_FUZZY_DISTANCE)),
+            ("search fuzzy alise 1", fuzzy(1)),
+            (
+                "search attr score 9.5",
+                Command::Search(Search::Attr {
+                    key: "score".to_string(),
+                    value: AttrValue::Float(9.5),
+                }),
+            ),
+            (
+                "set 3 phone 0123",
+                Command::Set {
+                    id: 3,
+                    key: "phone".to_string(),
+                    value: AttrValue::Text("0123".to_string()),
+                },
+            ),
+            (
+                "unset 3 phone",
+                Command::Unset {
+                    id: 3,
+                    key: "phone".to_string(),
+                },
+            ),
+            (
+                "order class desc",
+                Command::Order(SortField::Class, SortDirection::Desc),
+            ),
+            ("stats", Command::Stats),
+            ("group age", Command::Group(GroupField::Age)),
+            (
+                "grade 3 'art history' 100",
+                Command::Grade {
+                    id: 3,
+                    course: "art history".to_string(),
+                    score: 100,
+                },
+            ),
+            ("report 3", Command::Report(3)),
+            ("ranking math", Command::Ranking("math".to_string())),
+            ("save a.sms", Command::Save(PathBuf::from("a.sms"))),
+            ("load a.sms", Command::Load(PathBuf::from("a.sms"))),
+            ("import in.csv", import(false)),
+            ("import in.csv atomic", import(true)),
+            ("export out.csv", Command::Export(PathBuf::from("out.csv"))),
+            ("checkpoint", Command::Checkpoint),
+            ("check", Command::Check),
+            ("repair", Command::Repair),
+            ("undo", Command::Undo),
+            ("redo", Command::Redo),
+            ("begin", Command::Begin),
+            ("commit", Command::Commit),
+            ("rollback", Command::Rollback),
+            ("format", Command::Format(None)),
+            ("format tsv", Command::Format(Some(OutputFormat::Tsv))),
+            ("help", Command::Help),
+            ("quit", Command::Quit("quit")),
+            ("exit now", Command::Quit("exit")),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(Command::parse(line), Ok(expected), "{line}");
+        }
+
+        // query 的列号相对整行，前导空白也算。
+        let query = QueryParser::parse(" where age > 3 limit 2", "query".len()).unwrap();
+        assert_eq!(
+            Command::parse("query where age > 3 limit 2"),
+            Ok(Command::Query(query))
+        );
+        match Command::parse("  query where age >> 3") {
+            Err(CommandError::Query(e)) => assert_eq!(e.column, 20),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_carry_the_offending_token() {
+        let invalid = |what, token: &str| CommandError::Invalid {
+            what,
+            token: token.to_string(),
+        };
+        let cases = [
+            ("   ", CommandError::Empty),
+            (
+                "frobnicate 1",
+                CommandError::Unknown("frobnicate".to_string()),
+            ),
+            ("add a 1", CommandError::Usage("add <name> <age> <class>")),
+            (
+                "class",
+                CommandError::Usage("class <add|remove|list|rename> ..."),
+            ),
+            ("class add", CommandError::Usage("class add <name>")),
+            (
+                "class remove A1 -f",
+                CommandError::Usage("class remove <name> [--force]"),
+            ),
+            ("class list x", CommandError::Usage("class list")),
+            (
+                "class rename A1",
+                CommandError::Usage("class rename <old> <new>"),
+            ),
+            ("class add ''", invalid("class", "")),
+            ("remove", CommandError::Usage("remove <id>")),
+            (
+                "mod 1 a 2",
+                CommandError::Usage("mod <id> <name> <age> <class>"),
+            ),
+            ("list 1 2", CommandError::Usage("list [<id range>]")),
+            (
+                "search",
+                CommandError::Usage("search <id|name|class|age|prefix|fuzzy|attr> <value>"),
+            ),
+            (
+                "search bogus x",
+                CommandError::Usage("search <id|name|class|age|prefix|fuzzy|attr> <value>"),
+            ),
+            ("search id 1 2", CommandError::Usage("search id <id>")),
+            (
+                "search fuzzy a 1 2",
+                CommandError::Usage("search fuzzy <name> [maxdist]"),
+            ),
+            (
+                "order id",
+                CommandError::Usage("order <id|name|age|class> <asc|desc>"),
+            ),
+            ("stats x", CommandError::Usage("stats")),
+            ("group", CommandError::Usage("group <class|age>")),
+            ("save", CommandError::Usage("save <path>")),
+            (
+                "import a b",
+                CommandError::Usage("import <file.csv> [atomic]"),
+            ),
+            ("undo 1", CommandError::Usage("undo")),
+            ("check all", CommandError::Usage("check")),
+            (
+                "format a b",
+                CommandError::Usage("format [table|json|csv|tsv]"),
+            ),
+            ("add a -1 b", invalid("age", "-1")),
+            ("add a 256 b", invalid("age", "256")),
+            ("remove x", invalid("id", "x")),
+            ("mod 1 a x b", invalid("age", "x")),
+            ("mod x a x b", invalid("id", "x")),
+            ("list 5..x", invalid("id range", "5..x")),
+            ("search id 1.5", invalid("id", "1.5")),
+            ("search age old", invalid("age range", "old")),
+            ("search fuzzy a far", invalid("maxdist", "far")),
+            ("order height asc", invalid("field", "height")),
+            ("order age up", invalid("direction", "up")),
+            ("group name", invalid("group field", "name")),
+            ("format xml", invalid("format", "xml")),
+            ("add \"\" 1 A1", invalid("name", "")),
+            ("mod 1 a 1 '\t'", invalid("class", "\t")),
+            (
+                "add 'Mary Ann 12 A1",
+                CommandError::Syntax {
+                    column: 5,
+                    message: "unterminated `'`",
+                },
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(Command::parse(line), Err(expected), "{line}");
+        }
+        assert_eq!(
+            Command::parse("order age up").unwrap_err().to_string(),
+            "invalid direction `up`"
+        );
+    }
+
+    #[test]
+    fn test_completion_of_commands_fields_and_names() {
+        let mut store = StudentStore::new();
+        for class in ["A1", "B2", "C 3"] {
+            store.add_class(class).unwrap();
+        }
+        store.add("Mary Ann", 12, "A1").unwrap();
+        store.add("Mark", 11, "B2").unwrap();
+        store.add("bob", 10, "A1").unwrap();
+        let session = MemorySession {
+            store,
+            quit_warned: false,
+        };
+        let completer = CommandCompleter { session: &session };
+        let complete = |line: &str| completer.complete(line, line.len());
+
+        assert_eq!(complete("sea"), (0, vec!["search".to_string()]));
+        assert_eq!(complete("se").1, ["search", "set"]);
+        assert_eq!(complete("re").1, ["redo", "remove", "repair", "report"]);
+        assert_eq!(complete("order a"), (6, vec!["age".to_string()]));
+        assert_eq!(complete("order age d").1, ["desc"]);
+        assert_eq!(complete("format ").1, ["table", "json", "csv", "tsv"]);
+        // 名字带空格时补成加引号的形式，已经开了引号也照样能补。
+        assert_eq!(complete("search name Ma").1, ["Mark", "\"Mary Ann\""]);
+        assert_eq!(complete("search prefix \"Mary").1, ["\"Mary Ann\""]);
+        assert_eq!(complete("search name Mary\\ A").1, ["\"Mary Ann\""]);
+        // 班级来自班级表，还没有学生的班级也能补。
+        assert_eq!(complete("mod 1 bob 12 ").1, ["A1", "B2", "\"C 3\""]);
+        assert_eq!(complete("class re").1, ["remove", "rename"]);
+        assert_eq!(complete("class remove C").1, ["\"C 3\""]);
+        assert_eq!(complete("class remove A1 ").1, ["--force"]);
+        assert_eq!(complete("class add ").1, Vec::<String>::new());
+        assert_eq!(complete("search age ").1, Vec::<String>::new());
+        assert_eq!(complete("query where na").1, Vec::<String>::new());
+        // 光标在行中间时只看光标前的部分。
+        assert_eq!(
+            completer.complete("lo 1..3", 2),
+            (0, vec!["load".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_quotes_and_escapes() {
+        let cases: [(&str, &[&str]); 7] = [
+            ("  add  bob 9\tA1 ", &["add", "bob", "9", "A1"]),
+            (
+                r#"add "Mary Ann" 12 'Grade 3'"#,
+                &["add", "Mary Ann", "12", "Grade 3"],
+            ),
+            (r#"Mary\ Ann Mary' 'Ann "Mary"' Ann'"#, &["Mary Ann"; 3]),
+            (r#"'' "" x"#, &["", "", "x"]),
+            (
+                r#"'a\"b' "a\"b\\c\d" a\'b"#,
+                &[r#"a\"b"#, r#"a"b\c\d"#, "a'b"],
+            ),
+            ("search name 张三", &["search", "name", "张三"]),
+            ("", &[]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(tokenize(line).unwrap(), expected, "{line}");
+        }
+
+        let syntax = |column, message| Err(CommandError::Syntax { column, message });
+        assert_eq!(tokenize(r#"add "Mary 1"#), syntax(5, "unterminated `\"`"));
+        assert_eq!(tokenize("张三 'x"), syntax(4, "unterminated `'`"));
+        assert_eq!(tokenize("a b\\"), syntax(4, "trailing `\\`"));
+
+        // quote_arg 的输出切回去还是同一个参数。
+        for raw in [
+            "plain",
+            "Mary Ann",
+            "",
+            r#"say "hi""#,
+            r"back\slash",
+            "it's",
+        ] {
+            assert_eq!(tokenize(&quote_arg(raw)).unwrap(), [raw], "{raw}");
+        }
+        assert_eq!(quote_arg("A1"), "A1");
+        assert_eq!(quote_arg("Mary Ann"), "\"Mary Ann\"");
+
+        let script = concat!(
+            "class add 'Grade 3'\n",
+            "add \"Mary Ann\" 12 'Grade 3'\n",
+            "search name \"Mary Ann\"\n",
+            "add bob 9 \"Grade 3\n",
+        );
+        let (failure, out) = run_script(script, false);
+        assert_eq!(failure, Some(Failure::Parse));
+        let expected = concat!(
+            "ok: class added \"Grade 3\"\n",
+            "ok: added id=1\n",
+            "id  name        age  class\n",
+            "1   \"Mary Ann\"  12   \"Grade 3\"\n",
+            "error: parse failed at column 11: unterminated `\"`\n",
+            "  add bob 9 \"Grade 3\n",
+            "            ^\n",
+        );
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_error_caret_lines_up_after_wide_chars() {
+        let script = "query where name = \"张三\" and\nadd 张三 x\"\n";
+        let (failure, out) = run_script(script, false);
+        assert_eq!(failure, Some(Failure::Parse));
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 6, "{out}");
+        // `^` 落在输入末尾之后 / 未闭合的引号下面，而不是按字符数往前偏两列。
+        assert_eq!(lines[1], "  query where name = \"张三\" and");
+        assert_eq!(lines[2], format!("  {}^", " ".repeat(29)));
+        assert_eq!(lines[4], "  add 张三 x\"");
+        assert_eq!(lines[5], format!("  {}^", " ".repeat(10)));
+    }
+
+    // 测试用的临时目录：drop 时（包括断言失败 panic）连同里面的文件一起删掉。
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("19_demo_{}_{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn join(&self, file: &str) -> PathBuf {
+            self.0.join(file)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_import_report_atomic_and_export() {
+        let dir = TempDir::new("import");
+        let src = dir.join("import.csv");
+        std::fs::write(
+            &src,
+            concat!(
+                "class,name,age\r\n",
+                "A1,alice,12\r\n",
+                "A1,bob,x\r\n",
+                "\r\n",
+                "\"B,2\",\"smith\tjr\",9\r\n",
+                "B2,carol\r\n",
+                "B2,\"\"\"dave\"\"\",300\r\n",
+                "C3,o\"x,1\r\n",
+                "C3,eve,10\r\n",
+                "D4,frank,11\r\n",
+            ),
+        )
+        .unwrap();
+        let dst = dir.join("export.csv");
+        let script = format!(
+            "class add A1\nclass add C3\nimport {src} atomic\nlist\nimport {src}\nexport {dst}\nundo\nlist\n",
+            src = src.display(),
+            dst = dst.display()
+        );
+        let (failure, out) = run_script(&script, false);
+        assert_eq!(failure, Some(Failure::Parse));
+        let report = concat!(
+            "error: line 3: invalid age `x`\n",
+            "error: line 5: name `smith\\tjr` contains control characters\n",
+            "error: line 6: expected 3 fields, got 2\n",
+            "error: line 7: invalid age `300`\n",
+            "error: line 8: `\"` inside an unquoted field\n",
+            "error: line 10: class `D4` does not exist\n",
+        );
+        let expected = format!(
+            "ok: class added A1\nok: class added C3\n{report}error: import aborted: 6 lines rejected, nothing imported\n(empty)\n\
+             {report}ok: imported 2 students from {}, 6 lines rejected\n\
+             ok: exported 2 students to {}\n\
+             ok: undo applied remove id=2\nok: undo applied remove id=1\n(empty)\n",
+            src.display(),
+            dst.display()
+        );
+        assert_eq!(out, expected);
+        let exported = std::fs::read_to_string(&dst).unwrap();
+        assert_eq!(
+            exported,
+            "id,name,age,class\r\n1,alice,12,A1\r\n2,eve,10,C3\r\n"
+        );
+
+        // export 出来的文件能原样导回（id 列被忽略，拿新 id）。
+        let script = format!(
+            "class add A1\nclass add C3\nimport {dst}\nimport {dst} atomic\nexport {dst}\n",
+            dst = dst.display()
+        );
+        let (failure, _) = run_script(&script, false);
+        assert_eq!(failure, None);
+        let exported = std::fs::read_to_string(&dst).unwrap();
+        assert!(exported.ends_with("3,alice,12,A1\r\n4,eve,10,C3\r\n"));
+
+        std::fs::write(&src, "name,age,Grade!\nx,1,2\n").unwrap();
+        let (failure, out) = run_script(&format!("import {}\n", src.display()), false);
+        assert_eq!(failure, Some(Failure::Parse));
+        assert_eq!(
+            out,
+            "error: import failed: line 1: unknown column `Grade!` in header\n"
+        );
+    }
+
+    #[test]
+    fn test_custom_attributes_in_listings_and_csv() {
+        let dir = TempDir::new("attrs");
+        let dst = dir.join("attrs.csv");
+        let script = format!(
+            concat!(
+                "class add A1\n",
+                "class add B2\n",
+                "add alice 12 A1\n",
+                "add bob 9 B2\n",
+                "set 1 score 95\n",
+                "set 1 note 'needs help'\n",
+                "set 2 phone 0123\n",
+                "set 2 name x\n",
+                "unset 2 score\n",
+                "search attr score 95\n",
+                "format json\n",
+                "list\n",
+                "format csv\n",
+                "list\n",
+                "export {dst}\n",
+                "unset 2 phone\n",
+                "undo\n",
+                "format table\n",
+                "import {dst}\n",
+                "search attr phone 0123\n",
+            ),
+            dst = dst.display()
+        );
+        // 超过 2^53 的整数属性（证件号、账号）在 JSON 里原样输出，不经过 f64。
+        let (_, out) = run_script(
+            "class add A1\nadd a 1 A1\nset 1 card 9007199254740993\nformat json\nlist\n",
+            false,
+        );
+        assert!(
+            out.contains(r#""attrs":{"card":9007199254740993}"#),
+            "{out}"
+        );
+
+        // 属性不存在不是 id 不存在：退出码 1 而不是 4。
+        let (failure, _) = run_script("class add A1\nadd a 1 A1\nunset 1 score\n", false);
+        assert_eq!(failure, Some(Failure::Other));
+
+        let (failure, out) = run_script(&script, false);
+        assert_eq!(failure, Some(Failure::Parse));
+        let expected = format!(
+            concat!(
+                "ok: class added A1\n",
+                "ok: class added B2\n",
+                "ok: added id=1\n",
+                "ok: added id=2\n",
+                "ok: set id=1 score=95 (int)\n",
+                "ok: set id=1 note=\"needs help\" (text)\n",
+                "ok: set id=2 phone=0123 (text)\n",
+                "error: invalid attribute key `name`\n",
+                "error: id=2 has no attribute `score`\n",
+                "id  name   age  class  attrs\n",
+                "1   alice  12   A1     note=\"needs help\" score=95\n",
+                "ok: format json\n",
+                r#"[{{"id":1,"name":"alice","age":12,"class":"A1","attrs":{{"note":"needs help","score":95}}}},"#,
+                r#"{{"id":2,"name":"bob","age":9,"class":"B2","attrs":{{"phone":"0123"}}}}]"#,
+                "\n",
+                "ok: format csv\n",
+                "id,name,age,class,note,phone,score\r\n",
+                "1,alice,12,A1,needs help,,95\r\n",
+                "2,bob,9,B2,,0123,\r\n",
+                "ok: exported 2 students to {dst}\n",
+                "ok: unset id=2 phone\n",
+                "ok: undo applied mod id=2 bob 9 B2\n",
+                "ok: format table\n",
+                "ok: imported 2 students from {dst}\n",
+                "id  name  age  class  attrs\n",
+                "2   bob   9    B2     phone=0123\n",
+                "4   bob   9    B2     phone=0123\n",
+            ),
+            dst = dst.display()
+        );
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_grades_report_ranking_and_cascade() {
+        let script = concat!(
+            "class add A1\n",
+            "class add B2\n",
+            "add alice 12 A1\n",
+            "add bob 11 A1\n",
+            "add carol 10 B2\n",
+            "grade 1 math 92\n",
+            "grade 2 math 92\n",
+            "grade 3 math 59\n",
+            "grade 1 art 75\n",
+            "grade 1 art 74\n",
+            "grade 2 math 101\n",
+            "report 1\n",
+            "report 3\n",
+            "ranking math\n",
+            "remove 2\n",
+            "ranking math\n",
+            "undo\n",
+            "report 2\n",
+            "ranking pe\n",
+            "report 9\n",
+        );
+        let (failure, out) = run_script(script, false);
+        assert_eq!(failure, Some(Failure::Parse));
+        let expected = concat!(
+            "ok: class added A1\n",
+            "ok: class added B2\n",
+            "ok: added id=1\n",
+            "ok: added id=2\n",
+            "ok: added id=3\n",
+            "ok: grade id=1 math=92 (A)\n",
+            "ok: grade id=2 math=92 (A)\n",
+            "ok: grade id=3 math=59 (D)\n",
+            "ok: grade id=1 art=75 (B)\n",
+            "ok: grade id=1 art=74 (C), was 75\n",
+            "error: invalid score `101`\n",
+            "report id=1 alice\n",
+            "course  score  grade\n",
+            "art     74     C\n",
+            "math    92     A\n",
+            "average: 83.00 (B)\n",
+            "report id=3 carol\n",
+            "course  score  grade\n",
+            "math    59     D\n",
+            "average: 59.00 (D)\n",
+            "rank  id  name   score  grade\n",
+            "1     1   alice  92     A\n",
+            "1     2   bob    92     A\n",
+            "3     3   carol  59     D\n",
+            "ok: removed id=2\n",
+            "rank  id  name   score  grade\n",
+            "1     1   alice  92     A\n",
+            "2     3   carol  59     D\n",
+            "ok: undo applied add id=2 bob 11 A1\n",
+            "ok: undo applied grade id=2 math 92\n",
+            "report id=2 bob\n",
+            "course  score  grade\n",
+            "math    92     A\n",
+            "average: 92.00 (A)\n",
+            "(empty)\n",
+            "error: id=9 not found\n",
+        );
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_class_table_commands() {
+        let script = concat!(
+            "add alice 12 A1\n",
+            "class add A1\n",
+            "class add A1\n",
+            "class add 'B 2'\n",
+            "add alice 12 A1\n",
+            "add bob 11 A1\n",
+            "mod 2 bob 11 C3\n",
+            "class list\n",
+            "class rename A1 'B 2'\n",
+            "class rename A1 C3\n",
+            "search class C3\n",
+            "undo\n",
+            "class remove A1\n",
+            "grade 1 math 90\n",
+            "class remove A1 --force\n",
+            "class list\n",
+            "undo\n",
+            "report 1\n",
+            "class remove 'B 2'\n",
+            "class remove 'B 2'\n",
+        );
+        let (failure, out) = run_script(script, false);
+        assert_eq!(failure, Some(Failure::Other));
+        let expected = concat!(
+            "error: class `A1` does not exist\n",
+            "ok: class added A1\n",
+            "error: class `A1` already exists\n",
+            "ok: class added \"B 2\"\n",
+            "ok: added id=1\n",
+            "ok: added id=2\n",
+            "error: class `C3` does not exist\n",
+            "class  students\n",
+            "A1     2\n",
+            "\"B 2\"  0\n",
+            "error: class `B 2` already exists\n",
+            "ok: class renamed A1 -> C3 (2 students)\n",
+            "id  name   age  class\n",
+            "1   alice  12   C3\n",
+            "2   bob    11   C3\n",
+            "ok: undo applied class rename C3 A1\n",
+            "error: class `A1` still has 2 students; use `class remove A1 --force`\n",
+            "ok: grade id=1 math=90 (A)\n",
+            "ok: class removed A1 (2 students removed)\n",
+            "class  students\n",
+            "\"B 2\"  0\n",
+            "ok: undo applied class add A1\n",
+            "ok: undo applied add id=2 bob 11 A1\n",
+            "ok: undo applied add id=1 alice 12 A1\n",
+            "ok: undo applied grade id=1 math 90\n",
+            "report id=1 alice\n",
+            "course  score  grade\n",
+            "math    90     A\n",
+            "average: 90.00 (A)\n",
+            "ok: class removed \"B 2\"\n",
+            "error: class `B 2` does not exist\n",
+        );
+        assert_eq!(out, expected);
+        let (_, out) = run_script("class list\n", false);
+        assert_eq!(out, "(empty)\n");
+    }
+
+    #[test]
+    fn test_check_and_repair() {
+        let script = concat!(
+            "check\n",
+            "class add A1\n",
+            "add alice 12 A1\n",
+            "check\n",
+            "repair\n",
+            "begin\n",
+            "repair\n",
+        );
+        let (failure, out) = run_script(script, false);
+        assert_eq!(failure, Some(Failure::Other));
+        let expected = concat!(
+            "ok: 0 students, indexes consistent\n",
+            "ok: class added A1\n",
+            "ok: added id=1\n",
+            "ok: 1 students, indexes consistent\n",
+            "ok: nothing to repair\n",
+            "ok: transaction started\n",
+            "error: `repair` is not allowed inside a transaction\n",
+        );
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_strict_script_stops_at_first_failure() {
+        let (failure, out) = run_script("class add b\nadd a 1 b\nfrobnicate\nadd c 2 d\n", true);
+        assert_eq!(failure, Some(Failure::Parse));
+        assert_eq!(
+            out,
+            "ok: class added b\nok: added id=1\nunknown command. type `help`\n"
+        );
+        assert_eq!(Failure::Parse.exit_code(), 3);
+        assert_eq!(Failure::NotFound.exit_code(), 4);
+    }
 }
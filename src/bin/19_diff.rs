@@ -0,0 +1,445 @@
+//! 19_diff: 19_demo 的 Rust 版与 C++ 版的差分测试。
+//!
+//! 两个版本本该对同一串命令给出同样的输出，这个程序负责检查：
+//! 用系统 C++ 编译器编译 `src/bin/19_demo.cc`，把同一份随机生成的命令脚本分别从 stdin 喂给两个程序，
+//! 逐行比较 stdout，出现差异时报告第一条输出不同的命令。
+//!
+//! 运行：
+//! cargo run --bin 19_diff
+//! cargo run --bin 19_diff -- --seeds 500 --len 60
+//! cargo run --bin 19_diff -- --script cases.sms
+//!
+//! `--seeds <n>`：跑种子 0..n（默认 100），每个种子生成一份脚本；`--len <n>`：每份脚本的命令数（默认 40）。
+//! `--script <file>`：不生成，直接比较给定的脚本（空行和 `#` 注释行会被去掉，C++ 版不认识注释）。
+//! `--cxx <compiler>`：C++ 编译器，默认取环境变量 `CXX`，没有则用 `c++`。
+//! `--rust <path>`：Rust 版可执行文件；默认先 `cargo build --bin 19_demo`，再用同目录下的产物。
+//!
+//! 生成的命令只用两边都支持的子集（add / list / remove / mod / search id / search name / order），
+//! 也包括两边错误信息一致的错误输入（年龄越界、id 不存在、参数个数不对等）。
+//! Rust 版要求先建班级，所以只给它多喂一段 `class add`（见 `RUST_PRELUDE`），对应的输出不参与比较。
+//!
+//! 比较前先做归一化：去掉 C++ 版的横幅、提示符和 `bye`，每行的连续空白压成一个空格
+//! （两边的表格列宽算法不同，对齐方式不算行为差异）。
+//!
+//! 退出码：0 没有差异；1 发现差异；2 参数错误或编译、启动失败。
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::thread;
+
+use rust_notes::rng::Rng;
+
+// Rust 版独有的前置命令：生成的 `add` / `mod` 只会用到这些班级。
+const RUST_PRELUDE: &[&str] = &["class add A1", "class add B2"];
+const CLASSES: &[&str] = &["A1", "B2"];
+// 名字带大小写、重音、全角和中文，两边的排序都按字节比较，`order name` 应该一致。
+const NAMES: &[&str] = &["amy", "Amy", "amos", "bob", "Zoë", "zoe", "ＢＯＢ", "张三"];
+const BAD_AGES: &[&str] = &["x", "256", "-1", "4294967296"];
+const FIELDS: &[&str] = &["id", "name", "age", "class"];
+const DIRECTIONS: &[&str] = &["asc", "desc"];
+const C_PROMPT: &str = "sms> ";
+
+/// 按种子生成一份命令脚本。
+///
+/// id 在“已经分配过的范围再多两个”里随机取，所以既会命中也会落空；
+/// 年龄多数合法，偶尔是越界或不是数字。
+fn gen_script(seed: u64, len: usize) -> Vec<String> {
+    // 固定种子：同一个种子永远生成同一份脚本，报告里的种子可以直接复现。
+    let mut rng = Rng::new(seed);
+    let mut added = 0u32;
+    let mut cmds = Vec::with_capacity(len);
+    while cmds.len() < len {
+        let id = rng.below(added + 2);
+        let name = rng.pick(NAMES);
+        let class = rng.pick(CLASSES);
+        let age = rng.below(30).to_string();
+        let cmd = match rng.below(100) {
+            0..25 => {
+                added += 1;
+                format!("add {name} {age} {class}")
+            }
+            25..28 => format!("add {name} {} {class}", rng.pick(BAD_AGES)),
+            28..40 => format!("remove {id}"),
+            40..50 => format!("mod {id} {name} {age} {class}"),
+            50..52 => format!("mod {id} {name} {} {class}", rng.pick(BAD_AGES)),
+            52..60 => "list".to_string(),
+            60..68 => format!("search id {id}"),
+            68..75 => format!("search name {name}"),
+            75..92 => format!("order {} {}", rng.pick(FIELDS), rng.pick(DIRECTIONS)),
+            92..94 => format!("order {name} asc"),
+            94..96 => format!("order age {name}"),
+            // 参数个数不对、id 不是数字、未知命令：两边的错误信息应该一字不差。
+            96 => "remove".to_string(),
+            97 => format!("mod {id}"),
+            98 => format!("search id {name}"),
+            _ => "frobnicate".to_string(),
+        };
+        cmds.push(cmd);
+    }
+    cmds
+}
+
+/// 去掉空行和 `#` 注释行，得到两边都能吃的命令序列。
+fn parse_script(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// 每行的连续空白压成一个空格：表格列宽和尾随空格不算差异。
+fn normalize_line(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// C++ 版的 stdout：第一个提示符之前是横幅，最后一个提示符之后是 EOF 时的换行和 `bye`，
+/// 中间每段是一条命令的输出。
+fn normalize_cpp(stdout: &str) -> Vec<String> {
+    let chunks = stdout.split(C_PROMPT).collect::<Vec<_>>();
+    let body = match chunks.len() {
+        0..=2 => &[][..],
+        n => &chunks[1..n - 1],
+    };
+    body.iter()
+        .flat_map(|chunk| chunk.lines())
+        .map(normalize_line)
+        .collect()
+}
+
+/// Rust 版的 stdout：stdin 不是终端时没有横幅和提示符，只需跳过 `RUST_PRELUDE` 的输出。
+fn normalize_rust(stdout: &str) -> Result<Vec<String>, String> {
+    let mut lines = stdout.lines();
+    for cmd in RUST_PRELUDE {
+        let line = lines.next().unwrap_or_default();
+        if !line.starts_with("ok: ") {
+            return Err(format!("rust prelude `{cmd}` failed: {line}"));
+        }
+    }
+    Ok(lines.map(normalize_line).collect())
+}
+
+// 把脚本从 stdin 喂给程序，返回完整的 stdout。
+// 写 stdin 放在单独的线程里：脚本和输出都很长时，先写完再读可能两边都卡在管道上。
+fn run_program(path: &Path, lines: Vec<String>) -> io::Result<String> {
+    let mut child = Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = thread::spawn(move || -> io::Result<()> {
+        for line in lines {
+            writeln!(stdin, "{line}")?;
+        }
+        Ok(())
+    });
+    let output = child.wait_with_output()?;
+    // 对方提前退出时写端会收到 EPIPE，这不算错：比较的只是 stdout。
+    let _ = writer.join();
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 两个被测程序：输入一段命令，返回归一化后的 stdout 行。
+struct Programs {
+    rust: PathBuf,
+    cpp: PathBuf,
+}
+
+impl Programs {
+    fn run(&self, cmds: &[String]) -> Result<(Vec<String>, Vec<String>), String> {
+        let prelude = RUST_PRELUDE.iter().map(|s| s.to_string());
+        let rust_input = prelude.chain(cmds.iter().cloned()).collect();
+        let rust = run_program(&self.rust, rust_input)
+            .map_err(|e| format!("run {} failed: {e}", self.rust.display()))?;
+        let cpp = run_program(&self.cpp, cmds.to_vec())
+            .map_err(|e| format!("run {} failed: {e}", self.cpp.display()))?;
+        Ok((normalize_rust(&rust)?, normalize_cpp(&cpp)))
+    }
+}
+
+/// 第一条输出不同的命令，以及两边在这条命令上各自的输出。
+#[derive(Debug, PartialEq)]
+struct Divergence {
+    step: usize,
+    rust: Vec<String>,
+    cpp: Vec<String>,
+}
+
+/// 找出第一条输出不同的命令。
+///
+/// 每个程序都是确定性的，所以前 k 条命令的输出一定是前 k+1 条输出的前缀：
+/// “前 k 条是否已经不同”随 k 单调，可以对前缀长度二分，只多跑 O(log n) 次。
+fn first_divergence<R>(cmds: &[String], mut run: R) -> Result<Option<Divergence>, String>
+where
+    R: FnMut(&[String]) -> Result<(Vec<String>, Vec<String>), String>,
+{
+    let (rust, cpp) = run(cmds)?;
+    if rust == cpp {
+        return Ok(None);
+    }
+    // 不变式：前 lo 条相同，前 hi 条不同。
+    let (mut lo, mut hi) = (0, cmds.len());
+    let mut same = (Vec::new(), Vec::new());
+    let mut differ = (rust, cpp);
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        let out = run(&cmds[..mid])?;
+        if out.0 == out.1 {
+            (lo, same) = (mid, out);
+        } else {
+            (hi, differ) = (mid, out);
+        }
+    }
+    // 去掉前 lo 条的公共输出，剩下的就是第 hi 条命令自己的输出。
+    let tail = |all: Vec<String>, before: &[String]| match all.strip_prefix(before) {
+        Some(rest) => rest.to_vec(),
+        None => all,
+    };
+    Ok(Some(Divergence {
+        step: hi - 1,
+        rust: tail(differ.0, &same.0),
+        cpp: tail(differ.1, &same.1),
+    }))
+}
+
+fn print_divergence(label: &str, cmds: &[String], d: &Divergence) {
+    println!(
+        "{label}: first divergence at command {} `{}`",
+        d.step + 1,
+        cmds[d.step]
+    );
+    println!(
+        "script (rust also runs `{}` first):",
+        RUST_PRELUDE.join("`, `")
+    );
+    for (i, cmd) in cmds[..=d.step].iter().enumerate() {
+        if i == d.step {
+            println!("  {cmd}    # <- diverges");
+        } else {
+            println!("  {cmd}");
+        }
+    }
+    for (side, lines) in [("rust", &d.rust), ("cpp", &d.cpp)] {
+        println!("{side}:");
+        if lines.is_empty() {
+            println!("  (no output)");
+        }
+        for line in lines {
+            println!("  {line}");
+        }
+    }
+}
+
+// 用系统编译器编译 C++ 版，产物放在本程序旁边（target/<profile>/），不污染源码目录。
+fn build_cpp(cxx: &str, out_dir: &Path) -> Result<PathBuf, String> {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/bin/19_demo.cc");
+    let out = out_dir.join(format!("19_demo_cc{}", std::env::consts::EXE_SUFFIX));
+    let status = Command::new(cxx)
+        .args(["-std=c++17", "-O2", "-Wall", "-Wextra", "-pedantic"])
+        .arg(&src)
+        .arg("-o")
+        .arg(&out)
+        .status()
+        .map_err(|e| format!("run `{cxx}` failed: {e}"))?;
+    if !status.success() {
+        return Err(format!("`{cxx}` failed to compile {}", src.display()));
+    }
+    Ok(out)
+}
+
+// `cargo run` 会设置 `CARGO`；按本程序所在的 profile 构建 Rust 版，避免拿到过期的产物。
+fn build_rust(out_dir: &Path) -> Result<PathBuf, String> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut cmd = Command::new(&cargo);
+    cmd.args(["build", "--quiet", "--bin", "19_demo"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"));
+    if out_dir.ends_with("release") {
+        cmd.arg("--release");
+    }
+    let status = cmd
+        .status()
+        .map_err(|e| format!("run `{cargo}` failed: {e}"))?;
+    if !status.success() {
+        return Err("`cargo build --bin 19_demo` failed".to_string());
+    }
+    Ok(out_dir.join(format!("19_demo{}", std::env::consts::EXE_SUFFIX)))
+}
+
+#[derive(Debug)]
+struct Options {
+    seeds: u64,
+    len: usize,
+    script: Option<PathBuf>,
+    cxx: String,
+    rust: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options {
+        seeds: 100,
+        len: 40,
+        script: None,
+        cxx: std::env::var("CXX").unwrap_or_else(|_| "c++".to_string()),
+        rust: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seeds" => {
+                let raw = args.next().ok_or("`--seeds` needs a <n>")?;
+                opts.seeds = raw
+                    .parse()
+                    .map_err(|_| format!("invalid seed count `{raw}`"))?;
+            }
+            "--len" => {
+                let raw = args.next().ok_or("`--len` needs a <n>")?;
+                opts.len = raw
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("invalid script length `{raw}`"))?;
+            }
+            "--script" => {
+                let path = args.next().ok_or("`--script` needs a <file>")?;
+                opts.script = Some(PathBuf::from(path));
+            }
+            "--cxx" => opts.cxx = args.next().ok_or("`--cxx` needs a <compiler>")?,
+            "--rust" => {
+                let path = args.next().ok_or("`--rust` needs a <path>")?;
+                opts.rust = Some(PathBuf::from(path));
+            }
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+    Ok(opts)
+}
+
+// 返回是否发现了差异；编译、启动失败返回 Err。
+fn run(opts: &Options) -> Result<bool, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("locate current exe failed: {e}"))?;
+    let out_dir = exe.parent().ok_or("current exe has no parent directory")?;
+    let rust = match &opts.rust {
+        Some(path) => path.clone(),
+        None => build_rust(out_dir)?,
+    };
+    let programs = Programs {
+        rust,
+        cpp: build_cpp(&opts.cxx, out_dir)?,
+    };
+
+    if let Some(path) = &opts.script {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("read {} failed: {e}", path.display()))?;
+        let cmds = parse_script(&text);
+        return match first_divergence(&cmds, |c| programs.run(c))? {
+            Some(d) => {
+                print_divergence(&path.display().to_string(), &cmds, &d);
+                Ok(true)
+            }
+            None => {
+                println!("ok: {} commands, no divergence", cmds.len());
+                Ok(false)
+            }
+        };
+    }
+
+    for seed in 0..opts.seeds {
+        let cmds = gen_script(seed, opts.len);
+        if let Some(d) = first_divergence(&cmds, |c| programs.run(c))? {
+            print_divergence(&format!("seed {seed}"), &cmds, &d);
+            return Ok(true);
+        }
+    }
+    println!(
+        "ok: {} scripts x {} commands, no divergence",
+        opts.seeds, opts.len
+    );
+    Ok(false)
+}
+
+fn main() {
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("error: {e}");
+            eprintln!(
+                "usage: 19_diff [--seeds <n>] [--len <n>] [--script <file>] [--cxx <compiler>] [--rust <path>]"
+            );
+            process::exit(2);
+        }
+    };
+    match run(&opts) {
+        Ok(false) => {}
+        Ok(true) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_normalize_strips_banner_prompts_and_padding() {
+        let cpp = "student-cli demo (cpp17)\ntype `help` to see commands\n\
+                   sms> ok: added id=1\n\
+                   sms> id   name         age  class       \n1    amy          20   A1          \n\
+                   sms> \nbye\n";
+        let rust = "ok: class added A1\nok: class added B2\n\
+                    ok: added id=1\nid  name  age  class\n1   amy   20   A1\n";
+        let expected = lines("ok: added id=1\nid name age class\n1 amy 20 A1");
+        assert_eq!(normalize_cpp(cpp), expected);
+        assert_eq!(normalize_rust(rust), Ok(expected));
+        // 前置命令失败（比如 Rust 版改了 `class add` 的输出）要报出来，而不是错位比较。
+        assert!(normalize_rust("error: unknown command\n").is_err());
+        assert_eq!(normalize_cpp(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_first_divergence_reports_the_command_and_its_output() {
+        // 假的两个程序：每条命令原样回显；“C++ 版”把 `order` 的输出写成大写。
+        let run = |cmds: &[String]| -> Result<(Vec<String>, Vec<String>), String> {
+            let rust = cmds.to_vec();
+            let cpp = cmds
+                .iter()
+                .map(|c| match c.starts_with("order") {
+                    true => c.to_uppercase(),
+                    false => c.clone(),
+                })
+                .collect();
+            Ok((rust, cpp))
+        };
+        let cmds = lines("add a 1 A1\nlist\nadd b 1 A1\norder age desc\nlist\norder id asc");
+        let d = first_divergence(&cmds, run).unwrap().unwrap();
+        assert_eq!(d.step, 3);
+        assert_eq!(d.rust, ["order age desc"]);
+        assert_eq!(d.cpp, ["ORDER AGE DESC"]);
+        assert_eq!(first_divergence(&cmds[..3], run), Ok(None));
+    }
+
+    #[test]
+    fn test_generated_scripts_are_deterministic_and_use_known_classes() {
+        assert_eq!(gen_script(7, 50), gen_script(7, 50));
+        assert_ne!(gen_script(7, 50), gen_script(8, 50));
+        let cmds = (0..20)
+            .flat_map(|seed| gen_script(seed, 50))
+            .collect::<Vec<_>>();
+        for cmd in cmds.iter().filter(|c| c.starts_with("add ")) {
+            let class = cmd.split(' ').next_back().unwrap();
+            assert!(CLASSES.contains(&class), "{cmd}");
+        }
+        assert_eq!(
+            parse_script("# 注释\n\nadd amy 20 A1\n  list  \n"),
+            lines("add amy 20 A1\nlist")
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! CSV（RFC 4180）的读写与 TSV 的记录输出。只用标准库。
+//!
+//! - CSV：字段里有 `,`、`"`、CR 或 LF 时整个字段用双引号包起来，内部的 `"` 写成 `""`；
+//!   每条记录以 CRLF 结尾（RFC 4180 的要求）。
+//! - TSV：字段不加引号，字段里的 `\`、TAB、LF、CR 分别写成 `\\`、`\t`、`\n`、`\r`，
+//!   保证一条记录永远是一行；记录以 LF 结尾。
+//!
+//! ```
+//! use rust_notes::csv::{write_csv_record, write_tsv_record};
+//!
+//! let mut out = Vec::new();
+//! write_csv_record(&mut out, &["1", "bob, jr", "say \"hi\""]).unwrap();
+//! assert_eq!(out, b"1,\"bob, jr\",\"say \"\"hi\"\"\"\r\n");
+//!
+//! let mut out = Vec::new();
+//! write_tsv_record(&mut out, &["1", "a\tb"]).unwrap();
+//! assert_eq!(out, b"1\ta\\tb\n");
+//! ```
+//!
+//! 读取用 [`records`]：逐条返回记录和它开始的行号，一条记录的语法错误不影响后面的记录。
+//!
+//! ```
+//! let text = "name,age\r\n\"smith, jr\",12\nbad\"quote,1\n";
+//! let mut it = rust_notes::csv::records(text);
+//! assert_eq!(it.next().unwrap().unwrap().fields, ["name", "age"]);
+//! assert_eq!(it.next().unwrap().unwrap().fields, ["smith, jr", "12"]);
+//! assert_eq!(it.next().unwrap().unwrap_err().line, 3);
+//! assert!(it.next().is_none());
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+
+/// 按 RFC 4180 转义一个字段；不需要引号时原样返回。
+pub fn escape_csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// 转义一个 TSV 字段；没有特殊字符时原样返回。
+pub fn escape_tsv_field(field: &str) -> Cow<'_, str> {
+    if !field.contains(['\\', '\t', '\n', '\r']) {
+        return Cow::Borrowed(field);
+    }
+    let mut out = String::with_capacity(field.len() + 2);
+    for c in field.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// 写一条 CSV 记录（以 CRLF 结尾）。
+pub fn write_csv_record<W: Write + ?Sized>(w: &mut W, fields: &[&str]) -> io::Result<()> {
+    write_record(w, fields, ',', escape_csv_field, "\r\n")
+}
+
+/// 写一条 TSV 记录（以 LF 结尾）。
+pub fn write_tsv_record<W: Write + ?Sized>(w: &mut W, fields: &[&str]) -> io::Result<()> {
+    write_record(w, fields, '\t', escape_tsv_field, "\n")
+}
+
+fn write_record<W: Write + ?Sized>(
+    w: &mut W,
+    fields: &[&str],
+    sep: char,
+    escape: fn(&str) -> Cow<'_, str>,
+    end: &str,
+) -> io::Result<()> {
+    let mut line = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            line.push(sep);
+        }
+        line.push_str(&escape(field));
+    }
+    line.push_str(end);
+    w.write_all(line.as_bytes())
+}
+
+/// 一条 CSV 记录。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// 记录开始的行号，从 1 开始（带引号的字段可以跨行）。
+    pub line: usize,
+    /// 去掉引号、还原 `""` 之后的字段。
+    pub fields: Vec<String>,
+}
+
+/// CSV 语法错误。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvError {
+    /// 出错的行号，从 1 开始。
+    pub line: usize,
+    /// 出错原因。
+    pub message: &'static str,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// 逐条解析 CSV 文本。换行可以是 CRLF 或 LF；开头的 UTF-8 BOM 和空行会被跳过。
+pub fn records(text: &str) -> Records<'_> {
+    Records {
+        rest: text.strip_prefix('\u{feff}').unwrap_or(text),
+        line: 1,
+    }
+}
+
+/// [`records`] 返回的迭代器。出错的记录返回 `Err`，然后从下一行接着解析。
+#[derive(Debug, Clone)]
+pub struct Records<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+impl<'a> Records<'a> {
+    fn parse_record(&mut self) -> Result<Vec<String>, &'static str> {
+        let mut fields = Vec::new();
+        loop {
+            if let Some(body) = self.rest.strip_prefix('"') {
+                fields.push(self.parse_quoted(body)?);
+            } else {
+                let end = self.rest.find([',', '\n']).unwrap_or(self.rest.len());
+                let mut raw = &self.rest[..end];
+                if end == self.rest.len() || self.rest[end..].starts_with('\n') {
+                    raw = raw.strip_suffix('\r').unwrap_or(raw);
+                }
+                if raw.contains('"') {
+                    return Err("`\"` inside an unquoted field");
+                }
+                fields.push(raw.to_string());
+                self.rest = &self.rest[end..];
+            }
+
+            // 字段之后：`,` 接着读下一个字段；换行或结尾结束这条记录。
+            if let Some(rest) = self.rest.strip_prefix(',') {
+                self.rest = rest;
+            } else if let Some(rest) = self
+                .rest
+                .strip_prefix("\r\n")
+                .or(self.rest.strip_prefix('\n'))
+            {
+                self.rest = rest;
+                self.line += 1;
+                return Ok(fields);
+            } else if self.rest.is_empty() {
+                return Ok(fields);
+            } else {
+                return Err("unexpected character after closing quote");
+            }
+        }
+    }
+
+    // `body` 是开头引号之后的文本；成功时 `self.rest` 停在结尾引号之后。
+    fn parse_quoted(&mut self, body: &'a str) -> Result<String, &'static str> {
+        let start_line = self.line;
+        let mut value = String::new();
+        let mut i = 0;
+        loop {
+            let Some(p) = body[i..].find(['"', '\n']) else {
+                // 报告引号开始的那一行，那里才是要改的地方。
+                self.line = start_line;
+                self.rest = "";
+                return Err("unterminated quoted field");
+            };
+            let at = i + p;
+            value.push_str(&body[i..at]);
+            if body[at..].starts_with('\n') {
+                value.push('\n');
+                self.line += 1;
+                i = at + 1;
+            } else if body[at + 1..].starts_with('"') {
+                value.push('"');
+                i = at + 2;
+            } else {
+                self.rest = &body[at + 1..];
+                return Ok(value);
+            }
+        }
+    }
+
+    fn skip_line(&mut self) {
+        match self.rest.find('\n') {
+            Some(p) => {
+                self.rest = &self.rest[p + 1..];
+                self.line += 1;
+            }
+            None => self.rest = "",
+        }
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = Result<Record, CsvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rest = self
+                .rest
+                .strip_prefix("\r\n")
+                .or(self.rest.strip_prefix('\n'));
+            match rest {
+                Some(rest) => {
+                    self.rest = rest;
+                    self.line += 1;
+                }
+                None => break,
+            }
+        }
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let line = self.line;
+        Some(match self.parse_record() {
+            Ok(fields) => Ok(Record { line, fields }),
+            Err(message) => {
+                let err = CsvError {
+                    line: self.line,
+                    message,
+                };
+                self.skip_line();
+                Err(err)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_csv_field, escape_tsv_field, records, write_csv_record};
+
+    #[test]
+    fn test_escape_fields() {
+        assert_eq!(escape_csv_field("张三"), "张三");
+        assert_eq!(escape_csv_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(escape_csv_field("\"q\""), "\"\"\"q\"\"\"");
+        assert_eq!(escape_tsv_field("plain, text"), "plain, text");
+        assert_eq!(escape_tsv_field("a\\b\tc\r\n"), "a\\\\b\\tc\\r\\n");
+
+        let mut out = Vec::new();
+        write_csv_record(&mut out, &["", "x"]).unwrap();
+        assert_eq!(out, b",x\r\n");
+    }
+
+    #[test]
+    fn test_records_roundtrip_and_recovery() {
+        let fields = ["1", "a,b", "say \"hi\"", "two\r\nlines", ""];
+        let mut text = Vec::new();
+        write_csv_record(&mut text, &fields).unwrap();
+        write_csv_record(&mut text, &["next"]).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let rows = records(&text).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].fields, fields);
+        assert_eq!((rows[0].line, rows[1].line), (1, 3));
+
+        // 出错的记录之后从下一行接着读；未闭合的引号吞掉剩下的全部文本。
+        let text = "\u{feff}a,b\n\n\"x\"y,1\nc,d\n\"open,1\ne,f\n";
+        let got = records(text)
+            .map(|r| r.map(|r| (r.line, r.fields.join("|"))))
+            .map(|r| r.map_err(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            got,
+            [
+                Ok((1, "a|b".to_string())),
+                Err("line 3: unexpected character after closing quote".to_string()),
+                Ok((4, "c|d".to_string())),
+                Err("line 5: unterminated quoted field".to_string()),
+            ]
+        );
+    }
+}
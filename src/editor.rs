@@ -0,0 +1,734 @@
+//! 终端行编辑器：光标移动、历史（可存到文件）、Ctrl-R 反向搜索、Tab 补全。
+//!
+//! 只用标准库：终端的 raw 模式通过 FFI 调 libc 的 `tcgetattr` / `tcsetattr`（仅 Linux）。
+//! stdin 不是终端、`TERM=dumb`、或者进不了 raw 模式时，[`LineEditor::read_line`]
+//! 退化成打印提示符后按行读取。
+//!
+//! 按键：
+//! - ←/→、Ctrl-B/Ctrl-F 移动一个字符；Home/End、Ctrl-A/Ctrl-E 到行首 / 行尾。
+//! - Backspace 删前一个字符；Delete 删光标处的字符；Ctrl-U / Ctrl-K 删到行首 / 行尾；Ctrl-W 删前一个词。
+//! - ↑/↓、Ctrl-P/Ctrl-N 翻历史；Ctrl-R 反向搜索历史，再按一次找更早的一条，Ctrl-G 取消。
+//! - Tab 补全（候选由 [`Completer`] 给出），没有唯一结果时连按两次列出全部候选。
+//! - Ctrl-C 放弃当前行；空行上 Ctrl-D 表示输入结束；Ctrl-L 清屏。
+//!
+//! 只处理单行显示：输入超过终端宽度折行后，重绘会不准。
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+
+/// 历史最多保留多少条，文件里也只留这么多。
+pub const MAX_HISTORY: usize = 1000;
+
+/// Tab 补全的候选来源。
+pub trait Completer {
+    /// `line` 是整行，`pos` 是光标的字节位置。返回要替换的起点（字节位置，`<= pos`）
+    /// 和候选列表：按下 Tab 后 `line[start..pos]` 会被换成某个候选。
+    fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>);
+}
+
+/// 不做任何补全。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoCompletion;
+
+impl Completer for NoCompletion {
+    fn complete(&self, _line: &str, pos: usize) -> (usize, Vec<String>) {
+        (pos, Vec::new())
+    }
+}
+
+/// 行编辑器：持有输入历史，每次 [`read_line`](Self::read_line) 读一行。
+#[derive(Debug, Default)]
+pub struct LineEditor {
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+}
+
+impl LineEditor {
+    /// 没有历史文件的编辑器，历史只在内存里。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从 `path` 加载历史（不存在就从空开始），之后每条新历史都追加到这个文件。
+    /// 文件超过 [`MAX_HISTORY`] 行时只保留最近的，并把文件重写成截断后的样子。
+    pub fn with_history_file(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut history = match fs::read_to_string(&path) {
+            Ok(text) => text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if history.len() > MAX_HISTORY {
+            history.drain(..history.len() - MAX_HISTORY);
+            let mut text = history.join("\n");
+            text.push('\n');
+            fs::write(&path, text)?;
+        }
+        Ok(Self {
+            history,
+            history_file: Some(path),
+        })
+    }
+
+    /// 目前的历史，最旧的在前。
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// 记一条历史：空行和与上一条相同的行不记。有历史文件时立刻追加进去；
+    /// 写文件失败时返回错误，并且之后不再写文件（内存里的历史照常保留）。
+    pub fn add_history(&mut self, line: &str) -> io::Result<()> {
+        let line = line.trim();
+        if line.is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        if let Some(path) = &self.history_file {
+            let appended = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{line}"));
+            if let Err(e) = appended {
+                self.history_file = None;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 打印提示符并读一行（不含换行符）；输入结束（EOF、空行上的 Ctrl-D）时返回 `None`。
+    ///
+    /// 不会自动记历史，需要的话由调用方 [`add_history`](Self::add_history)。
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        completer: &dyn Completer,
+    ) -> io::Result<Option<String>> {
+        let mut stdout = io::stdout().lock();
+        match sys::RawMode::enable() {
+            Some(_raw) => self.edit(&mut io::stdin().lock(), &mut stdout, prompt, completer),
+            None => {
+                write!(stdout, "{prompt}")?;
+                stdout.flush()?;
+                let mut line = String::new();
+                if io::stdin().lock().read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                let len = line.trim_end_matches(['\r', '\n']).len();
+                line.truncate(len);
+                Ok(Some(line))
+            }
+        }
+    }
+
+    // 编辑一行：从 `input` 逐键读，往 `out` 重绘。和终端无关，测试直接喂字节。
+    fn edit<R: Read, W: Write>(
+        &self,
+        input: &mut R,
+        out: &mut W,
+        prompt: &str,
+        completer: &dyn Completer,
+    ) -> io::Result<Option<String>> {
+        let mut line = Line::new(prompt);
+        // 正在看第几条历史；等于 `history.len()` 表示正在编辑的新行。
+        let mut hist_pos = self.history.len();
+        // 翻历史之前正在编辑的内容，翻回来时还原。
+        let mut draft = String::new();
+        // Ctrl-R 搜索结束时按下的那个键，还要按普通按键处理一次。
+        let mut pending = None;
+        let mut last_was_tab = false;
+        line.render(out)?;
+
+        loop {
+            let key = match pending.take() {
+                Some(key) => key,
+                None => match read_key(input)? {
+                    Some(key) => key,
+                    None => return Ok(None),
+                },
+            };
+            let double_tab =
+                std::mem::replace(&mut last_was_tab, key == Key::Tab) && key == Key::Tab;
+            match key {
+                Key::Enter => {
+                    out.write_all(b"\r\n")?;
+                    out.flush()?;
+                    return Ok(Some(line.text));
+                }
+                Key::Ctrl('d') if line.text.is_empty() => return Ok(None),
+                Key::Ctrl('c') => {
+                    out.write_all(b"^C\r\n")?;
+                    line = Line::new(prompt);
+                    hist_pos = self.history.len();
+                }
+                Key::Char(c) => line.insert(c),
+                Key::Backspace | Key::Ctrl('h') => line.backspace(),
+                Key::Delete | Key::Ctrl('d') => line.delete(),
+                Key::Left | Key::Ctrl('b') => line.left(),
+                Key::Right | Key::Ctrl('f') => line.right(),
+                Key::Home | Key::Ctrl('a') => line.cursor = 0,
+                Key::End | Key::Ctrl('e') => line.cursor = line.text.len(),
+                Key::Ctrl('u') => line.replace(0, line.cursor, ""),
+                Key::Ctrl('k') => line.text.truncate(line.cursor),
+                Key::Ctrl('w') => line.delete_word(),
+                Key::Ctrl('l') => out.write_all(b"\x1b[H\x1b[2J")?,
+                Key::Up | Key::Ctrl('p') if hist_pos > 0 => {
+                    if hist_pos == self.history.len() {
+                        draft = line.text.clone();
+                    }
+                    hist_pos -= 1;
+                    line.set(&self.history[hist_pos]);
+                }
+                Key::Down | Key::Ctrl('n') if hist_pos < self.history.len() => {
+                    hist_pos += 1;
+                    match self.history.get(hist_pos) {
+                        Some(entry) => line.set(entry),
+                        None => line.set(&draft),
+                    }
+                }
+                Key::Tab => self.complete(&mut line, completer, double_tab, out)?,
+                Key::Ctrl('r') => pending = self.search(&mut line, input, out)?,
+                _ => out.write_all(b"\x07")?,
+            }
+            line.render(out)?;
+        }
+    }
+
+    fn complete<W: Write>(
+        &self,
+        line: &mut Line,
+        completer: &dyn Completer,
+        list_all: bool,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let (start, candidates) = completer.complete(&line.text, line.cursor);
+        let start = start.min(line.cursor);
+        let typed = line.text[start..line.cursor].len();
+        match candidates.as_slice() {
+            [] => out.write_all(b"\x07"),
+            [only] => {
+                let cursor = line.cursor;
+                line.replace(start, cursor, &format!("{only} "));
+                Ok(())
+            }
+            _ => {
+                let common = common_prefix(&candidates);
+                if common.len() > typed {
+                    let cursor = line.cursor;
+                    line.replace(start, cursor, common);
+                    Ok(())
+                } else if list_all {
+                    write!(out, "\r\n{}\r\n", candidates.join("  "))
+                } else {
+                    out.write_all(b"\x07")
+                }
+            }
+        }
+    }
+
+    // Ctrl-R 反向搜索：返回结束搜索的那个键（交给 `edit` 接着处理），取消时返回 None。
+    fn search<R: Read, W: Write>(
+        &self,
+        line: &mut Line,
+        input: &mut R,
+        out: &mut W,
+    ) -> io::Result<Option<Key>> {
+        let original = line.text.clone();
+        let mut query = String::new();
+        // 当前命中的历史下标；下一次 Ctrl-R 从它之前继续找。
+        let mut found: Option<usize> = None;
+        let mut failed = false;
+        loop {
+            let shown = found.map_or("", |i| self.history[i].as_str());
+            let label = if failed {
+                "failed reverse-i-search"
+            } else {
+                "reverse-i-search"
+            };
+            write!(out, "\r({label})`{query}': {shown}\x1b[K")?;
+            out.flush()?;
+
+            let Some(key) = read_key(input)? else {
+                return Ok(None);
+            };
+            let before = match key {
+                Key::Char(c) => {
+                    query.push(c);
+                    // 当前这条可能仍然匹配更长的查询，所以从它（含）往前找。
+                    found.map_or(self.history.len(), |i| i + 1)
+                }
+                Key::Backspace | Key::Ctrl('h') => {
+                    query.pop();
+                    self.history.len()
+                }
+                Key::Ctrl('r') => found.unwrap_or(self.history.len()),
+                Key::Ctrl('g') | Key::Ctrl('c') => {
+                    line.set(&original);
+                    return Ok(None);
+                }
+                key => {
+                    if let Some(i) = found {
+                        line.set(&self.history[i]);
+                    }
+                    return Ok(Some(key));
+                }
+            };
+            let hit = if query.is_empty() {
+                None
+            } else {
+                self.history[..before]
+                    .iter()
+                    .rposition(|entry| entry.contains(&query))
+            };
+            failed = hit.is_none() && !query.is_empty();
+            if hit.is_some() || query.is_empty() {
+                found = hit;
+            }
+        }
+    }
+}
+
+// 正在编辑的一行：文本加光标（字节位置，总在字符边界上）。
+struct Line<'a> {
+    prompt: &'a str,
+    text: String,
+    cursor: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(prompt: &'a str) -> Self {
+        Self {
+            prompt,
+            text: String::new(),
+            cursor: 0,
+        }
+    }
+
+    fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    fn replace(&mut self, start: usize, end: usize, with: &str) {
+        self.text.replace_range(start..end, with);
+        self.cursor = start + with.len();
+    }
+
+    fn prev_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    fn left(&mut self) {
+        self.cursor = self.prev_boundary();
+    }
+
+    fn right(&mut self) {
+        self.cursor = self.next_boundary();
+    }
+
+    fn backspace(&mut self) {
+        let start = self.prev_boundary();
+        self.replace(start, self.cursor, "");
+    }
+
+    fn delete(&mut self) {
+        let end = self.next_boundary();
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    // Ctrl-W：先跳过光标前的空白，再删到上一个空白为止。
+    fn delete_word(&mut self) {
+        let before = self.text[..self.cursor].trim_end();
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        self.replace(start, self.cursor, "");
+    }
+
+    // 回到行首整行重画，再重画一遍光标前的部分，让终端自己把光标停在正确的列上：
+    // 这样不用自己算中文等宽字符占几列。
+    fn render<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(
+            out,
+            "\r{}{}\x1b[K\r{}{}",
+            self.prompt,
+            self.text,
+            self.prompt,
+            &self.text[..self.cursor]
+        )?;
+        out.flush()
+    }
+}
+
+fn common_prefix(candidates: &[String]) -> &str {
+    let first = candidates[0].as_str();
+    let mut end = first.len();
+    for other in &candidates[1..] {
+        end = first
+            .char_indices()
+            .zip(other.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(end.min(other.len()), |((i, _), _)| i.min(end));
+    }
+    &first[..end]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    /// Ctrl 加一个小写字母。
+    Ctrl(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Unknown,
+}
+
+fn read_byte<R: Read>(input: &mut R) -> io::Result<Option<u8>> {
+    let mut byte = [0u8];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// 读一个按键。输入结束时返回 None。
+fn read_key<R: Read>(input: &mut R) -> io::Result<Option<Key>> {
+    let Some(b) = read_byte(input)? else {
+        return Ok(None);
+    };
+    let key = match b {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f => Key::Backspace,
+        0x1b => read_escape(input)?,
+        0x01..=0x1a => Key::Ctrl(char::from(b'a' + b - 1)),
+        0x20..=0x7e => Key::Char(char::from(b)),
+        _ => {
+            // UTF-8 多字节字符：首字节决定总长度。
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => return Ok(Some(Key::Unknown)),
+            };
+            let mut buf = [b, 0, 0, 0];
+            input.read_exact(&mut buf[1..len])?;
+            std::str::from_utf8(&buf[..len])
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map_or(Key::Unknown, Key::Char)
+        }
+    };
+    Ok(Some(key))
+}
+
+// ESC 之后的部分：`ESC [ <数字;..> <结尾>` 或 `ESC O <结尾>`。认不出来的当 Unknown。
+fn read_escape<R: Read>(input: &mut R) -> io::Result<Key> {
+    let Some(kind) = read_byte(input)? else {
+        return Ok(Key::Unknown);
+    };
+    if kind != b'[' && kind != b'O' {
+        return Ok(Key::Unknown);
+    }
+    let mut param = 0u32;
+    loop {
+        let Some(b) = read_byte(input)? else {
+            return Ok(Key::Unknown);
+        };
+        let key = match b {
+            b'0'..=b'9' => {
+                param = param.saturating_mul(10).saturating_add(u32::from(b - b'0'));
+                continue;
+            }
+            b';' => continue,
+            b'A' => Key::Up,
+            b'B' => Key::Down,
+            b'C' => Key::Right,
+            b'D' => Key::Left,
+            b'H' => Key::Home,
+            b'F' => Key::End,
+            b'~' => match param {
+                1 | 7 => Key::Home,
+                3 => Key::Delete,
+                4 | 8 => Key::End,
+                _ => Key::Unknown,
+            },
+            _ => Key::Unknown,
+        };
+        return Ok(key);
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+mod sys {
+    use std::ffi::c_int;
+    use std::io::{self, IsTerminal};
+
+    // glibc / musl 在这些架构上的 `struct termios` 布局和常量（asm-generic）。
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Termios {
+        c_iflag: u32,
+        c_oflag: u32,
+        c_cflag: u32,
+        c_lflag: u32,
+        c_line: u8,
+        c_cc: [u8; 32],
+        c_ispeed: u32,
+        c_ospeed: u32,
+    }
+
+    const ICRNL: u32 = 0o400;
+    const IXON: u32 = 0o2000;
+    const ISIG: u32 = 0o1;
+    const ICANON: u32 = 0o2;
+    const ECHO: u32 = 0o10;
+    const IEXTEN: u32 = 0o100000;
+    const VTIME: usize = 5;
+    const VMIN: usize = 6;
+    const TCSAFLUSH: c_int = 2;
+    const STDIN: c_int = 0;
+
+    unsafe extern "C" {
+        fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *const Termios) -> c_int;
+    }
+
+    // stdin 处在 raw 模式期间的守卫，drop 时恢复原来的终端设置（panic 时也会恢复）。
+    pub(super) struct RawMode {
+        original: Termios,
+    }
+
+    impl RawMode {
+        // 不是终端、`TERM=dumb` 或者系统调用失败时返回 None，调用方退化成按行读取。
+        pub(super) fn enable() -> Option<RawMode> {
+            if !io::stdin().is_terminal() || std::env::var("TERM").is_ok_and(|t| t == "dumb") {
+                return None;
+            }
+            let mut original = Termios {
+                c_iflag: 0,
+                c_oflag: 0,
+                c_cflag: 0,
+                c_lflag: 0,
+                c_line: 0,
+                c_cc: [0; 32],
+                c_ispeed: 0,
+                c_ospeed: 0,
+            };
+            // SAFETY:
+            // 1) `original` 是本函数里的局部变量，指针在调用期间有效、可写。
+            // 2) `Termios` 按 `#[repr(C)]` 与这些平台上 libc 的 `struct termios` 布局一致。
+            if unsafe { tcgetattr(STDIN, &mut original) } != 0 {
+                return None;
+            }
+            let mut raw = original;
+            raw.c_iflag &= !(ICRNL | IXON);
+            raw.c_lflag &= !(ECHO | ICANON | ISIG | IEXTEN);
+            raw.c_cc[VMIN] = 1;
+            raw.c_cc[VTIME] = 0;
+            // SAFETY: 同上，`raw` 是有效的局部变量，只读。
+            if unsafe { tcsetattr(STDIN, TCSAFLUSH, &raw) } != 0 {
+                return None;
+            }
+            Some(RawMode { original })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            // SAFETY: `self.original` 是 enable 时 tcgetattr 填好的有效设置。
+            unsafe {
+                tcsetattr(STDIN, TCSAFLUSH, &self.original);
+            }
+        }
+    }
+}
+
+// 其它平台没有 raw 模式，总是按行读取。
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+mod sys {
+    pub(super) struct RawMode;
+
+    impl RawMode {
+        pub(super) fn enable() -> Option<RawMode> {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Completer, LineEditor, NoCompletion};
+
+    fn edit(editor: &LineEditor, keys: &str, completer: &dyn Completer) -> Option<String> {
+        let mut out = Vec::new();
+        editor
+            .edit(&mut keys.as_bytes(), &mut out, "> ", completer)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cursor_movement_and_kills() {
+        let editor = LineEditor::new();
+        let left = "\x1b[D";
+        let cases = [
+            ("add bob\r", "add bob"),
+            (&format!("ac{left}b\r"), "abc"),
+            ("张三\x7f四\r", "张四"),
+            (&format!("abc\x01x\x1b[Fy{left}{left}\x1b[3~\r"), "xaby"),
+            (&format!("search name{left}{left}\x0b\x15x\r"), "x"),
+            ("order age  desc\x17asc\r", "order age  asc"),
+            ("abc\x03def\r", "def"),
+            ("ab\x02\x02\x04\x06\r", "b"),
+        ];
+        for (keys, expected) in cases {
+            assert_eq!(
+                edit(&editor, keys, &NoCompletion),
+                Some(expected.to_string())
+            );
+        }
+        assert_eq!(edit(&editor, "\x04", &NoCompletion), None);
+        assert_eq!(edit(&editor, "abc", &NoCompletion), None);
+    }
+
+    #[test]
+    fn test_history_navigation_search_and_file() {
+        let path = std::env::temp_dir().join(format!("editor_{}.history", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut editor = LineEditor::with_history_file(&path).unwrap();
+        for line in [
+            "list",
+            "search name bob",
+            "search name bob",
+            " ",
+            "order age desc",
+        ] {
+            editor.add_history(line).unwrap();
+        }
+        assert_eq!(
+            editor.history(),
+            ["list", "search name bob", "order age desc"]
+        );
+
+        let up = "\x1b[A";
+        let down = "\x1b[B";
+        let cases = [
+            (format!("{up}\r"), "order age desc"),
+            (format!("{up}{up}{up}{up}\r"), "list"),
+            (format!("draft{up}{up}{down}{down}\r"), "draft"),
+            // Ctrl-R：`a` 先命中最近的 order，再按一次找到更早的 search。
+            ("\x12a\x12\r".to_string(), "search name bob"),
+            ("\x12li\x1b[D!\r".to_string(), "lis!t"),
+            ("x\x12zzz\x07\r".to_string(), "x"),
+        ];
+        for (keys, expected) in cases {
+            assert_eq!(
+                edit(&editor, &keys, &NoCompletion),
+                Some(expected.to_string())
+            );
+        }
+
+        // 重新打开能读回来；超过上限时只留最近的。
+        let reopened = LineEditor::with_history_file(&path).unwrap();
+        assert_eq!(reopened.history(), editor.history());
+        let many = (0..super::MAX_HISTORY + 5)
+            .map(|i| format!("cmd {i}\n"))
+            .collect::<String>();
+        std::fs::write(&path, many).unwrap();
+        let trimmed = LineEditor::with_history_file(&path).unwrap();
+        assert_eq!(trimmed.history().len(), super::MAX_HISTORY);
+        assert_eq!(trimmed.history()[0], "cmd 5");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    struct Words(&'static [&'static str]);
+
+    impl Completer for Words {
+        fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+            let start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
+            let word = &line[start..pos];
+            let hits = self.0.iter().filter(|w| w.starts_with(word));
+            (start, hits.map(|w| w.to_string()).collect())
+        }
+    }
+
+    #[test]
+    fn test_tab_completion() {
+        let editor = LineEditor::new();
+        let words = Words(&["search", "stats", "save", "remove"]);
+        let cases = [
+            ("re\t\r", "remove "),
+            ("sea\tname\r", "search name"),
+            ("s\t\tt\t\r", "stats "),
+            ("x\t\r", "x"),
+            ("stats r\t\r", "stats remove "),
+        ];
+        for (keys, expected) in cases {
+            assert_eq!(edit(&editor, keys, &words), Some(expected.to_string()));
+        }
+
+        // 没有唯一前缀时，第二次 Tab 列出全部候选。
+        let mut out = Vec::new();
+        editor
+            .edit(&mut "s\t\t\r".as_bytes(), &mut out, "> ", &words)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\r\nsearch  stats  save\r\n"), "{out:?}");
+    }
+}
@@ -0,0 +1,398 @@
+//! 手写的 HTTP/1.1：从 `BufRead` 读一个请求，把响应写到 `Write`。只用标准库。
+//!
+//! 只实现 REST API 用得到的那一小部分：
+//! - 请求行 + 头部总共不超过 [`MAX_HEAD_BYTES`]，超出返回 431；
+//! - 请求体只认 `Content-Length`（不支持 chunked，返回 501），超过调用方给的上限返回 413；
+//! - 路径和查询参数做百分号解码，查询参数里的 `+` 当空格；
+//! - 每个连接只处理一个请求，响应总是带 `Connection: close`。
+//!
+//! ```
+//! use std::io::Cursor;
+//! use rust_notes::http::{Request, Response};
+//!
+//! let raw = "GET /students?name=%E5%BC%A0%E4%B8%89 HTTP/1.1\r\nHost: x\r\n\r\n";
+//! let req = Request::read_from(&mut Cursor::new(raw), 1024).unwrap().unwrap();
+//! assert_eq!(req.path, "/students");
+//! assert_eq!(req.query_param("name"), Some("张三"));
+//!
+//! let mut out = Vec::new();
+//! Response::new(204).write_to(&mut out).unwrap();
+//! assert!(out.starts_with(b"HTTP/1.1 204 No Content\r\n"));
+//! ```
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use crate::json::Json;
+
+/// 请求行加全部头部最多多少字节。
+pub const MAX_HEAD_BYTES: usize = 8 * 1024;
+
+/// 解析好的请求。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    /// 方法，如 `GET`（区分大小写，和 RFC 一致）。
+    pub method: String,
+    /// 百分号解码后的路径，不含查询串。
+    pub path: String,
+    /// 解码后的查询参数，按出现顺序。
+    pub query: Vec<(String, String)>,
+    /// 头部，名字保持原样；查找用 [`Request::header`]，不区分大小写。
+    pub headers: Vec<(String, String)>,
+    /// 请求体。
+    pub body: Vec<u8>,
+}
+
+/// 读请求失败。除了 `Io` 以外都应该回一个错误响应，状态码见 [`HttpError::status`]。
+#[derive(Debug)]
+pub enum HttpError {
+    /// 底层读失败，或者请求没发完连接就断了。
+    Io(io::Error),
+    /// 请求格式不对（400）。
+    BadRequest(String),
+    /// 请求行 + 头部太长（431）。
+    HeadTooLarge,
+    /// 请求体超过上限（413）。
+    BodyTooLarge {
+        /// 允许的最大字节数。
+        max: usize,
+    },
+    /// 用了不支持的特性，比如 chunked 请求体（501）。
+    Unsupported(String),
+}
+
+impl HttpError {
+    /// 对应的响应状态码；`Io` 没有响应可回，返回 `None`。
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            HttpError::Io(_) => None,
+            HttpError::BadRequest(_) => Some(400),
+            HttpError::HeadTooLarge => Some(431),
+            HttpError::BodyTooLarge { .. } => Some(413),
+            HttpError::Unsupported(_) => Some(501),
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Io(e) => write!(f, "read failed: {e}"),
+            HttpError::BadRequest(reason) => write!(f, "bad request: {reason}"),
+            HttpError::HeadTooLarge => {
+                write!(f, "request head is longer than {MAX_HEAD_BYTES} bytes")
+            }
+            HttpError::BodyTooLarge { max } => write!(f, "request body is longer than {max} bytes"),
+            HttpError::Unsupported(what) => write!(f, "{what} is not supported"),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<io::Error> for HttpError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+fn bad(reason: impl Into<String>) -> HttpError {
+    HttpError::BadRequest(reason.into())
+}
+
+impl Request {
+    /// 读一个请求。连接在请求开始前就关了（客户端什么都没发）时返回 `Ok(None)`。
+    ///
+    /// `max_body`：请求体最多多少字节。
+    pub fn read_from<R: BufRead>(r: &mut R, max_body: usize) -> Result<Option<Request>, HttpError> {
+        let mut head_left = MAX_HEAD_BYTES;
+        let request_line = match read_head_line(r, &mut head_left)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+
+        let mut fields = request_line.split(' ');
+        let (method, target, version) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(m), Some(t), Some(v)) if fields.next().is_none() && !m.is_empty() => (m, t, v),
+            _ => return Err(bad("malformed request line")),
+        };
+        if version != "HTTP/1.1" && version != "HTTP/1.0" {
+            return Err(HttpError::Unsupported(format!("version `{version}`")));
+        }
+        if !target.starts_with('/') {
+            return Err(bad("request target must start with `/`"));
+        }
+        let (raw_path, raw_query) = target.split_once('?').unwrap_or((target, ""));
+        let path = percent_decode(raw_path, false).ok_or_else(|| bad("invalid path encoding"))?;
+        let query = parse_query(raw_query).ok_or_else(|| bad("invalid query encoding"))?;
+
+        let mut headers = Vec::new();
+        loop {
+            let line = read_head_line(r, &mut head_left)?
+                .ok_or_else(|| HttpError::Io(io::ErrorKind::UnexpectedEof.into()))?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .filter(|(name, _)| !name.is_empty() && !name.contains(' '))
+                .ok_or_else(|| bad(format!("malformed header `{line}`")))?;
+            headers.push((name.to_string(), value.trim().to_string()));
+        }
+
+        let mut req = Request {
+            method: method.to_string(),
+            path,
+            query,
+            headers,
+            body: Vec::new(),
+        };
+        if req.header("transfer-encoding").is_some() {
+            return Err(HttpError::Unsupported("transfer-encoding".to_string()));
+        }
+        let len = match req.header("content-length") {
+            Some(raw) => raw
+                .parse::<usize>()
+                .map_err(|_| bad(format!("invalid content-length `{raw}`")))?,
+            None => 0,
+        };
+        if len > max_body {
+            return Err(HttpError::BodyTooLarge { max: max_body });
+        }
+        req.body = vec![0; len];
+        r.read_exact(&mut req.body)?;
+        Ok(Some(req))
+    }
+
+    /// 按名字取头部的值（不区分大小写），有重复时取第一个。
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 按名字取查询参数，有重复时取第一个。
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// 读一行头部（去掉 `\r\n` 或 `\n`），并从剩余额度里扣掉。
+// 连接在这一行开始之前就关了时返回 `Ok(None)`。
+fn read_head_line<R: BufRead>(r: &mut R, left: &mut usize) -> Result<Option<String>, HttpError> {
+    let mut buf = Vec::new();
+    // 多读一个字节：读满额度还没见到换行，说明超长了。
+    let n = r.take(*left as u64 + 1).read_until(b'\n', &mut buf)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if n > *left {
+        return Err(HttpError::HeadTooLarge);
+    }
+    *left -= n;
+    if buf.pop() != Some(b'\n') {
+        return Err(HttpError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    if buf.last() == Some(&b'\r') {
+        buf.pop();
+    }
+    String::from_utf8(buf)
+        .map(Some)
+        .map_err(|_| bad("request head is not UTF-8"))
+}
+
+fn parse_query(raw: &str) -> Option<Vec<(String, String)>> {
+    raw.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((percent_decode(k, true)?, percent_decode(v, true)?))
+        })
+        .collect()
+}
+
+/// 百分号解码；`plus_as_space` 为真时把 `+` 解成空格（查询参数的写法）。
+/// `%` 后面不是两位十六进制、或解出来不是 UTF-8 时返回 `None`。
+pub fn percent_decode(raw: &str, plus_as_space: bool) -> Option<String> {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = raw.get(i + 1..i + 3)?;
+                if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return None;
+                }
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// 状态码对应的原因短语；不认识的状态码返回空串。
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// 要写回去的响应。`Content-Length` 和 `Connection: close` 在 [`Response::write_to`] 里自动加。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// 状态码。
+    pub status: u16,
+    /// 额外的头部。
+    pub headers: Vec<(String, String)>,
+    /// 响应体。
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// 空响应体的响应。
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// JSON 响应体，带 `Content-Type: application/json`。
+    pub fn json(status: u16, body: &Json) -> Self {
+        let mut body = body.to_string().into_bytes();
+        body.push(b'\n');
+        Self::new(status)
+            .with_header("Content-Type", "application/json; charset=utf-8")
+            .with_body(body)
+    }
+
+    /// 加一个头部。
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// 设置响应体。
+    pub fn with_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// 按 HTTP/1.1 格式写出：状态行、头部、空行、响应体。
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\n",
+            self.status,
+            reason_phrase(self.status)
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        w.write_all(head.as_bytes())?;
+        w.write_all(&self.body)?;
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpError, MAX_HEAD_BYTES, Request, Response, percent_decode};
+    use crate::json::Json;
+    use std::io::Cursor;
+
+    fn read(raw: &[u8]) -> Result<Option<Request>, HttpError> {
+        Request::read_from(&mut Cursor::new(raw), 16)
+    }
+
+    #[test]
+    fn test_read_request_with_body() {
+        let raw = b"POST /students/%31?x=a+b&flag HTTP/1.1\r\ncontent-LENGTH: 5\r\nHost:  h \r\n\r\nhelloEXTRA";
+        let req = read(raw).unwrap().unwrap();
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/students/1");
+        assert_eq!(req.query_param("x"), Some("a b"));
+        assert_eq!(req.query_param("flag"), Some(""));
+        assert_eq!(req.header("Content-Length"), Some("5"));
+        assert_eq!(req.header("host"), Some("h"));
+        assert_eq!(req.body, b"hello");
+
+        // 裸 `\n` 换行也接受；连接没发任何东西就关了不算错。
+        let req = read(b"GET / HTTP/1.0\n\n").unwrap().unwrap();
+        assert_eq!((req.method.as_str(), req.path.as_str()), ("GET", "/"));
+        assert!(read(b"").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_request_errors() {
+        let status = |raw: &[u8]| read(raw).map(|_| ()).unwrap_err().status();
+        assert_eq!(status(b"GET /\r\n\r\n"), Some(400));
+        assert_eq!(status(b"GET x HTTP/1.1\r\n\r\n"), Some(400));
+        assert_eq!(status(b"GET /%zz HTTP/1.1\r\n\r\n"), Some(400));
+        assert_eq!(status(b"GET / HTTP/1.1\r\nbad header\r\n\r\n"), Some(400));
+        assert_eq!(status(b"GET / HTTP/2\r\n\r\n"), Some(501));
+        assert_eq!(
+            status(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Some(501)
+        );
+        assert_eq!(
+            status(b"POST / HTTP/1.1\r\nContent-Length: 17\r\n\r\n"),
+            Some(413)
+        );
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEAD_BYTES));
+        assert_eq!(status(long.as_bytes()), Some(431));
+        // 请求体没发完连接就断了：没法回响应。
+        assert!(matches!(
+            read(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nab"),
+            Err(HttpError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_percent_decode_and_response() {
+        assert_eq!(percent_decode("a%20b+c", false).as_deref(), Some("a b+c"));
+        assert_eq!(percent_decode("a%20b+c", true).as_deref(), Some("a b c"));
+        assert_eq!(percent_decode("%E5%BC%A0", true).as_deref(), Some("张"));
+        assert_eq!(percent_decode("%e5", true), None);
+        assert_eq!(percent_decode("%4", true), None);
+
+        let mut out = Vec::new();
+        Response::json(404, &Json::Object(vec![("error".into(), "x".into())]))
+            .with_header("Allow", "GET")
+            .write_to(&mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json; charset=utf-8\r\nAllow: GET\r\nContent-Length: 14\r\nConnection: close\r\n\r\n{\"error\":\"x\"}\n"
+        );
+    }
+}
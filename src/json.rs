@@ -0,0 +1,487 @@
+//! 最小的 JSON：一个值类型 [`Json`]，手写的递归下降解析器，和 `Display` 序列化（紧凑格式）。
+//!
+//! 只用标准库，够 HTTP API 和导出用：
+//! - 解析出来的数字统一存成 `f64`；整数（绝对值不超过 2^53）序列化时不带小数点。
+//!   超出 2^53 的整数 `f64` 存不精确，要原样输出时用 [`Json::Int`]。
+//! - 对象用 `Vec` 保存，保持键的原始顺序；解析时重复的键算错误。
+//! - 嵌套深度最多 [`MAX_DEPTH`] 层，防止恶意输入把栈打爆。
+//!
+//! ```
+//! use rust_notes::json::Json;
+//!
+//! let v = Json::parse(r#"{"name": "张三", "age": 12}"#).unwrap();
+//! assert_eq!(v.get("name").and_then(Json::as_str), Some("张三"));
+//! assert_eq!(v.to_string(), r#"{"name":"张三","age":12}"#);
+//! ```
+
+use std::fmt;
+
+/// 解析时允许的最大嵌套深度。
+pub const MAX_DEPTH: usize = 64;
+
+/// 一个 JSON 值。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    /// `null`
+    Null,
+    /// `true` / `false`
+    Bool(bool),
+    /// 数字。
+    Number(f64),
+    /// 整数，按十进制原样输出，不经过 `f64`（超过 2^53 也不丢精度）。解析器不会产生这个变体。
+    Int(i64),
+    /// 字符串。
+    String(String),
+    /// 数组。
+    Array(Vec<Json>),
+    /// 对象，按出现顺序保存键值对。
+    Object(Vec<(String, Json)>),
+}
+
+/// 解析失败：出错的字节偏移和原因。`Display` 形如 `at byte 7: expected ':'`。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonError {
+    /// 0-based 字节偏移。
+    pub offset: usize,
+    /// 出错原因。
+    pub message: String,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl Json {
+    /// 解析一段完整的 JSON 文本；值前后只允许空白。
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser { text, pos: 0 };
+        let value = parser.value(0)?;
+        parser.skip_ws();
+        if parser.pos != text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// 对象里按键取值；不是对象或没有这个键时返回 `None`。
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// 字符串值。
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// 非负整数值；带小数、负数或超出 `u64` 时返回 `None`。
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Json::Number(n) if n >= 0.0 && n.fract() == 0.0 && n <= u64::MAX as f64 => {
+                Some(n as u64)
+            }
+            Json::Int(n) => u64::try_from(n).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(n: u32) -> Self {
+        Json::Number(n.into())
+    }
+}
+
+impl From<u8> for Json {
+    fn from(n: u8) -> Self {
+        Json::Number(n.into())
+    }
+}
+
+impl From<i64> for Json {
+    fn from(n: i64) -> Self {
+        Json::Int(n)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            // JSON 没有 NaN / Infinity。
+            Json::Number(n) if !n.is_finite() => f.write_str("null"),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() <= 9_007_199_254_740_992.0 => {
+                write!(f, "{}", *n as i64)
+            }
+            Json::Number(n) => write!(f, "{n}"),
+            Json::Int(n) => write!(f, "{n}"),
+            Json::String(s) => write_json_string(f, s),
+            Json::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_json_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+// 只转义必须转义的字符：引号、反斜杠和控制字符；其余（包括中文）原样输出。
+fn write_json_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    f.write_str("\"")
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> JsonError {
+        JsonError {
+            offset: self.pos,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        self.skip_ws();
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.skip_ws();
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.pos += 1;
+        let mut fields: Vec<(String, Json)> = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_ws();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected string key"));
+            }
+            let key_at = self.pos;
+            let key = self.string()?;
+            if fields.iter().any(|(k, _)| *k == key) {
+                return Err(JsonError {
+                    offset: key_at,
+                    message: format!("duplicate key \"{key}\""),
+                });
+            }
+            self.expect(b':')?;
+            let value = self.value(depth + 1)?;
+            fields.push((key, value));
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value(depth + 1)?);
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    // 调用时 pos 指向开头的引号。
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            // 一次跳过一段普通字符，按 char 处理，不会切开多字节 UTF-8。
+            let plain = rest
+                .find(|c: char| c == '"' || c == '\\' || (c as u32) < 0x20)
+                .ok_or_else(|| self.error("unterminated string"))?;
+            out.push_str(&rest[..plain]);
+            self.pos += plain;
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    out.push(self.escape()?);
+                }
+                _ => return Err(self.error("control character in string")),
+            }
+        }
+    }
+
+    // 调用时 pos 指向反斜杠后面那个字符。
+    fn escape(&mut self) -> Result<char, JsonError> {
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                let high = self.hex4()?;
+                // UTF-16 代理对：高位后面必须紧跟 `\uDC00..=\uDFFF` 的低位。
+                let code = if (0xD800..0xDC00).contains(&high) {
+                    if !self.text[self.pos..].starts_with("\\u") {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    self.pos += 2;
+                    let low = self.hex4()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    high
+                };
+                return char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"));
+            }
+            _ => return Err(self.error("invalid escape")),
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|d| d.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).expect("checked hex digits"))
+    }
+
+    // 按 JSON 语法圈出数字的范围（不允许前导 0、`+`、`.5` 这类写法），再交给 f64 解析。
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        let digits = |p: &mut Self| {
+            let from = p.pos;
+            while matches!(p.peek(), Some(b'0'..=b'9')) {
+                p.pos += 1;
+            }
+            p.pos > from
+        };
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return Err(self.error("invalid number"));
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        self.text[start..self.pos]
+            .parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| JsonError {
+                offset: start,
+                message: "invalid number".to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Json, MAX_DEPTH};
+
+    #[test]
+    fn test_parse_and_serialize_round_trip() {
+        let text = r#" { "a" : [1, -2.5, 1e3, true, false, null], "b": {"c": "x\"y\\z\n\u00e9\ud83d\ude00"} } "#;
+        let v = Json::parse(text).unwrap();
+        assert_eq!(
+            v.get("a").unwrap(),
+            &Json::parse("[1,-2.5,1000,true,false,null]").unwrap()
+        );
+        assert_eq!(
+            v.get("b").and_then(|b| b.get("c")).and_then(Json::as_str),
+            Some("x\"y\\z\né😀")
+        );
+        let compact = v.to_string();
+        assert_eq!(
+            compact,
+            r#"{"a":[1,-2.5,1000,true,false,null],"b":{"c":"x\"y\\z\né😀"}}"#
+        );
+        assert_eq!(Json::parse(&compact).unwrap(), v);
+        assert_eq!(Json::from("\u{1}").to_string(), r#""\u0001""#);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_input() {
+        for (text, offset) in [
+            ("", 0),
+            ("{\"a\" 1}", 5),
+            ("[1,]", 3),
+            ("01", 1),
+            ("{\"a\":1,\"a\":2}", 7),
+            ("\"abc", 1),
+            ("\"\\ud800\"", 7),
+            ("[1] x", 4),
+            ("\"tab\there\"", 4),
+        ] {
+            let e = Json::parse(text).unwrap_err();
+            assert_eq!(e.offset, offset, "{text:?}: {e}");
+        }
+        let deep = "[".repeat(MAX_DEPTH + 2);
+        assert_eq!(Json::parse(&deep).unwrap_err().message, "nesting too deep");
+    }
+
+    #[test]
+    fn test_as_u64() {
+        let v = Json::parse("[12, 12.5, -1, \"12\"]").unwrap();
+        let Json::Array(items) = v else {
+            panic!("expected array")
+        };
+        let got = items.iter().map(Json::as_u64).collect::<Vec<_>>();
+        assert_eq!(got, vec![Some(12), None, None, None]);
+        assert_eq!(Json::Int(-1).as_u64(), None);
+    }
+
+    #[test]
+    fn test_int_is_serialized_exactly() {
+        // 2^53 + 1：转成 f64 会变成 2^53。
+        let big = 9_007_199_254_740_993_i64;
+        assert_eq!(Json::Number(big as f64).to_string(), "9007199254740992");
+        assert_eq!(Json::from(big).to_string(), "9007199254740993");
+        assert_eq!(Json::Int(i64::MIN).to_string(), "-9223372036854775808");
+        assert_eq!(Json::Int(big).as_u64(), Some(9_007_199_254_740_993));
+    }
+}
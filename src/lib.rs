@@ -0,0 +1,21 @@
+//! rust_notes 库 crate：各章示例里可以复用的部分。
+//!
+//! 示例程序（`src/bin/*.rs`）大多是自包含的单文件；只有需要被多个工具共用的代码才放到这里。
+//!
+//! - [`student`]：学生管理的存储、索引、持久化与查询，`19_demo` 的 REPL 建在它上面。
+//! - [`json`]：最小的 JSON 值、解析与序列化。
+//! - [`csv`]：RFC 4180 CSV 的读写与 TSV 的记录输出。
+//! - [`editor`]：只用标准库的终端行编辑器（历史、Ctrl-R 搜索、Tab 补全），`19_demo` 的交互模式用它。
+//! - [`table`]：按终端显示宽度（中文占两列）对齐、自动列宽的文本表格。
+//! - [`http`]：手写的 HTTP/1.1 请求解析与响应输出，`19_demo --http` 用它提供 REST API。
+//! - [`rng`]：固定种子的伪随机数，模型测试和 `19_diff` 差分测试共用。
+
+#![warn(missing_docs)]
+
+pub mod csv;
+pub mod editor;
+pub mod http;
+pub mod json;
+pub mod rng;
+pub mod student;
+pub mod table;
@@ -0,0 +1,34 @@
+//! 固定种子的伪随机数（splitmix64），给随机测试生成可复现的命令序列。
+//!
+//! 不引入依赖；同一个种子永远得到同一串数，报告里的种子可以直接拿来复现。
+//! 只用于生成测试输入，不适合任何和安全有关的场合。
+
+/// splitmix64 生成器，状态就是一个 `u64`。
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// 从种子开始的生成器。
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    /// 下一个 64 位随机数。
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// `0..n` 里的一个数；n 很小，取模的偏差可以忽略。`n` 为 0 时 panic。
+    pub fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % u64::from(n)) as u32
+    }
+
+    /// 从非空切片里随机取一个元素。
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u32) as usize]
+    }
+}
@@ -0,0 +1,319 @@
+//! 学生管理：[`StudentStore`] 及其二级索引、班级表、成绩、持久化（快照 + 预写日志）、
+//! undo/redo、事务、一致性检查（[`StudentStore::verify`]）与 `query` 小语言。
+//!
+//! 基本的增删改查抽象成 [`StudentRepository`] trait，除了内存版 [`StudentStore`]，
+//! 还有记录不常驻内存的文件分页后端 [`PagedStore`]。
+//!
+//! 这里只有数据和规则，不做任何输入输出；命令解析和打印留给调用方（如 `19_demo` 的 REPL）。
+//! 所有可能失败的操作都返回 `Result`，错误类型见 [`StoreError`]、[`PersistError`]、
+//! [`QueryError`] 和 [`ParseError`]。
+//!
+//! ```
+//! use rust_notes::student::{SortDirection, SortField, StoreError, StudentStore};
+//!
+//! let mut store = StudentStore::new();
+//! // 班级要先建好，学生才能加进去。
+//! store.add_class("A1").unwrap();
+//! store.add_class("A3").unwrap();
+//! assert!(matches!(store.add("carol", 10, "B2"), Err(StoreError::UnknownClass(_))));
+//! let id = store.add("alice", 12, "A3").unwrap();
+//! store.add("bob", 9, "A1").unwrap();
+//! assert_eq!(store.get_by_id(id).unwrap().name, "alice");
+//!
+//! let by_age = store.ordered(SortField::Age, SortDirection::Asc);
+//! assert_eq!(by_age[0].name, "bob");
+//! assert!(matches!(store.remove(99), Err(StoreError::NotFound(99))));
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::str::FromStr;
+
+mod grade;
+mod index;
+#[cfg(test)]
+mod model;
+mod paged;
+mod persist;
+mod query;
+mod repository;
+mod store;
+mod verify;
+
+pub use grade::{GradeBands, MAX_SCORE, classify};
+pub use index::{AgeSummary, ClassStats};
+pub use paged::{MAX_FIELD_BYTES, PAGE_SIZE, PagedStore};
+pub use persist::PersistError;
+pub use query::{CmpOp, Expr, Plan, Query, QueryError, QueryParser, Value, plan_query, run_query};
+pub use repository::StudentRepository;
+pub use store::{DEFAULT_FUZZY_DISTANCE, DEFAULT_HISTORY_LIMIT, Op, StoreError, StudentStore};
+pub use verify::Inconsistency;
+
+/// 一条学生记录。`id` 由 [`StudentStore`] 分配，其余字段由调用方给出。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Student {
+    /// 主键，只增不复用。
+    pub id: u32,
+    /// 名字，可以重名。
+    pub name: String,
+    /// 年龄。
+    pub age: u8,
+    /// 班级名。
+    pub class_name: String,
+    /// 自定义属性（如 email、监护人电话），按 key 排序；大多数记录是空的。
+    pub attrs: Attrs,
+}
+
+/// 一条记录的全部自定义属性：key -> 值。
+pub type Attrs = BTreeMap<String, AttrValue>;
+
+/// 属性 key 最多多少字节。
+pub const MAX_ATTR_KEY_LEN: usize = 32;
+
+/// 自定义属性的值，带类型。
+///
+/// 用 [`AttrValue::infer`] 从文本得到：只有写法规范、转回文本能原样得到输入的数字才当数字，
+/// 所以 `0123` 这样的电话号码仍是文本，不会丢掉前导 0。
+///
+/// ```
+/// use rust_notes::student::AttrValue;
+///
+/// assert_eq!(AttrValue::infer("12"), AttrValue::Int(12));
+/// assert_eq!(AttrValue::infer("3.5"), AttrValue::Float(3.5));
+/// assert_eq!(AttrValue::infer("true"), AttrValue::Bool(true));
+/// assert_eq!(AttrValue::infer("0123"), AttrValue::Text("0123".to_string()));
+/// assert_eq!(AttrValue::infer("3.50").to_string(), "3.50");
+/// ```
+#[derive(Debug, Clone)]
+pub enum AttrValue {
+    /// 整数。
+    Int(i64),
+    /// 有限的小数（不会是 NaN / 无穷）。
+    Float(f64),
+    /// `true` / `false`。
+    Bool(bool),
+    /// 其它文本。
+    Text(String),
+}
+
+impl AttrValue {
+    /// 按字面推断类型：`true` / `false`、规范写法的整数、规范写法的有限小数，其余是文本。
+    pub fn infer(raw: &str) -> AttrValue {
+        match raw {
+            "true" => return AttrValue::Bool(true),
+            "false" => return AttrValue::Bool(false),
+            _ => {}
+        }
+        if let Ok(n) = raw.parse::<i64>()
+            && n.to_string() == raw
+        {
+            return AttrValue::Int(n);
+        }
+        if let Ok(x) = raw.parse::<f64>()
+            && x.is_finite()
+            && format!("{x:?}") == raw
+        {
+            return AttrValue::Float(x);
+        }
+        AttrValue::Text(raw.to_string())
+    }
+
+    /// 类型名：`int` / `float` / `bool` / `text`。
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            AttrValue::Int(_) => "int",
+            AttrValue::Float(_) => "float",
+            AttrValue::Bool(_) => "bool",
+            AttrValue::Text(_) => "text",
+        }
+    }
+}
+
+// 小数按位比较：`infer` 保证不会有 NaN，于是相等关系是完整的，`Student` 才能继续是 `Eq`。
+impl PartialEq for AttrValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AttrValue::Int(a), AttrValue::Int(b)) => a == b,
+            (AttrValue::Float(a), AttrValue::Float(b)) => a.to_bits() == b.to_bits(),
+            (AttrValue::Bool(a), AttrValue::Bool(b)) => a == b,
+            (AttrValue::Text(a), AttrValue::Text(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AttrValue {}
+
+/// 输出值本身（不带类型），再交给 [`AttrValue::infer`] 能得到同一个值。
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrValue::Int(n) => write!(f, "{n}"),
+            // `{:?}` 保留 `.0`，`1.0` 不会被读回成整数 1。
+            AttrValue::Float(x) => write!(f, "{x:?}"),
+            AttrValue::Bool(b) => write!(f, "{b}"),
+            AttrValue::Text(s) => f.write_str(s),
+        }
+    }
+}
+
+/// 检查属性 key：1..=[`MAX_ATTR_KEY_LEN`] 字节，小写字母开头，只含小写字母、数字、`_`、`-`，
+/// 且不能和固定字段 `id` / `name` / `age` / `class` 重名（导出的表头里会冲突）。
+///
+/// ```
+/// use rust_notes::student::validate_attr_key;
+///
+/// assert!(validate_attr_key("guardian_phone").is_ok());
+/// assert_eq!(
+///     validate_attr_key("Email").unwrap_err().to_string(),
+///     "invalid attribute key `Email`"
+/// );
+/// assert!(validate_attr_key("age").is_err());
+/// ```
+pub fn validate_attr_key(key: &str) -> Result<(), ParseError> {
+    let valid = key.len() <= MAX_ATTR_KEY_LEN
+        && key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        && !matches!(key, "id" | "name" | "age" | "class");
+    if valid {
+        Ok(())
+    } else {
+        Err(ParseError::new("attribute key", key))
+    }
+}
+
+/// 可以排序 / 比较的字段。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    /// 按 id。
+    Id,
+    /// 按名字（按字节序，不折叠大小写）。
+    Name,
+    /// 按年龄。
+    Age,
+    /// 按班级名。
+    Class,
+}
+
+/// 排序方向。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    /// 升序。
+    Asc,
+    /// 降序。
+    Desc,
+}
+
+/// `group` 的分组字段。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupField {
+    /// 按班级分组。
+    Class,
+    /// 按年龄分组。
+    Age,
+}
+
+/// 命令参数（字段名、排序方向、区间等）解析失败。
+///
+/// `Display` 形如 ``invalid field `x` ``，可以直接拼在 `error: ` 后面给用户看。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    what: String,
+    raw: String,
+}
+
+impl ParseError {
+    fn new(what: impl Into<String>, raw: &str) -> Self {
+        Self {
+            what: what.into(),
+            raw: raw.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {} `{}`", self.what, self.raw)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for SortField {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "id" => Ok(SortField::Id),
+            "name" => Ok(SortField::Name),
+            "age" => Ok(SortField::Age),
+            "class" => Ok(SortField::Class),
+            _ => Err(ParseError::new("field", raw)),
+        }
+    }
+}
+
+impl FromStr for SortDirection {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "asc" => Ok(SortDirection::Asc),
+            "desc" => Ok(SortDirection::Desc),
+            _ => Err(ParseError::new("direction", raw)),
+        }
+    }
+}
+
+impl FromStr for GroupField {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "class" => Ok(GroupField::Class),
+            "age" => Ok(GroupField::Age),
+            _ => Err(ParseError::new("group field", raw)),
+        }
+    }
+}
+
+/// 解析 Rust 风格区间：`a..b`（不含 b）、`a..=b`、`a..`、`..b`、`..=b`；
+/// 不带 `..` 时当作单个值 `a..=a`。
+///
+/// `label` 只用于错误信息，如 `"age"` 得到 ``invalid age range `x` ``。
+///
+/// ```
+/// use std::ops::Bound;
+/// use rust_notes::student::parse_range;
+///
+/// assert_eq!(
+///     parse_range::<u8>("10..18", "age"),
+///     Ok((Bound::Included(10), Bound::Excluded(18)))
+/// );
+/// assert!(parse_range::<u8>("10..x", "age").is_err());
+/// ```
+pub fn parse_range<T: FromStr + Copy>(
+    raw: &str,
+    label: &str,
+) -> Result<(Bound<T>, Bound<T>), ParseError> {
+    let parse_end = |v: &str| -> Option<T> { v.parse::<T>().ok() };
+    let range = match raw.split_once("..") {
+        None => parse_end(raw).map(|v| (Bound::Included(v), Bound::Included(v))),
+        Some((start, end)) => {
+            let start = match start {
+                "" => Some(Bound::Unbounded),
+                v => parse_end(v).map(Bound::Included),
+            };
+            let end = match end.strip_prefix('=') {
+                Some(v) => parse_end(v).map(Bound::Included),
+                None if end.is_empty() => Some(Bound::Unbounded),
+                None => parse_end(end).map(Bound::Excluded),
+            };
+            start.zip(end)
+        }
+    };
+    range.ok_or_else(|| ParseError::new(format!("{label} range"), raw))
+}
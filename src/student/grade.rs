@@ -0,0 +1,158 @@
+//! 成绩：分数到等级的映射（[`classify`] / [`GradeBands`]）。成绩本身存在 [`StudentStore`] 里。
+//!
+//! [`StudentStore`]: super::StudentStore
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::ParseError;
+
+/// 分数上限；成绩取值 `0..=MAX_SCORE`。
+pub const MAX_SCORE: u8 = 100;
+
+// Rust 没有 `switch` 关键字；这里用 `match` 做分支匹配。
+// `90..=100` 是闭区间（包含 100），`_` 是兜底分支。
+// `..=` 必须连写成一个语法符号，不能拆成 `.. =`。
+// 对照：`a..b` 是右开区间 `[a, b)`，`a..=b` 是闭区间 `[a, b]`。
+// 返回类型是 `&'static str`：`"A"` 这类字符串字面量存放在静态区，
+// 生命周期覆盖整个程序运行期，因此是 `'static`。
+/// 按固定的 90 / 75 / 60 分档给出等级 `A` / `B` / `C` / `D`。
+///
+/// 在 `0..=100` 上与 [`GradeBands::default`] 一致；需要别的分档时用 [`GradeBands`]。
+///
+/// ```
+/// use rust_notes::student::classify;
+///
+/// assert_eq!(classify(95), "A");
+/// assert_eq!(classify(75), "B");
+/// assert_eq!(classify(59), "D");
+/// ```
+pub fn classify(score: i32) -> &'static str {
+    match score {
+        90..=100 => "A",
+        75..=89 => "B",
+        60..=74 => "C",
+        _ => "D",
+    }
+}
+
+/// 可配置的分档：按下限从高到低排列的 `(下限, 等级)`，都不够时是兜底等级。
+///
+/// 文本写法是 `A=90,B=75,C=60,D`：每段 `等级=下限`，最后一段只有等级，表示兜底。
+///
+/// ```
+/// use rust_notes::student::GradeBands;
+///
+/// let bands = "A+=97,A=90,B=80,F".parse::<GradeBands>().unwrap();
+/// assert_eq!(bands.classify(98), "A+");
+/// assert_eq!(bands.classify(80), "B");
+/// assert_eq!(bands.classify(79), "F");
+/// assert_eq!(GradeBands::default().to_string(), "A=90,B=75,C=60,D");
+/// assert!("A=60,B=75,D".parse::<GradeBands>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradeBands {
+    bands: Vec<(i32, String)>,
+    fallback: String,
+}
+
+impl GradeBands {
+    /// 分数对应的等级：第一个下限不超过它的分档，都不满足时是兜底等级。
+    pub fn classify(&self, score: i32) -> &str {
+        self.bands
+            .iter()
+            .find(|(min, _)| score >= *min)
+            .map_or(&self.fallback, |(_, letter)| letter)
+    }
+}
+
+/// 和 [`classify`] 相同的 90 / 75 / 60 分档。
+impl Default for GradeBands {
+    fn default() -> Self {
+        Self {
+            bands: vec![(90, "A".into()), (75, "B".into()), (60, "C".into())],
+            fallback: "D".into(),
+        }
+    }
+}
+
+impl fmt::Display for GradeBands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (min, letter) in &self.bands {
+            write!(f, "{letter}={min},")?;
+        }
+        f.write_str(&self.fallback)
+    }
+}
+
+// 等级要能直接放进表格和命令参数：非空、没有空白和 `,` `=`。
+fn valid_letter(letter: &str) -> bool {
+    !letter.is_empty() && !letter.contains(|c: char| c.is_whitespace() || c == ',' || c == '=')
+}
+
+/// 下限必须在 `0..=MAX_SCORE` 内严格递减，等级不能重复。
+impl FromStr for GradeBands {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::new("grade bands", raw);
+        let mut parts = raw.split(',').collect::<Vec<&str>>();
+        let fallback = parts
+            .pop()
+            .filter(|f| valid_letter(f))
+            .ok_or_else(invalid)?;
+        let mut bands: Vec<(i32, String)> = Vec::new();
+        for part in parts {
+            let (letter, min) = part.split_once('=').ok_or_else(invalid)?;
+            let min = min
+                .parse::<u8>()
+                .ok()
+                .filter(|m| *m <= MAX_SCORE)
+                .ok_or_else(invalid)?;
+            let min = i32::from(min);
+            let descending = bands.last().is_none_or(|(prev, _)| min < *prev);
+            let duplicate = letter == fallback || bands.iter().any(|(_, l)| l == letter);
+            if !valid_letter(letter) || !descending || duplicate {
+                return Err(invalid());
+            }
+            bands.push((min, letter.to_string()));
+        }
+        Ok(Self {
+            bands,
+            fallback: fallback.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GradeBands, MAX_SCORE, classify};
+
+    #[test]
+    fn test_default_bands_match_classify_and_spec_roundtrips() {
+        let bands = GradeBands::default();
+        for score in 0..=i32::from(MAX_SCORE) {
+            assert_eq!(bands.classify(score), classify(score), "score={score}");
+        }
+
+        let spec = "S=100,A=85,B=70,C";
+        let custom = spec.parse::<GradeBands>().unwrap();
+        assert_eq!(custom.to_string(), spec);
+        assert_eq!(custom.classify(100), "S");
+        assert_eq!(custom.classify(0), "C");
+        // 只有兜底等级也合法：所有分数同一个等级。
+        assert_eq!("P".parse::<GradeBands>().unwrap().classify(3), "P");
+
+        for bad in [
+            "",
+            "A=90,",
+            "A=90,B=90,C",
+            "A=101,B",
+            "A=x,B",
+            "A=90,A",
+            "A 1=90,B",
+        ] {
+            assert!(bad.parse::<GradeBands>().is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! 二级索引与增量统计：`StudentStore` 的 add/remove/modify/回放都经由 `Indexes` 同步它们。
+
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+
+use super::{SortDirection, Student};
+
+// 二级索引的维护接口：StudentStore 只通过它同步索引，不关心具体字段和 key 类型。
+pub(super) trait StudentIndex {
+    fn insert(&mut self, s: &Student);
+    fn remove(&mut self, s: &Student);
+}
+
+// 通用二级索引：字段值 -> 持有该值的 id 集合。
+// key 有序（BTreeMap），所以既能精确查，也能做范围查询和按字段有序遍历；
+// 同一个 key 下的 id 也有序，天然满足“同值再按 id”的 tie-break。
+#[derive(Debug, Clone)]
+pub(super) struct SecondaryIndex<K> {
+    key_of: fn(&Student) -> K,
+    pub(super) entries: BTreeMap<K, BTreeSet<u32>>,
+}
+
+impl<K: Ord> SecondaryIndex<K> {
+    fn new(key_of: fn(&Student) -> K) -> Self {
+        Self {
+            key_of,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    // 一条记录在这个索引里应该挂在哪个 key 下。
+    pub(super) fn key(&self, s: &Student) -> K {
+        (self.key_of)(s)
+    }
+
+    pub(super) fn get<Q>(&self, key: &Q) -> impl Iterator<Item = u32> + '_
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entries.get(key).into_iter().flatten().copied()
+    }
+
+    // 非法区间（起点大于终点）返回空，而不是让 BTreeMap::range panic。
+    pub(super) fn range(&self, range: (Bound<K>, Bound<K>)) -> impl Iterator<Item = u32> + '_ {
+        let entries = if range_is_valid(&range) {
+            Some(self.entries.range(range))
+        } else {
+            None
+        };
+        entries
+            .into_iter()
+            .flatten()
+            .flat_map(|(_, ids)| ids.iter().copied())
+    }
+
+    // 按 (key, id) 升序或整体降序遍历全部 id。
+    pub(super) fn ids(&self, direction: SortDirection) -> Box<dyn Iterator<Item = u32> + '_> {
+        match direction {
+            SortDirection::Asc => Box::new(self.entries.values().flatten().copied()),
+            SortDirection::Desc => Box::new(
+                self.entries
+                    .values()
+                    .rev()
+                    .flat_map(|ids| ids.iter().rev())
+                    .copied(),
+            ),
+        }
+    }
+}
+
+impl<K: Ord> StudentIndex for SecondaryIndex<K> {
+    fn insert(&mut self, s: &Student) {
+        self.entries
+            .entry((self.key_of)(s))
+            .or_default()
+            .insert(s.id);
+    }
+
+    // 桶空了就整个删掉，避免留下没有 id 的空 key。
+    fn remove(&mut self, s: &Student) {
+        let key = (self.key_of)(s);
+        if let Some(ids) = self.entries.get_mut(&key) {
+            ids.remove(&s.id);
+            if ids.is_empty() {
+                self.entries.remove(&key);
+            }
+        }
+    }
+}
+
+impl SecondaryIndex<String> {
+    // 字符串 key 的前缀查询：从 prefix 开始顺序扫，遇到第一个不以它开头的 key 就停。
+    pub(super) fn prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = u32> + 'a {
+        self.entries
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(key, _)| key.starts_with(prefix))
+            .flat_map(|(_, ids)| ids.iter().copied())
+    }
+}
+
+// 只比较索引内容；key_of 是函数指针，比较它没有意义。
+impl<K: PartialEq> PartialEq for SecondaryIndex<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+/// 一组学生的年龄汇总，由 [`StudentStore::stats`](super::StudentStore::stats) 返回。
+///
+/// 内部记人数、年龄和，以及“年龄 -> 人数”直方图：直方图有序，min/max 取首尾 key 即可；
+/// 删除时只需把对应桶减一，不用重扫。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgeSummary {
+    count: usize,
+    age_sum: u64,
+    ages: BTreeMap<u8, usize>,
+}
+
+impl AgeSummary {
+    fn add(&mut self, age: u8) {
+        self.count += 1;
+        self.age_sum += u64::from(age);
+        *self.ages.entry(age).or_default() += 1;
+    }
+
+    fn sub(&mut self, age: u8) {
+        if let Some(n) = self.ages.get_mut(&age) {
+            *n -= 1;
+            if *n == 0 {
+                self.ages.remove(&age);
+            }
+            self.count -= 1;
+            self.age_sum -= u64::from(age);
+        }
+    }
+
+    /// 人数。
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// 最小年龄；没有学生时为 `None`。
+    pub fn min(&self) -> Option<u8> {
+        self.ages.keys().next().copied()
+    }
+
+    /// 最大年龄；没有学生时为 `None`。
+    pub fn max(&self) -> Option<u8> {
+        self.ages.keys().next_back().copied()
+    }
+
+    /// 平均年龄；没有学生时为 `None`。
+    pub fn average(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.age_sum as f64 / self.count as f64)
+    }
+}
+
+/// 增量维护的统计：全体一份，每个班级一份。
+///
+/// 和二级索引走同一条维护路径，所以读取统计不需要扫描全部学生。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClassStats {
+    total: AgeSummary,
+    by_class: BTreeMap<String, AgeSummary>,
+}
+
+impl ClassStats {
+    /// 全体学生的汇总。
+    pub fn total(&self) -> &AgeSummary {
+        &self.total
+    }
+
+    /// 各班级的汇总，按班级名升序；没有学生的班级不会出现。
+    pub fn classes(&self) -> impl Iterator<Item = (&str, &AgeSummary)> {
+        self.by_class
+            .iter()
+            .map(|(class_name, summary)| (class_name.as_str(), summary))
+    }
+
+    /// 某个班级的汇总。
+    pub fn class(&self, class_name: &str) -> Option<&AgeSummary> {
+        self.by_class.get(class_name)
+    }
+}
+
+impl StudentIndex for ClassStats {
+    fn insert(&mut self, s: &Student) {
+        self.total.add(s.age);
+        self.by_class
+            .entry(s.class_name.clone())
+            .or_default()
+            .add(s.age);
+    }
+
+    fn remove(&mut self, s: &Student) {
+        self.total.sub(s.age);
+        if let Some(summary) = self.by_class.get_mut(&s.class_name) {
+            summary.sub(s.age);
+            if summary.count == 0 {
+                self.by_class.remove(&s.class_name);
+            }
+        }
+    }
+}
+
+// StudentStore 的全部二级索引。新增字段索引时：加一个字段，在 new 里给出取值函数，
+// 再登记进 all_mut；add/remove/modify/load/回放都会自动维护它。
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Indexes {
+    pub(super) name: SecondaryIndex<String>,
+    // 折叠后的名字（见 fold_name），服务 prefix/fuzzy 这类“不区分大小写”的查询。
+    pub(super) name_folded: SecondaryIndex<String>,
+    pub(super) class: SecondaryIndex<String>,
+    pub(super) age: SecondaryIndex<u8>,
+    pub(super) stats: ClassStats,
+}
+
+impl Indexes {
+    pub(super) fn new() -> Self {
+        Self {
+            name: SecondaryIndex::new(|s| s.name.clone()),
+            name_folded: SecondaryIndex::new(|s| fold_name(&s.name)),
+            class: SecondaryIndex::new(|s| s.class_name.clone()),
+            age: SecondaryIndex::new(|s| s.age),
+            stats: ClassStats::default(),
+        }
+    }
+
+    fn all_mut(&mut self) -> [&mut dyn StudentIndex; 5] {
+        [
+            &mut self.name,
+            &mut self.name_folded,
+            &mut self.class,
+            &mut self.age,
+            &mut self.stats,
+        ]
+    }
+
+    pub(super) fn insert(&mut self, s: &Student) {
+        for index in self.all_mut() {
+            index.insert(s);
+        }
+    }
+
+    pub(super) fn remove(&mut self, s: &Student) {
+        for index in self.all_mut() {
+            index.remove(s);
+        }
+    }
+}
+
+// 名字比较用的折叠形式：小写化、全角 ASCII 转半角、去掉拉丁字母上的重音。
+// 这不是完整的 Unicode NFKC（那需要很大的映射表），但足以让
+// `José` / `jose` / `ＪＯＳＥ` 互相匹配；中文等无大小写的文字保持原样。
+pub(super) fn fold_name(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        let c = match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        };
+        // 组合用附加符号（分解形式的重音），直接丢掉。
+        if ('\u{0300}'..='\u{036F}').contains(&c) {
+            continue;
+        }
+        for lower in c.to_lowercase() {
+            out.push(strip_accent(lower));
+        }
+    }
+    out
+}
+
+// 预组合形式的常见拉丁重音字母 -> 基本字母。
+fn strip_accent(c: char) -> char {
+    match c {
+        'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+        'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+        'ł' | 'ľ' | 'ĺ' | 'ļ' => 'l',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+        'ŕ' | 'ř' => 'r',
+        'ś' | 'ŝ' | 'ş' | 'š' => 's',
+        'ţ' | 'ť' => 't',
+        'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+        'ý' | 'ÿ' | 'ŷ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => c,
+    }
+}
+
+// 按字符（不是字节）计算 Levenshtein 距离，中文一个字算一个编辑单位。
+// 超过 max 时提前返回 None：长度差已超限直接跳过，某一行最小值超限就停止。
+pub(super) fn edit_distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev = (0..=b.len()).collect::<Vec<usize>>();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        let mut row_min = cur[0];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+            row_min = row_min.min(cur[j + 1]);
+        }
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    let dist = prev[b.len()];
+    (dist <= max).then_some(dist)
+}
+
+// BTreeMap/BTreeSet::range 在起点大于终点（或相等且两端都开）时会 panic。
+pub(super) fn range_is_valid<T: Ord>(range: &(Bound<T>, Bound<T>)) -> bool {
+    match range {
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) => {
+            start < end
+                || (start == end && !matches!(range, (Bound::Excluded(_), Bound::Excluded(_))))
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, fold_name};
+
+    #[test]
+    fn test_fold_name_and_edit_distance() {
+        assert_eq!(fold_name("José"), "jose");
+        assert_eq!(fold_name("Jose\u{0301}"), "jose");
+        assert_eq!(fold_name("ＪＯＳＥ"), "jose");
+        assert_eq!(fold_name("张三"), "张三");
+        let chars = |s: &str| s.chars().collect::<Vec<char>>();
+        assert_eq!(edit_distance(&chars("张三"), &chars("张三丰"), 2), Some(1));
+        assert_eq!(
+            edit_distance(&chars("kitten"), &chars("sitting"), 3),
+            Some(3)
+        );
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting"), 2), None);
+    }
+}
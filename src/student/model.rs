@@ -0,0 +1,502 @@
+//! 基于模型的随机测试：同一串随机命令分别交给 [`StudentStore`] 和一个一眼能看懂的参考模型
+//! （`Vec<Student>` + 线性扫描 + 排序），每一步都比较可观察的结果。
+//!
+//! - 命令序列由固定种子的伪随机数生成，失败可以复现；
+//! - 出现分歧时自动缩减（删掉不影响结果的命令），把最小复现打印成 REPL 命令，
+//!   可以直接粘进 `19_demo` 里重放；
+//! - 除了每条命令的返回值，每一步之后还比较全部记录，并要求 [`StudentStore::verify`] 为空。
+//!
+//! 整个模块只在测试时编译。
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+
+use crate::rng::Rng;
+
+use super::index::fold_name;
+use super::{
+    DEFAULT_HISTORY_LIMIT, SortDirection, SortField, StoreError, Student, StudentStore, parse_range,
+};
+
+// 名字故意有大小写、重音、全角和中文，让精确匹配、前缀折叠和排序都有得比；
+// 不含空白，打印成 REPL 命令时不需要加引号。
+const NAMES: &[&str] = &["amy", "Amy", "amos", "bob", "Zoë", "zoe", "ＢＯＢ", "张三"];
+const PREFIXES: &[&str] = &["a", "AM", "b", "zo", "张", "x"];
+// `Z9` 从不建班，用来覆盖“班级不存在”的错误路径。
+const CLASSES: &[&str] = &["A1", "B2", "Z9"];
+const REGISTERED: &[&str] = &["A1", "B2"];
+const FIELDS: &[&str] = &["id", "name", "age", "class"];
+const DIRECTIONS: &[&str] = &["asc", "desc"];
+
+/// 随机生成的一条命令，字段都是 REPL 里的写法。
+#[derive(Debug, Clone, PartialEq)]
+enum Cmd {
+    Add {
+        name: &'static str,
+        age: u8,
+        class: &'static str,
+    },
+    Remove(u32),
+    Modify {
+        id: u32,
+        name: &'static str,
+        age: u8,
+        class: &'static str,
+    },
+    Undo,
+    Redo,
+    List,
+    SearchId(u32),
+    SearchName(&'static str),
+    SearchClass(&'static str),
+    SearchAge(String),
+    SearchPrefix(&'static str),
+    Order(&'static str, &'static str),
+}
+
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cmd::Add { name, age, class } => write!(f, "add {name} {age} {class}"),
+            Cmd::Remove(id) => write!(f, "remove {id}"),
+            Cmd::Modify {
+                id,
+                name,
+                age,
+                class,
+            } => write!(f, "mod {id} {name} {age} {class}"),
+            Cmd::Undo => write!(f, "undo"),
+            Cmd::Redo => write!(f, "redo"),
+            Cmd::List => write!(f, "list"),
+            Cmd::SearchId(id) => write!(f, "search id {id}"),
+            Cmd::SearchName(name) => write!(f, "search name {name}"),
+            Cmd::SearchClass(class) => write!(f, "search class {class}"),
+            Cmd::SearchAge(range) => write!(f, "search age {range}"),
+            Cmd::SearchPrefix(prefix) => write!(f, "search prefix {prefix}"),
+            Cmd::Order(field, direction) => write!(f, "order {field} {direction}"),
+        }
+    }
+}
+
+// 年龄取值范围很小，制造大量同龄（排序要靠 id 打破平局）；偶尔取到边界 255。
+fn gen_age(rng: &mut Rng) -> u8 {
+    if rng.below(20) == 0 {
+        u8::MAX
+    } else {
+        rng.below(6) as u8 + 8
+    }
+}
+
+// 单边、双边、含 / 不含终点，以及起点大于终点的非法区间。
+fn gen_age_range(rng: &mut Rng) -> String {
+    let (lo, hi) = (gen_age(rng), gen_age(rng));
+    match rng.below(5) {
+        0 => format!("{lo}"),
+        1 => format!("{lo}.."),
+        2 => format!("..{hi}"),
+        3 => format!("{lo}..{hi}"),
+        _ => format!("{lo}..={hi}"),
+    }
+}
+
+// id 多数落在已分配的范围里，也会取到从没分配过的 id。
+fn gen_cmd(rng: &mut Rng, max_id: u32) -> Cmd {
+    let id = rng.below(max_id + 2);
+    let name = rng.pick(NAMES);
+    let class = rng.pick(CLASSES);
+    match rng.below(100) {
+        0..30 => Cmd::Add {
+            name,
+            age: gen_age(rng),
+            class,
+        },
+        30..42 => Cmd::Remove(id),
+        42..54 => Cmd::Modify {
+            id,
+            name,
+            age: gen_age(rng),
+            class,
+        },
+        54..60 => Cmd::Undo,
+        60..63 => Cmd::Redo,
+        63..67 => Cmd::List,
+        67..72 => Cmd::SearchId(id),
+        72..78 => Cmd::SearchName(name),
+        78..82 => Cmd::SearchClass(class),
+        82..88 => Cmd::SearchAge(gen_age_range(rng)),
+        88..92 => Cmd::SearchPrefix(rng.pick(PREFIXES)),
+        _ => Cmd::Order(rng.pick(FIELDS), rng.pick(DIRECTIONS)),
+    }
+}
+
+fn gen_cmds(seed: u64, len: usize) -> Vec<Cmd> {
+    let mut rng = Rng::new(seed);
+    let mut adds = 0;
+    (0..len)
+        .map(|_| {
+            let cmd = gen_cmd(&mut rng, adds);
+            if matches!(cmd, Cmd::Add { .. }) {
+                adds += 1;
+            }
+            cmd
+        })
+        .collect()
+}
+
+/// 一条命令可观察到的结果。错误只比较文本，模型用同样的 `StoreError` 变体生成。
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Added(u32),
+    Done,
+    Failed(String),
+    Rows(Vec<Student>),
+}
+
+fn age_range(raw: &str) -> (Bound<u8>, Bound<u8>) {
+    parse_range(raw, "age").expect("generated age range parses")
+}
+
+// 参考模型：不建任何索引，查询都是“全部过滤一遍再整体排序”。
+// undo / redo 直接存整份记录的快照；next_id 只增不减，和 store 一样不复用 id。
+struct Model {
+    students: Vec<Student>,
+    next_id: u32,
+    undo: VecDeque<Vec<Student>>,
+    redo: Vec<Vec<Student>>,
+}
+
+impl Model {
+    fn new() -> Self {
+        Self {
+            students: Vec::new(),
+            next_id: 1,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    // 每次成功的修改之前调用：记下旧状态，清空 redo。
+    fn checkpoint(&mut self) {
+        self.undo.push_back(self.students.clone());
+        if self.undo.len() > DEFAULT_HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    fn sorted_by<K: Ord>(
+        &self,
+        keep: impl Fn(&Student) -> bool,
+        key: impl Fn(&Student) -> K,
+    ) -> Vec<Student> {
+        let mut rows = self
+            .students
+            .iter()
+            .filter(|s| keep(s))
+            .cloned()
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|s| (key(s), s.id));
+        rows
+    }
+
+    fn list(&self) -> Vec<Student> {
+        self.sorted_by(|_| true, |_| ())
+    }
+
+    fn run(&mut self, cmd: &Cmd) -> Outcome {
+        let failed = |e: StoreError| Outcome::Failed(e.to_string());
+        match cmd {
+            Cmd::Add { name, age, class } => {
+                if !REGISTERED.contains(class) {
+                    return failed(StoreError::UnknownClass(class.to_string()));
+                }
+                self.checkpoint();
+                let id = self.next_id;
+                self.next_id += 1;
+                self.students.push(Student {
+                    id,
+                    name: name.to_string(),
+                    age: *age,
+                    class_name: class.to_string(),
+                    attrs: Default::default(),
+                });
+                Outcome::Added(id)
+            }
+            Cmd::Remove(id) => match self.students.iter().position(|s| s.id == *id) {
+                Some(pos) => {
+                    self.checkpoint();
+                    self.students.remove(pos);
+                    Outcome::Done
+                }
+                None => failed(StoreError::NotFound(*id)),
+            },
+            Cmd::Modify {
+                id,
+                name,
+                age,
+                class,
+            } => {
+                let Some(pos) = self.students.iter().position(|s| s.id == *id) else {
+                    return failed(StoreError::NotFound(*id));
+                };
+                if !REGISTERED.contains(class) {
+                    return failed(StoreError::UnknownClass(class.to_string()));
+                }
+                self.checkpoint();
+                let s = &mut self.students[pos];
+                s.name = name.to_string();
+                s.age = *age;
+                s.class_name = class.to_string();
+                Outcome::Done
+            }
+            Cmd::Undo => match self.undo.pop_back() {
+                Some(prev) => {
+                    self.redo.push(std::mem::replace(&mut self.students, prev));
+                    Outcome::Done
+                }
+                None => failed(StoreError::NothingToUndo),
+            },
+            Cmd::Redo => match self.redo.pop() {
+                Some(next) => {
+                    self.undo
+                        .push_back(std::mem::replace(&mut self.students, next));
+                    Outcome::Done
+                }
+                None => failed(StoreError::NothingToRedo),
+            },
+            Cmd::List => Outcome::Rows(self.list()),
+            Cmd::SearchId(id) => Outcome::Rows(self.sorted_by(|s| s.id == *id, |_| ())),
+            Cmd::SearchName(name) => Outcome::Rows(self.sorted_by(|s| s.name == *name, |_| ())),
+            Cmd::SearchClass(class) => {
+                Outcome::Rows(self.sorted_by(|s| s.class_name == *class, |_| ()))
+            }
+            Cmd::SearchAge(raw) => {
+                let range = age_range(raw);
+                Outcome::Rows(self.sorted_by(|s| range.contains(&s.age), |s| s.age))
+            }
+            Cmd::SearchPrefix(prefix) => {
+                let prefix = fold_name(prefix);
+                Outcome::Rows(self.sorted_by(
+                    |s| fold_name(&s.name).starts_with(&prefix),
+                    |s| fold_name(&s.name),
+                ))
+            }
+            Cmd::Order(field, direction) => {
+                let mut rows = match field.parse::<SortField>().unwrap() {
+                    SortField::Id => self.list(),
+                    SortField::Name => self.sorted_by(|_| true, |s| s.name.clone()),
+                    SortField::Age => self.sorted_by(|_| true, |s| s.age),
+                    SortField::Class => self.sorted_by(|_| true, |s| s.class_name.clone()),
+                };
+                if direction.parse::<SortDirection>().unwrap() == SortDirection::Desc {
+                    rows.reverse();
+                }
+                Outcome::Rows(rows)
+            }
+        }
+    }
+}
+
+// 被测系统：一条命令在 store 上的执行方式。正常测试用 `exec_store`，
+// 缩减器自己的测试换成故意带 bug 的版本。
+type Exec = fn(&mut StudentStore, &Cmd) -> Outcome;
+
+fn exec_store(store: &mut StudentStore, cmd: &Cmd) -> Outcome {
+    let done = |r: Result<(), StoreError>| match r {
+        Ok(()) => Outcome::Done,
+        Err(e) => Outcome::Failed(e.to_string()),
+    };
+    let rows = |rows: Vec<&Student>| Outcome::Rows(rows.into_iter().cloned().collect());
+    match cmd {
+        Cmd::Add { name, age, class } => match store.add(name, *age, class) {
+            Ok(id) => Outcome::Added(id),
+            Err(e) => Outcome::Failed(e.to_string()),
+        },
+        Cmd::Remove(id) => done(store.remove(*id)),
+        Cmd::Modify {
+            id,
+            name,
+            age,
+            class,
+        } => done(store.modify(*id, name, *age, class)),
+        Cmd::Undo => done(store.undo().map(drop)),
+        Cmd::Redo => done(store.redo().map(drop)),
+        Cmd::List => rows(store.list_by_id()),
+        Cmd::SearchId(id) => rows(store.get_by_id(*id).into_iter().collect()),
+        Cmd::SearchName(name) => rows(store.search_by_name_exact(name)),
+        Cmd::SearchClass(class) => rows(store.search_by_class(class)),
+        Cmd::SearchAge(raw) => rows(store.search_by_age(age_range(raw))),
+        Cmd::SearchPrefix(prefix) => rows(store.search_by_name_prefix(prefix)),
+        Cmd::Order(field, direction) => {
+            rows(store.ordered(field.parse().unwrap(), direction.parse().unwrap()))
+        }
+    }
+}
+
+/// 第一次分歧：第几条命令（从 0 开始）、哪里不一致、两边各是什么。
+#[derive(Debug)]
+struct Divergence {
+    step: usize,
+    what: &'static str,
+    model: String,
+    store: String,
+}
+
+// 每一步比较三样：命令的结果、之后的全部记录、store 自己的一致性检查。
+fn run_cmds(cmds: &[Cmd], exec: Exec) -> Result<(), Divergence> {
+    let mut store = StudentStore::new();
+    for class in REGISTERED {
+        store.add_class(class).unwrap();
+    }
+    // 建班不算在命令序列里，模型的 undo 历史也从空开始。
+    store.undo_stack.clear();
+    let mut model = Model::new();
+    for (step, cmd) in cmds.iter().enumerate() {
+        let diverge = |what, model: String, store: String| Divergence {
+            step,
+            what,
+            model,
+            store,
+        };
+        let (expected, got) = (model.run(cmd), exec(&mut store, cmd));
+        if expected != got {
+            return Err(diverge(
+                "result",
+                format!("{expected:?}"),
+                format!("{got:?}"),
+            ));
+        }
+        let listed = store.list_by_id().into_iter().cloned().collect::<Vec<_>>();
+        if model.list() != listed {
+            return Err(diverge(
+                "records",
+                format!("{:?}", model.list()),
+                format!("{listed:?}"),
+            ));
+        }
+        let issues = store.verify();
+        if !issues.is_empty() {
+            return Err(diverge("verify", "[]".into(), format!("{issues:?}")));
+        }
+    }
+    Ok(())
+}
+
+// 缩减：先按大块、再按小块尝试删掉一段命令，删了之后仍然出现分歧就保留删除。
+// 块大小减到 1 且整轮删不掉任何一条时停止，此时去掉任意一条命令都不再失败。
+fn shrink(mut cmds: Vec<Cmd>, exec: Exec) -> Vec<Cmd> {
+    let mut chunk = cmds.len().div_ceil(2).max(1);
+    loop {
+        let mut removed = false;
+        let mut start = 0;
+        while start < cmds.len() {
+            let end = (start + chunk).min(cmds.len());
+            let candidate = [&cmds[..start], &cmds[end..]].concat();
+            if run_cmds(&candidate, exec).is_err() {
+                cmds = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if chunk == 1 && !removed {
+            return cmds;
+        }
+        if !removed {
+            chunk = chunk.div_ceil(2);
+        }
+    }
+}
+
+// 最小复现写成 REPL 脚本：先建班，再逐条命令；分歧那一行标出来。
+fn repro_script(cmds: &[Cmd], divergence: &Divergence) -> String {
+    let mut out = String::new();
+    for class in REGISTERED {
+        out.push_str(&format!("class add {class}\n"));
+    }
+    for (i, cmd) in cmds.iter().enumerate() {
+        out.push_str(&cmd.to_string());
+        if i == divergence.step {
+            out.push_str(&format!("    # <- {} differs", divergence.what));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// 跑一批种子；有分歧时缩减并返回报告，里面带种子、分歧和可以粘进 REPL 的复现脚本。
+fn check_seeds(seeds: std::ops::Range<u64>, len: usize, exec: Exec) -> Result<(), String> {
+    for seed in seeds {
+        let cmds = gen_cmds(seed, len);
+        if run_cmds(&cmds, exec).is_ok() {
+            continue;
+        }
+        let minimal = shrink(cmds, exec);
+        let divergence = run_cmds(&minimal, exec).expect_err("shrunk sequence still fails");
+        return Err(format!(
+            "seed {seed}: {} differs at step {} `{}`\n  model: {}\n  store: {}\nminimal reproduction ({} commands):\n{}",
+            divergence.what,
+            divergence.step,
+            minimal[divergence.step],
+            divergence.model,
+            divergence.store,
+            minimal.len(),
+            repro_script(&minimal, &divergence),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cmd, Outcome, check_seeds, exec_store, gen_cmds};
+    use crate::student::StudentStore;
+
+    #[test]
+    fn test_store_matches_model() {
+        if let Err(report) = check_seeds(0..300, 80, exec_store) {
+            panic!("{report}");
+        }
+    }
+
+    #[test]
+    fn test_generator_is_deterministic_and_covers_every_command() {
+        assert_eq!(gen_cmds(7, 50), gen_cmds(7, 50));
+        assert_ne!(gen_cmds(7, 50), gen_cmds(8, 50));
+        let cmds = gen_cmds(1, 2000);
+        let kinds = cmds
+            .iter()
+            .map(std::mem::discriminant)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(kinds.len(), 12);
+    }
+
+    // 故意带 bug 的 store：同龄学生按年龄降序排时把平局顺序弄反。
+    fn exec_buggy(store: &mut StudentStore, cmd: &Cmd) -> Outcome {
+        match (cmd, exec_store(store, cmd)) {
+            (Cmd::Order("age", "desc"), Outcome::Rows(mut rows)) => {
+                rows.sort_by_key(|s| (std::cmp::Reverse(s.age), s.id));
+                Outcome::Rows(rows)
+            }
+            (_, outcome) => outcome,
+        }
+    }
+
+    #[test]
+    fn test_shrinks_failure_to_minimal_repl_script() {
+        let report = check_seeds(0..50, 80, exec_buggy).unwrap_err();
+        // 最小复现：两个同龄学生加一条 `order age desc`，不多不少。
+        assert!(
+            report.contains("minimal reproduction (3 commands):\nclass add A1\nclass add B2\nadd "),
+            "{report}"
+        );
+        let script = report.split_once("commands):\n").unwrap().1;
+        let lines = script.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5, "{report}");
+        assert!(lines[2].starts_with("add ") && lines[3].starts_with("add "));
+        assert_eq!(lines[4], "order age desc    # <- result differs");
+        let age = |line: &str| line.split(' ').nth(2).unwrap().to_string();
+        assert_eq!(age(lines[2]), age(lines[3]));
+    }
+}
@@ -0,0 +1,600 @@
+//! 文件分页后端 `PagedStore`：记录放在固定大小的页里，内存只保留 id -> 槽位的映射
+//! 和少量缓存页，学生数量再多，常驻内存也只是每人十几个字节。
+//!
+//! 文件布局（整数一律小端）：
+//!
+//! ```text
+//! page 0      文件头：magic `SMSPAGE1`(8) | next_id u32 | 其余补 0
+//! page 1..N   数据页：crc32 u32 | 保留到 128 字节 | 31 个 128 字节槽位
+//! 槽位         used u8 | id u32 | age u8 | name_len u8 | class_len u8 | name[60] | class[60]
+//! ```
+//!
+//! - 每次修改只重写一个数据页（外加 add 时的文件头），写完 `sync_data` 才返回。
+//! - 数据页原地覆盖前先整页写进 `<path>.journal`（双写）：
+//!
+//! ```text
+//! journal     page_no u64 | page[4096] | crc32 u32（对前两段）
+//! ```
+//!
+//!   日志只有这一条，每次写页都覆盖它并先 fsync。原地写到一半崩溃、这一页 crc 对不上时，
+//!   打开文件会先用日志里的完整副本重做；日志本身没写完（crc 不符）说明原页还没动过，直接忽略。
+//! - 数据页带 crc32，打开时整份扫描一遍：校验每页、重建 id -> 槽位映射和空闲槽位。
+//! - 删除只把槽位标成空闲，之后的 add 优先复用最小的空闲槽位；id 本身不复用。
+//! - 槽位是定长的，放不下自定义属性：这个后端读出的记录 `attrs` 总是空的。
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use super::persist::{PersistError, crc32};
+use super::repository::StudentRepository;
+use super::store::StoreError;
+use super::{Attrs, Student};
+
+/// 页大小（字节）。
+pub const PAGE_SIZE: usize = 4096;
+/// `name` / `class` 各自最多占多少字节（UTF-8 编码后）。
+pub const MAX_FIELD_BYTES: usize = 60;
+
+const SLOT_SIZE: usize = 128;
+const PAGE_HEADER_SIZE: usize = 128;
+const SLOTS_PER_PAGE: u64 = ((PAGE_SIZE - PAGE_HEADER_SIZE) / SLOT_SIZE) as u64;
+const MAGIC: &[u8; 8] = b"SMSPAGE1";
+// 最多缓存多少页；超出时淘汰最久没用过的。
+const CACHE_PAGES: usize = 16;
+// 双写日志的一条记录：页号 + 整页 + crc。
+const JOURNAL_SIZE: usize = 8 + PAGE_SIZE + 4;
+
+type Page = [u8; PAGE_SIZE];
+
+// 文件读写 + LRU 页缓存。读操作也要改缓存，所以放在 Mutex 里，
+// 这样 `PagedStore` 的只读方法仍然是 `&self`，并且可以跨线程共享。
+#[derive(Debug)]
+struct Pager {
+    file: File,
+    // 双写日志 `<path>.journal`，只保存最近一次写的数据页。
+    journal: File,
+    cache: HashMap<u64, Box<Page>>,
+    // 队尾是最近用过的页。
+    lru: VecDeque<u64>,
+}
+
+impl Pager {
+    // 缓存命中直接返回；否则从磁盘读并校验 crc（文件头页除外）。
+    fn read_page(&mut self, page_no: u64) -> io::Result<&Page> {
+        if !self.cache.contains_key(&page_no) {
+            let mut page = Box::new([0; PAGE_SIZE]);
+            self.file
+                .seek(SeekFrom::Start(page_no * PAGE_SIZE as u64))?;
+            self.file.read_exact(page.as_mut_slice())?;
+            if page_no > 0 && !page_is_sealed(&page) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("page {page_no} checksum mismatch"),
+                ));
+            }
+            self.cache.insert(page_no, page);
+        }
+        self.touch(page_no);
+        Ok(&self.cache[&page_no])
+    }
+
+    // 写穿：先写文件，再更新缓存；调用方负责最后的 sync。
+    fn write_page(&mut self, page_no: u64, page: &Page) -> io::Result<()> {
+        self.file
+            .seek(SeekFrom::Start(page_no * PAGE_SIZE as u64))?;
+        self.file.write_all(page)?;
+        self.cache.insert(page_no, Box::new(*page));
+        self.touch(page_no);
+        Ok(())
+    }
+
+    // 数据页的写入：先把整页写进双写日志并 fsync，再原地覆盖。
+    // 文件头页不走这里：magic 和 next_id 都在前 12 字节，而且 next_id 打开时会按数据重算。
+    fn write_data_page(&mut self, page_no: u64, page: &Page) -> io::Result<()> {
+        let mut record = Vec::with_capacity(JOURNAL_SIZE);
+        record.extend_from_slice(&page_no.to_le_bytes());
+        record.extend_from_slice(page);
+        let crc = crc32(&record);
+        record.extend_from_slice(&crc.to_le_bytes());
+        self.journal.seek(SeekFrom::Start(0))?;
+        self.journal.write_all(&record)?;
+        self.journal.sync_data()?;
+        self.write_page(page_no, page)
+    }
+
+    // 打开时重做日志里的那一页。日志总是某个数据页的最新版本，重做一次已经写好的页也无妨；
+    // 返回重做的页号，日志为空或没写完时返回 None。
+    fn replay_journal(&mut self) -> io::Result<Option<u64>> {
+        let mut record = Vec::new();
+        self.journal.seek(SeekFrom::Start(0))?;
+        self.journal.read_to_end(&mut record)?;
+        if record.len() != JOURNAL_SIZE {
+            return Ok(None);
+        }
+        let (body, crc) = record.split_at(8 + PAGE_SIZE);
+        if crc != crc32(body).to_le_bytes() {
+            return Ok(None);
+        }
+        let page_no = u64::from_le_bytes(body[..8].try_into().expect("8 bytes"));
+        let page = body[8..].try_into().expect("one page");
+        if page_no == 0 {
+            return Ok(None);
+        }
+        self.write_page(page_no, page)?;
+        self.file.sync_all()?;
+        // 重做只是为了修磁盘上的页，缓存从扫描时重新读。
+        self.cache.clear();
+        self.lru.clear();
+        Ok(Some(page_no))
+    }
+
+    fn touch(&mut self, page_no: u64) {
+        self.lru.retain(|n| *n != page_no);
+        self.lru.push_back(page_no);
+        while self.lru.len() > CACHE_PAGES {
+            if let Some(evicted) = self.lru.pop_front() {
+                self.cache.remove(&evicted);
+            }
+        }
+    }
+
+    fn read_slot(&mut self, slot: u64) -> io::Result<Option<Student>> {
+        let (page_no, offset) = slot_position(slot);
+        let page = self.read_page(page_no)?;
+        decode_slot(&page[offset..offset + SLOT_SIZE])
+            .map_err(|reason| io::Error::new(io::ErrorKind::InvalidData, reason))
+    }
+
+    fn write_slot(&mut self, slot: u64, student: Option<&Student>) -> io::Result<()> {
+        let (page_no, offset) = slot_position(slot);
+        let mut page = *self.read_page(page_no)?;
+        encode_slot(&mut page[offset..offset + SLOT_SIZE], student);
+        seal_page(&mut page);
+        self.write_data_page(page_no, &page)
+    }
+}
+
+/// 文件分页的 [`StudentRepository`] 实现：记录不常驻内存。
+///
+/// 只支持 trait 里的基本操作；二级索引、undo、事务、查询语言等仍只有 [`StudentStore`](super::StudentStore) 提供。
+/// `search_by_name_exact` 没有索引，会顺序扫描全部数据页。
+#[derive(Debug)]
+pub struct PagedStore {
+    pager: Mutex<Pager>,
+    // 主索引：id -> 槽位编号（第几个槽，从 0 开始，跨页连续编号）。
+    slots: BTreeMap<u32, u64>,
+    // 空闲槽位，add 时取最小的。
+    free: BTreeSet<u64>,
+    next_id: u32,
+    // 含文件头页在内的总页数。
+    page_count: u64,
+}
+
+impl PagedStore {
+    /// 打开（不存在则创建）分页数据文件和它的双写日志 `<path>.journal`。
+    ///
+    /// 先用日志重做上次可能没写完的数据页，再扫描全部数据页：文件长度不是整页、magic 不对、
+    /// 某页 crc 不符或槽位内容非法时返回 [`PersistError::Page`]。崩溃撕裂的页会被重做修好，
+    /// 仍然报错说明是日志之外的损坏（比如磁盘坏了、文件被改过）。
+    pub fn open(path: &Path) -> Result<Self, PersistError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut journal_path = path.as_os_str().to_owned();
+        journal_path.push(".journal");
+        let journal = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(PathBuf::from(journal_path))?;
+        let mut pager = Pager {
+            file,
+            journal,
+            cache: HashMap::new(),
+            lru: VecDeque::new(),
+        };
+        pager.replay_journal()?;
+        let len = pager.file.metadata()?.len();
+        let mut store = PagedStore {
+            pager: Mutex::new(pager),
+            slots: BTreeMap::new(),
+            free: BTreeSet::new(),
+            next_id: 1,
+            page_count: 1,
+        };
+        if len == 0 {
+            store.write_header()?;
+            store.pager().file.sync_all()?;
+            return Ok(store);
+        }
+        if len % PAGE_SIZE as u64 != 0 {
+            return Err(page_error(len / PAGE_SIZE as u64, "truncated page"));
+        }
+        store.page_count = len / PAGE_SIZE as u64;
+        store.scan()?;
+        Ok(store)
+    }
+
+    // 读文件头和全部数据页，重建 slots/free/next_id。
+    fn scan(&mut self) -> Result<(), PersistError> {
+        let mut pager = self.pager.lock().expect("pager lock poisoned");
+        let header = pager.read_page(0)?;
+        if &header[..8] != MAGIC {
+            return Err(page_error(0, "bad magic, not a paged student file"));
+        }
+        let mut next_id = u32::from_le_bytes(header[8..12].try_into().expect("4 bytes"));
+        for page_no in 1..self.page_count {
+            let page = pager.read_page(page_no).map_err(|e| match e.kind() {
+                io::ErrorKind::InvalidData => page_error(page_no, "checksum mismatch"),
+                _ => e.into(),
+            })?;
+            for i in 0..SLOTS_PER_PAGE {
+                let slot = (page_no - 1) * SLOTS_PER_PAGE + i;
+                let offset = PAGE_HEADER_SIZE + i as usize * SLOT_SIZE;
+                match decode_slot(&page[offset..offset + SLOT_SIZE]) {
+                    Ok(Some(s)) => {
+                        if self.slots.insert(s.id, slot).is_some() {
+                            return Err(page_error(page_no, "duplicate id"));
+                        }
+                        // 文件头在数据页之后才写，崩溃时可能落后：以实际出现过的最大 id 为准。
+                        next_id = next_id.max(s.id.saturating_add(1));
+                    }
+                    Ok(None) => {
+                        self.free.insert(slot);
+                    }
+                    Err(reason) => return Err(page_error(page_no, reason)),
+                }
+            }
+        }
+        self.next_id = next_id;
+        Ok(())
+    }
+
+    fn pager(&self) -> MutexGuard<'_, Pager> {
+        self.pager.lock().expect("pager lock poisoned")
+    }
+
+    fn write_header(&self) -> io::Result<()> {
+        let mut page = [0; PAGE_SIZE];
+        page[..8].copy_from_slice(MAGIC);
+        page[8..12].copy_from_slice(&self.next_id.to_le_bytes());
+        self.pager().write_page(0, &page)
+    }
+
+    // 取一个空闲槽位；没有就在文件末尾追加一个空数据页。
+    fn allocate_slot(&mut self) -> io::Result<u64> {
+        if let Some(slot) = self.free.pop_first() {
+            return Ok(slot);
+        }
+        let page_no = self.page_count;
+        let mut page = [0; PAGE_SIZE];
+        seal_page(&mut page);
+        self.pager().write_data_page(page_no, &page)?;
+        self.page_count += 1;
+        let first = (page_no - 1) * SLOTS_PER_PAGE;
+        self.free.extend(first + 1..first + SLOTS_PER_PAGE);
+        Ok(first)
+    }
+
+    fn slot_of(&self, id: u32) -> Result<u64, StoreError> {
+        self.slots.get(&id).copied().ok_or(StoreError::NotFound(id))
+    }
+}
+
+impl StudentRepository for PagedStore {
+    fn add(&mut self, name: &str, age: u8, class_name: &str) -> Result<u32, StoreError> {
+        check_fields(name, class_name)?;
+        // 和内存后端一样，`u32::MAX` 留作哨兵不分配；先检查，别写出一页 id 重复的记录。
+        let next_id = self.next_id.checked_add(1).ok_or(StoreError::IdExhausted)?;
+        let student = Student {
+            id: self.next_id,
+            name: name.to_string(),
+            age,
+            class_name: class_name.to_string(),
+            attrs: Attrs::new(),
+        };
+        let slot = self.allocate_slot().map_err(StoreError::Storage)?;
+        let written = self.pager().write_slot(slot, Some(&student));
+        if let Err(e) = written {
+            self.free.insert(slot);
+            return Err(StoreError::Storage(e));
+        }
+        // 记录已经落到数据页上：即使下面写文件头失败，重新打开时也会看到它。
+        self.slots.insert(student.id, slot);
+        self.next_id = next_id;
+        self.write_header()
+            .and_then(|()| self.pager().file.sync_data())
+            .map_err(StoreError::Storage)?;
+        Ok(student.id)
+    }
+
+    fn get_by_id(&self, id: u32) -> Result<Option<Student>, StoreError> {
+        let Some(slot) = self.slots.get(&id) else {
+            return Ok(None);
+        };
+        self.pager().read_slot(*slot).map_err(StoreError::Storage)
+    }
+
+    fn list_by_id(&self) -> Result<Vec<Student>, StoreError> {
+        let mut pager = self.pager();
+        let mut out = Vec::with_capacity(self.slots.len());
+        for slot in self.slots.values() {
+            out.extend(pager.read_slot(*slot).map_err(StoreError::Storage)?);
+        }
+        Ok(out)
+    }
+
+    fn search_by_name_exact(&self, name: &str) -> Result<Vec<Student>, StoreError> {
+        let mut pager = self.pager();
+        let mut out = Vec::new();
+        for slot in 0..(self.page_count - 1) * SLOTS_PER_PAGE {
+            if let Some(s) = pager.read_slot(slot).map_err(StoreError::Storage)?
+                && s.name == name
+            {
+                out.push(s);
+            }
+        }
+        // 槽位顺序不是 id 顺序（空闲槽位会被复用），按约定排成 id 升序。
+        out.sort_by_key(|s| s.id);
+        Ok(out)
+    }
+
+    fn remove(&mut self, id: u32) -> Result<(), StoreError> {
+        let slot = self.slot_of(id)?;
+        let mut pager = self.pager();
+        pager
+            .write_slot(slot, None)
+            .and_then(|()| pager.file.sync_data())
+            .map_err(StoreError::Storage)?;
+        drop(pager);
+        self.slots.remove(&id);
+        self.free.insert(slot);
+        Ok(())
+    }
+
+    fn modify(&mut self, id: u32, name: &str, age: u8, class_name: &str) -> Result<(), StoreError> {
+        let slot = self.slot_of(id)?;
+        check_fields(name, class_name)?;
+        let student = Student {
+            id,
+            name: name.to_string(),
+            age,
+            class_name: class_name.to_string(),
+            attrs: Attrs::new(),
+        };
+        let mut pager = self.pager();
+        pager
+            .write_slot(slot, Some(&student))
+            .and_then(|()| pager.file.sync_data())
+            .map_err(StoreError::Storage)
+    }
+}
+
+fn check_fields(name: &str, class_name: &str) -> Result<(), StoreError> {
+    for (field, value) in [("name", name), ("class", class_name)] {
+        if value.len() > MAX_FIELD_BYTES {
+            return Err(StoreError::FieldTooLong {
+                field,
+                max: MAX_FIELD_BYTES,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn page_error(page: u64, reason: &str) -> PersistError {
+    PersistError::Page {
+        page,
+        reason: reason.to_string(),
+    }
+}
+
+// 槽位编号 -> (页号, 页内偏移)。页 0 是文件头，数据页从 1 开始。
+fn slot_position(slot: u64) -> (u64, usize) {
+    let page_no = 1 + slot / SLOTS_PER_PAGE;
+    let offset = PAGE_HEADER_SIZE + (slot % SLOTS_PER_PAGE) as usize * SLOT_SIZE;
+    (page_no, offset)
+}
+
+fn seal_page(page: &mut Page) {
+    let crc = crc32(&page[4..]);
+    page[..4].copy_from_slice(&crc.to_le_bytes());
+}
+
+fn page_is_sealed(page: &Page) -> bool {
+    page[..4] == crc32(&page[4..]).to_le_bytes()
+}
+
+// None 表示空闲槽位；整段清零，避免残留旧数据。
+fn encode_slot(buf: &mut [u8], student: Option<&Student>) {
+    buf.fill(0);
+    let Some(s) = student else {
+        return;
+    };
+    let name = s.name.as_bytes();
+    let class_name = s.class_name.as_bytes();
+    buf[0] = 1;
+    buf[1..5].copy_from_slice(&s.id.to_le_bytes());
+    buf[5] = s.age;
+    // 长度已由 check_fields 限制在 MAX_FIELD_BYTES 以内，放得进一个字节。
+    buf[6] = name.len() as u8;
+    buf[7] = class_name.len() as u8;
+    buf[8..8 + name.len()].copy_from_slice(name);
+    let class_start = 8 + MAX_FIELD_BYTES;
+    buf[class_start..class_start + class_name.len()].copy_from_slice(class_name);
+}
+
+fn decode_slot(buf: &[u8]) -> Result<Option<Student>, &'static str> {
+    match buf[0] {
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err("bad slot flag"),
+    }
+    let name_len = usize::from(buf[6]);
+    let class_len = usize::from(buf[7]);
+    if name_len > MAX_FIELD_BYTES || class_len > MAX_FIELD_BYTES {
+        return Err("bad field length");
+    }
+    let class_start = 8 + MAX_FIELD_BYTES;
+    let text =
+        |bytes: &[u8]| String::from_utf8(bytes.to_vec()).map_err(|_| "field is not valid utf-8");
+    Ok(Some(Student {
+        id: u32::from_le_bytes(buf[1..5].try_into().expect("4 bytes")),
+        age: buf[5],
+        name: text(&buf[8..8 + name_len])?,
+        class_name: text(&buf[class_start..class_start + class_len])?,
+        attrs: Attrs::new(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_FIELD_BYTES, PAGE_SIZE, PagedStore, SLOTS_PER_PAGE};
+    use crate::student::persist::tests::temp_data_path;
+    use crate::student::{PersistError, StoreError, StudentRepository};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_paged_store_reopens_across_many_pages() {
+        let path = temp_data_path("paged-reopen");
+        // 超过缓存页数，保证有页被淘汰后再从磁盘读回来。
+        let n = SLOTS_PER_PAGE as u32 * 20;
+        {
+            let mut store = PagedStore::open(&path).unwrap();
+            for i in 0..n {
+                store.add(&format!("s{i}"), (i % 100) as u8, "x").unwrap();
+            }
+            store.remove(5).unwrap();
+            store.modify(7, "seven", 7, "y").unwrap();
+        }
+        assert_eq!(fs::metadata(&path).unwrap().len() % PAGE_SIZE as u64, 0);
+
+        let mut store = PagedStore::open(&path).unwrap();
+        assert_eq!(store.list_by_id().unwrap().len(), n as usize - 1);
+        assert!(store.get_by_id(5).unwrap().is_none());
+        assert_eq!(store.get_by_id(7).unwrap().unwrap().name, "seven");
+        assert_eq!(
+            store.get_by_id(n).unwrap().unwrap().name,
+            format!("s{}", n - 1)
+        );
+        // 新记录复用 id=5 空出的槽位，但 id 继续递增。
+        assert_eq!(store.add("new", 1, "z").unwrap(), n + 1);
+        assert_eq!(store.slots[&(n + 1)], 4);
+    }
+
+    #[test]
+    fn test_paged_store_rejects_corrupt_page() {
+        let path = temp_data_path("paged-corrupt");
+        {
+            let mut store = PagedStore::open(&path).unwrap();
+            store.add("alice", 12, "A3").unwrap();
+        }
+        // 双写日志里正好是第 1 页的完整副本，会把下面的损坏修好；删掉它，看的是日志之外的损坏。
+        fs::remove_file(journal_path(&path)).unwrap();
+        let mut raw = fs::read(&path).unwrap();
+        raw[PAGE_SIZE + 200] ^= 0x01;
+        fs::write(&path, &raw).unwrap();
+        assert!(matches!(
+            PagedStore::open(&path),
+            Err(PersistError::Page { page: 1, .. })
+        ));
+
+        fs::write(&path, &raw[..PAGE_SIZE + 10]).unwrap();
+        fs::remove_file(journal_path(&path)).unwrap();
+        assert!(matches!(
+            PagedStore::open(&path),
+            Err(PersistError::Page { .. })
+        ));
+    }
+
+    fn journal_path(path: &Path) -> PathBuf {
+        let mut journal = path.as_os_str().to_owned();
+        journal.push(".journal");
+        PathBuf::from(journal)
+    }
+
+    #[test]
+    fn test_paged_store_recovers_torn_page_from_journal() {
+        let path = temp_data_path("paged-torn");
+        {
+            let mut store = PagedStore::open(&path).unwrap();
+            store.add("alice", 12, "A3").unwrap();
+            store.add("bob", 9, "A1").unwrap();
+        }
+        // 模拟原地写第 1 页写到一半断电：后半页还是旧内容（这里直接清零），crc 对不上。
+        let mut raw = fs::read(&path).unwrap();
+        raw[PAGE_SIZE + PAGE_SIZE / 2..2 * PAGE_SIZE].fill(0);
+        raw[PAGE_SIZE + 200] ^= 0x01;
+        fs::write(&path, &raw).unwrap();
+
+        let mut store = PagedStore::open(&path).unwrap();
+        let names = |store: &PagedStore| {
+            let rows = store.list_by_id().unwrap();
+            rows.into_iter().map(|s| s.name).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&store), ["alice", "bob"]);
+
+        // 追加新页时撕裂（文件末尾只有半页）同样能重做。
+        for i in 0..SLOTS_PER_PAGE {
+            store.add(&format!("s{i}"), 1, "x").unwrap();
+        }
+        drop(store);
+        let raw = fs::read(&path).unwrap();
+        fs::write(&path, &raw[..2 * PAGE_SIZE + 100]).unwrap();
+        let store = PagedStore::open(&path).unwrap();
+        assert_eq!(names(&store).len(), SLOTS_PER_PAGE as usize + 2);
+
+        // 日志本身没写完（crc 不符）时原页没动过，忽略日志照常打开。
+        let mut journal = fs::read(journal_path(&path)).unwrap();
+        journal[20] ^= 0x01;
+        fs::write(journal_path(&path), &journal).unwrap();
+        let store = PagedStore::open(&path).unwrap();
+        assert_eq!(names(&store).len(), SLOTS_PER_PAGE as usize + 2);
+    }
+
+    #[test]
+    fn test_paged_store_rejects_add_once_ids_run_out() {
+        let path = temp_data_path("paged-exhausted");
+        {
+            let mut store = PagedStore::open(&path).unwrap();
+            store.next_id = u32::MAX - 1;
+            assert_eq!(store.add("last", 1, "x").unwrap(), u32::MAX - 1);
+            assert!(matches!(
+                store.add("one more", 1, "x"),
+                Err(StoreError::IdExhausted)
+            ));
+        }
+        let mut store = PagedStore::open(&path).unwrap();
+        assert_eq!(store.list_by_id().unwrap()[0].id, u32::MAX - 1);
+        assert!(matches!(
+            store.add("one more", 1, "x"),
+            Err(StoreError::IdExhausted)
+        ));
+    }
+
+    #[test]
+    fn test_paged_store_limits_field_length() {
+        let path = temp_data_path("paged-long");
+        let mut store = PagedStore::open(&path).unwrap();
+        let long = "长".repeat(MAX_FIELD_BYTES / 3 + 1);
+        assert!(matches!(
+            store.add(&long, 1, "x"),
+            Err(StoreError::FieldTooLong { field: "name", .. })
+        ));
+        let id = store.add("ok", 1, "x").unwrap();
+        assert!(matches!(
+            store.modify(id, "ok", 1, &long),
+            Err(StoreError::FieldTooLong { field: "class", .. })
+        ));
+        assert_eq!(id, 1);
+        assert_eq!(store.list_by_id().unwrap().len(), 1);
+    }
+}
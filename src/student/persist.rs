@@ -0,0 +1,1066 @@
+//! 持久化：快照文件（save/load）与预写日志（WAL）的编码、回放和 checkpoint。
+//!
+//! 数据文件格式（文本，一行一条记录，字段用 `\t` 分隔）：
+//!
+//! ```text
+//! sms-store<TAB>5
+//! next_id<TAB><n>
+//! lsn<TAB><n>
+//! class<TAB><name>   （0..N 行，按名字排序）
+//! student<TAB><id><TAB><name><TAB><age><TAB><class>[<TAB><attr>...]   （0..N 行，按 id 升序）
+//! grade<TAB><id><TAB><course><TAB><score>   （0..N 行，按课程、id 排序）
+//! end<TAB><count>
+//! ```
+//!
+//! - name/class 中的 `\`、TAB、换行、回车分别转义为 `\\`、`\t`、`\n`、`\r`。
+//! - 每行都必须以换行结尾；缺少 `end` 行或 count 对不上，视为文件被截断。
+//! - 版本 2 在 `next_id` 之后多一行 `lsn<TAB><n>`，记录快照包含到哪条日志；
+//!   版本 1 文件仍可读取（lsn 视为 0）。
+//! - 版本 3 的学生行后面可以跟若干自定义属性，每个一列：`<key>=<类型>:<值>`，
+//!   类型是 `i`（整数）、`f`（小数）、`b`（布尔）、`s`（文本，转义规则同 name）。
+//!   日志里的 insert / update 记录用同样的编码。版本 1、2 的文件没有属性，照常读取。
+//! - 版本 4 在学生行之后加 `grade` 行；学生必须出现在前面，分数 `0..=100`。
+//!   `end` 的 count 仍然只数学生。分档（`GradeBands`）是运行时配置，不进文件。
+//! - 版本 5 在学生之前加 `class` 行（班级表），学生的班级必须在表里。
+//!   更早的版本没有班级表，读取时由学生行里出现过的班级补出来。
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::store::{Op, StudentStore};
+use super::{AttrValue, Attrs, MAX_SCORE, Student, validate_attr_key};
+
+const SNAPSHOT_HEADER: &str = "sms-store\t5";
+const SNAPSHOT_HEADER_V4: &str = "sms-store\t4";
+const SNAPSHOT_HEADER_V3: &str = "sms-store\t3";
+const SNAPSHOT_HEADER_V2: &str = "sms-store\t2";
+const SNAPSHOT_HEADER_V1: &str = "sms-store\t1";
+
+impl StudentStore {
+    /// 把全部学生写成一份快照（格式见本模块开头）。
+    pub fn write_snapshot<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "{SNAPSHOT_HEADER}")?;
+        writeln!(w, "next_id\t{}", self.next_id)?;
+        writeln!(w, "lsn\t{}", self.lsn)?;
+        for name in &self.classes {
+            writeln!(w, "class\t{}", escape_field(name))?;
+        }
+        for s in self.list_by_id() {
+            writeln!(w, "student\t{}", encode_student(s))?;
+        }
+        for (course, scores) in &self.grades {
+            for (id, score) in scores {
+                writeln!(w, "grade\t{id}\t{}\t{score}", escape_field(course))?;
+            }
+        }
+        writeln!(w, "end\t{}", self.len())?;
+        w.flush()
+    }
+
+    /// 读一份快照，得到纯内存的新 store。
+    ///
+    /// 只在整份文件都校验通过后才返回；任何一行出错都直接返回 [`PersistError::Corrupt`]，
+    /// 调用方手里的旧 store 保持不变，不会出现“加载了一半”的状态。
+    pub fn read_snapshot<R: BufRead>(mut r: R) -> Result<Self, PersistError> {
+        let mut store = StudentStore::new();
+        let mut buf = String::new();
+        let mut line_no = 0;
+        let mut version = 0;
+        let mut next_id = None;
+        let mut ended = false;
+
+        loop {
+            buf.clear();
+            if r.read_line(&mut buf)? == 0 {
+                break;
+            }
+            line_no += 1;
+            let line = buf
+                .strip_suffix('\n')
+                .ok_or_else(|| corrupt(line_no, "truncated line (missing newline)"))?;
+            if ended {
+                return Err(corrupt(line_no, "unexpected data after end marker"));
+            }
+            if line_no == 1 {
+                version = match line {
+                    SNAPSHOT_HEADER => 5,
+                    SNAPSHOT_HEADER_V4 => 4,
+                    SNAPSHOT_HEADER_V3 => 3,
+                    SNAPSHOT_HEADER_V2 => 2,
+                    SNAPSHOT_HEADER_V1 => 1,
+                    _ => return Err(corrupt(line_no, "bad header, not a student data file")),
+                };
+                continue;
+            }
+
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            match (line_no, fields.as_slice()) {
+                (2, ["next_id", n]) => {
+                    let n = n
+                        .parse::<u32>()
+                        .map_err(|_| corrupt(line_no, "invalid next_id"))?;
+                    store.next_id = n;
+                    next_id = Some(n);
+                }
+                (2, _) => return Err(corrupt(line_no, "expected `next_id` line")),
+                (3, ["lsn", n]) if version >= 2 => {
+                    store.lsn = n
+                        .parse::<u64>()
+                        .map_err(|_| corrupt(line_no, "invalid lsn"))?;
+                }
+                (3, _) if version >= 2 => return Err(corrupt(line_no, "expected `lsn` line")),
+                (_, ["class", name]) if version >= 5 => {
+                    let name =
+                        decode_class(name).ok_or_else(|| corrupt(line_no, "invalid class name"))?;
+                    if !store.is_empty() {
+                        return Err(corrupt(line_no, "class after student records"));
+                    }
+                    if !store.classes.insert(name) {
+                        return Err(corrupt(line_no, "duplicate class"));
+                    }
+                }
+                (_, ["student", rest @ ..]) => {
+                    let student = decode_student(rest).map_err(|e| corrupt(line_no, e))?;
+                    if next_id.is_some_and(|n| student.id >= n) {
+                        return Err(corrupt(line_no, "id is not below next_id"));
+                    }
+                    // 旧版本没有班级表：学生行里出现过的班级就是全部班级。
+                    if version < 5 {
+                        store.classes.insert(student.class_name.clone());
+                    } else if !store.classes.contains(&student.class_name) {
+                        return Err(corrupt(line_no, "unknown class"));
+                    }
+                    if !store.insert_record(student) {
+                        return Err(corrupt(line_no, "duplicate id"));
+                    }
+                }
+                (_, ["grade", id, course, score]) if version >= 4 => {
+                    let (id, course, score) =
+                        decode_grade(id, course, score).map_err(|e| corrupt(line_no, e))?;
+                    if store
+                        .grades
+                        .get(&course)
+                        .is_some_and(|g| g.contains_key(&id))
+                    {
+                        return Err(corrupt(line_no, "duplicate grade"));
+                    }
+                    if store.apply_grade(id, &course, Some(score)).is_none() {
+                        return Err(corrupt(line_no, "grade for unknown id"));
+                    }
+                }
+                (_, ["end", count]) => {
+                    let count = count
+                        .parse::<usize>()
+                        .map_err(|_| corrupt(line_no, "invalid end count"))?;
+                    if count != store.len() {
+                        return Err(corrupt(line_no, "record count mismatch"));
+                    }
+                    ended = true;
+                }
+                _ => return Err(corrupt(line_no, "unrecognized record")),
+            }
+        }
+
+        if line_no == 0 {
+            return Err(corrupt(0, "empty file"));
+        }
+        if !ended {
+            return Err(corrupt(line_no, "truncated file (missing end marker)"));
+        }
+        Ok(store)
+    }
+
+    /// 把全部学生原子地写入 `path`：先写 `<path>.tmp` 并 fsync，再 rename 覆盖，
+    /// 中途崩溃时旧文件仍然完整。事务内返回 [`PersistError::InTransaction`]。
+    pub fn save(&self, path: &Path) -> Result<(), PersistError> {
+        if self.txn.is_some() {
+            return Err(PersistError::InTransaction("save"));
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let file = File::create(&tmp)?;
+        self.write_snapshot(BufWriter::new(&file))?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// 从数据文件读一份快照，见 [`read_snapshot`](Self::read_snapshot)。
+    pub fn load(path: &Path) -> Result<Self, PersistError> {
+        let file = File::open(path)?;
+        Self::read_snapshot(BufReader::new(file))
+    }
+
+    /// 打开数据文件：加载快照（没有就从空开始）→ 回放 `<path>.wal`
+    /// → 之后的每次修改都先追加到这个日志并 fsync。
+    ///
+    /// 快照或日志损坏时返回错误，不会用半份数据继续运行。
+    pub fn open(data_path: &Path) -> Result<Self, PersistError> {
+        let mut store = if data_path.exists() {
+            Self::load(data_path)?
+        } else {
+            Self::new()
+        };
+        let (wal, ops) = Wal::open(data_path)?;
+        for (lsn, op) in ops {
+            // 快照已经包含的记录直接跳过：checkpoint 可能在“快照写完、日志还没截断”时崩溃。
+            if lsn <= store.lsn {
+                continue;
+            }
+            // 班级表出现之前写的日志里没有 class 记录：新增 / 修改引用的班级按需补上。
+            // 新日志不会走到这里，因为 add / modify 只接受已经存在的班级。
+            if let Op::Insert(s) | Op::Update(s) = &op
+                && !store.classes.contains(&s.class_name)
+            {
+                store.classes.insert(s.class_name.clone());
+            }
+            if lsn != store.lsn + 1 || store.apply(op).is_none() {
+                return Err(PersistError::Log {
+                    lsn,
+                    reason: "record does not apply to snapshot".to_string(),
+                });
+            }
+            store.lsn = lsn;
+        }
+        store.wal = Some(wal);
+        Ok(store)
+    }
+
+    /// 写一份新快照（带当前 lsn），然后清空日志，避免日志无限增长。
+    ///
+    /// 没有打开数据文件时返回 [`PersistError::NoDataPath`]。
+    pub fn checkpoint(&mut self) -> Result<(), PersistError> {
+        if self.txn.is_some() {
+            return Err(PersistError::InTransaction("checkpoint"));
+        }
+        let Some(wal) = &self.wal else {
+            return Err(PersistError::NoDataPath);
+        };
+        self.save(&wal.snapshot_path)?;
+        if let Some(wal) = &mut self.wal {
+            wal.truncate()?;
+        }
+        Ok(())
+    }
+
+    /// 整体换成另一份数据（通常来自 [`load`](Self::load)），但保留当前日志与 lsn 序列。
+    ///
+    /// 有日志时先把新数据写成快照，成功之后才换进内存：写快照失败时内存和磁盘都还是旧数据，
+    /// 不会出现内存已经换了、磁盘（快照 + 日志）回放出来却是旧数据的情况。
+    /// undo/redo 历史针对的是旧数据，直接丢弃。
+    pub fn replace_data(&mut self, mut loaded: StudentStore) -> Result<(), PersistError> {
+        if self.txn.is_some() {
+            return Err(PersistError::InTransaction("load"));
+        }
+        loaded.lsn = self.lsn;
+        loaded.history_limit = self.history_limit;
+        loaded.grade_bands = self.grade_bands.clone();
+        if let Some(wal) = &self.wal {
+            loaded.save(&wal.snapshot_path)?;
+        }
+        loaded.wal = self.wal.take();
+        *self = loaded;
+        // 快照已经换过去了：截断失败时旧日志留着也无妨，它的 lsn 都不大于快照的，回放时跳过。
+        if let Some(wal) = &mut self.wal {
+            wal.truncate()?;
+        }
+        Ok(())
+    }
+}
+
+// 日志文件格式（`<data>.wal`，一行一条记录，以换行结尾）：
+//
+//   <crc32 hex8><TAB><lsn><TAB><kind><TAB><fields...>
+//
+// - kind：`insert <id> <name> <age> <class> [<attr>...]` / `remove <id>` /
+//   `update <id> <name> <age> <class> [<attr>...]`，属性编码同快照；
+//   `grade <id> <course> <score>` / `ungrade <id> <course>`；
+//   `class_add <name>` / `class_remove <name>` / `class_rename <from> <to>`。
+// - crc32 覆盖 crc 之后的全部内容，用来识别写了一半的记录。
+// - 事务提交时整批写成 `begin`、若干操作、`commit`；没有 commit 的尾批次视为没提交。
+// - 只有最后一条记录允许损坏（崩溃时正在写），回放时忽略并把文件截回去；
+//   中间记录损坏说明日志本身坏了，直接报错。
+#[derive(Debug)]
+pub(super) struct Wal {
+    snapshot_path: PathBuf,
+    file: File,
+}
+
+impl Wal {
+    fn open(snapshot_path: &Path) -> Result<(Self, Vec<(u64, Op)>), PersistError> {
+        let mut log_path = snapshot_path.as_os_str().to_owned();
+        log_path.push(".wal");
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(PathBuf::from(log_path))?;
+
+        let mut raw = Vec::new();
+        io::Read::read_to_end(&mut file, &mut raw)?;
+        let (ops, valid_len) = decode_log(&raw)?;
+        if valid_len < raw.len() {
+            // 丢掉残缺的尾巴，后续追加才不会接在半条记录后面。
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+
+        let wal = Wal {
+            snapshot_path: snapshot_path.to_path_buf(),
+            file,
+        };
+        Ok((wal, ops))
+    }
+
+    // 一批操作一次 write + 一次 fsync；多于一条时用 begin/commit 包起来，
+    // 回放时只有看到 commit 的批次才生效。
+    pub(super) fn append(&mut self, first_lsn: u64, ops: &[Op]) -> io::Result<()> {
+        let last_lsn = first_lsn + ops.len() as u64 - 1;
+        let mut buf = String::new();
+        if ops.len() > 1 {
+            buf.push_str(&encode_log_marker(first_lsn, "begin"));
+        }
+        for (lsn, op) in (first_lsn..).zip(ops) {
+            buf.push_str(&encode_log_record(lsn, op));
+        }
+        if ops.len() > 1 {
+            buf.push_str(&encode_log_marker(last_lsn, "commit"));
+        }
+        self.file.write_all(buf.as_bytes())?;
+        self.file.sync_data()
+    }
+
+    fn truncate(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_all()
+    }
+}
+
+fn encode_log_record(lsn: u64, op: &Op) -> String {
+    let body = match op {
+        Op::Insert(s) => format!("{lsn}\tinsert\t{}", encode_student(s)),
+        Op::Remove(id) => format!("{lsn}\tremove\t{id}"),
+        Op::Update(s) => format!("{lsn}\tupdate\t{}", encode_student(s)),
+        Op::AddClass(name) => format!("{lsn}\tclass_add\t{}", escape_field(name)),
+        Op::RemoveClass(name) => format!("{lsn}\tclass_remove\t{}", escape_field(name)),
+        Op::RenameClass { from, to } => format!(
+            "{lsn}\tclass_rename\t{}\t{}",
+            escape_field(from),
+            escape_field(to)
+        ),
+        Op::Grade {
+            id,
+            course,
+            score: Some(score),
+        } => format!("{lsn}\tgrade\t{id}\t{}\t{score}", escape_field(course)),
+        Op::Grade {
+            id,
+            course,
+            score: None,
+        } => format!("{lsn}\tungrade\t{id}\t{}", escape_field(course)),
+    };
+    format!("{:08x}\t{body}\n", crc32(body.as_bytes()))
+}
+
+// 事务批次标记：begin 带批次第一条的 lsn，commit 带最后一条的 lsn。
+fn encode_log_marker(lsn: u64, kind: &str) -> String {
+    let body = format!("{lsn}\t{kind}");
+    format!("{:08x}\t{body}\n", crc32(body.as_bytes()))
+}
+
+enum LogLine {
+    Op(Op),
+    Begin,
+    Commit,
+}
+
+// 返回解析出的记录，以及“有效前缀”的字节长度（不含被忽略的残缺尾记录）。
+// 没等到 commit 的最后一个批次整体丢弃，有效前缀也截到它的 begin 之前。
+fn decode_log(raw: &[u8]) -> Result<(Vec<(u64, Op)>, usize), PersistError> {
+    let mut ops = Vec::new();
+    // 正在读的事务批次：(begin 所在偏移, 批次内已读到的操作)。
+    let mut batch: Option<(usize, Vec<(u64, Op)>)> = None;
+    let mut offset = 0;
+    let mut last_lsn = 0;
+    let log_error = |lsn: u64, reason: &str| PersistError::Log {
+        lsn,
+        reason: reason.to_string(),
+    };
+
+    while offset < raw.len() {
+        let Some(len) = raw[offset..].iter().position(|b| *b == b'\n') else {
+            // 没有换行：最后一条只写了一半。
+            break;
+        };
+        let end = offset + len + 1;
+        let is_last = end == raw.len();
+        let (lsn, line) = match decode_log_line(&raw[offset..end - 1]) {
+            Some(v) => v,
+            None if is_last => break,
+            None => {
+                return Err(log_error(
+                    last_lsn + 1,
+                    "checksum mismatch or malformed record",
+                ));
+            }
+        };
+        last_lsn = lsn;
+        match (line, &mut batch) {
+            (LogLine::Begin, None) => batch = Some((offset, Vec::new())),
+            (LogLine::Begin, Some(_)) => return Err(log_error(lsn, "nested begin")),
+            (LogLine::Commit, Some(_)) => {
+                let (_, batch_ops) = batch.take().expect("matched Some above");
+                ops.extend(batch_ops);
+            }
+            (LogLine::Commit, None) => return Err(log_error(lsn, "commit without begin")),
+            (LogLine::Op(op), Some((_, batch_ops))) => batch_ops.push((lsn, op)),
+            (LogLine::Op(op), None) => ops.push((lsn, op)),
+        }
+        offset = end;
+    }
+
+    let valid_len = match batch {
+        Some((begin_offset, _)) => begin_offset,
+        None => offset,
+    };
+    Ok((ops, valid_len))
+}
+
+fn decode_log_line(line: &[u8]) -> Option<(u64, LogLine)> {
+    let line = std::str::from_utf8(line).ok()?;
+    let (crc, body) = line.split_once('\t')?;
+    if u32::from_str_radix(crc, 16).ok()? != crc32(body.as_bytes()) {
+        return None;
+    }
+    let fields = body.split('\t').collect::<Vec<&str>>();
+    let lsn = fields.first()?.parse::<u64>().ok()?;
+    let line = match &fields[1..] {
+        ["insert", rest @ ..] => LogLine::Op(Op::Insert(decode_student(rest).ok()?)),
+        ["remove", id] => LogLine::Op(Op::Remove(id.parse().ok()?)),
+        ["update", rest @ ..] => LogLine::Op(Op::Update(decode_student(rest).ok()?)),
+        ["grade", id, course, score] => {
+            let (id, course, score) = decode_grade(id, course, score).ok()?;
+            LogLine::Op(Op::Grade {
+                id,
+                course,
+                score: Some(score),
+            })
+        }
+        ["class_add", name] => LogLine::Op(Op::AddClass(decode_class(name)?)),
+        ["class_remove", name] => LogLine::Op(Op::RemoveClass(decode_class(name)?)),
+        ["class_rename", from, to] => LogLine::Op(Op::RenameClass {
+            from: decode_class(from)?,
+            to: decode_class(to)?,
+        }),
+        ["ungrade", id, course] => LogLine::Op(Op::Grade {
+            id: id.parse().ok()?,
+            course: unescape_field(course).filter(|c| !c.is_empty())?,
+            score: None,
+        }),
+        ["begin"] => LogLine::Begin,
+        ["commit"] => LogLine::Commit,
+        _ => return None,
+    };
+    Some((lsn, line))
+}
+
+// CRC-32（IEEE 802.3 多项式，按位计算）：数据量小，不值得为它引入依赖或查表。
+pub(super) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for b in bytes {
+        crc ^= u32::from(*b);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// 快照 / 日志读写的错误。
+#[derive(Debug)]
+pub enum PersistError {
+    /// 底层文件读写失败。
+    Io(io::Error),
+    /// 快照文件损坏；`line` 从 1 开始，0 表示空文件。
+    Corrupt {
+        /// 出错的行号。
+        line: usize,
+        /// 出错原因。
+        reason: String,
+    },
+    /// 日志损坏，或日志与快照对不上。
+    Log {
+        /// 出错记录的序号。
+        lsn: u64,
+        /// 出错原因。
+        reason: String,
+    },
+    /// 分页数据文件损坏；`page` 是出错的页号（0 是文件头）。
+    Page {
+        /// 出错的页号。
+        page: u64,
+        /// 出错原因。
+        reason: String,
+    },
+    /// 需要数据文件的操作（如 checkpoint），但 store 是纯内存的。
+    NoDataPath,
+    /// 会越过事务直接读写磁盘的操作，参数是操作名。
+    InTransaction(&'static str),
+}
+
+impl From<io::Error> for PersistError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Io(e) => write!(f, "io error: {e}"),
+            PersistError::Corrupt { line, reason } => {
+                write!(f, "corrupt data file at line {line}: {reason}")
+            }
+            PersistError::Log { lsn, reason } => {
+                write!(f, "corrupt log at record lsn={lsn}: {reason}")
+            }
+            PersistError::Page { page, reason } => {
+                write!(f, "corrupt page {page}: {reason}")
+            }
+            PersistError::NoDataPath => write!(f, "no data file, start with `--data <path>`"),
+            PersistError::InTransaction(op) => {
+                write!(f, "`{op}` is not allowed inside a transaction")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PersistError {}
+
+fn corrupt(line: usize, reason: &str) -> PersistError {
+    PersistError::Corrupt {
+        line,
+        reason: reason.to_string(),
+    }
+}
+
+// 快照与日志共用的学生字段编码：`<id>\t<name>\t<age>\t<class>`，后面每个属性一列。
+fn encode_student(s: &Student) -> String {
+    let mut out = format!(
+        "{}\t{}\t{}\t{}",
+        s.id,
+        escape_field(&s.name),
+        s.age,
+        escape_field(&s.class_name)
+    );
+    for (key, value) in &s.attrs {
+        let encoded = match value {
+            AttrValue::Int(n) => format!("i:{n}"),
+            AttrValue::Float(x) => format!("f:{x:?}"),
+            AttrValue::Bool(b) => format!("b:{b}"),
+            AttrValue::Text(text) => format!("s:{}", escape_field(text)),
+        };
+        out.push_str(&format!("\t{key}={encoded}"));
+    }
+    out
+}
+
+fn decode_student(fields: &[&str]) -> Result<Student, &'static str> {
+    let [id, name, age, class_name, attrs @ ..] = fields else {
+        return Err("wrong number of student fields");
+    };
+    Ok(Student {
+        id: id.parse::<u32>().map_err(|_| "invalid id")?,
+        name: unescape_field(name).ok_or("bad escape in name")?,
+        age: age.parse::<u8>().map_err(|_| "invalid age")?,
+        class_name: unescape_field(class_name).ok_or("bad escape in class")?,
+        attrs: decode_attrs(attrs)?,
+    })
+}
+
+fn decode_class(raw: &str) -> Option<String> {
+    unescape_field(raw).filter(|name| !name.is_empty())
+}
+
+// 快照与日志共用的成绩字段：`<id>\t<course>\t<score>`。
+fn decode_grade(id: &str, course: &str, score: &str) -> Result<(u32, String, u8), &'static str> {
+    let id = id.parse::<u32>().map_err(|_| "invalid id")?;
+    let course = unescape_field(course)
+        .filter(|c| !c.is_empty())
+        .ok_or("invalid course")?;
+    let score = score
+        .parse::<u8>()
+        .ok()
+        .filter(|s| *s <= MAX_SCORE)
+        .ok_or("invalid score")?;
+    Ok((id, course, score))
+}
+
+fn decode_attrs(fields: &[&str]) -> Result<Attrs, &'static str> {
+    let mut attrs = Attrs::new();
+    for field in fields {
+        let (key, encoded) = field.split_once('=').ok_or("malformed attribute")?;
+        validate_attr_key(key).map_err(|_| "invalid attribute key")?;
+        let value = match encoded.split_once(':') {
+            Some(("i", n)) => AttrValue::Int(n.parse().map_err(|_| "invalid int attribute")?),
+            Some(("f", x)) => {
+                let x = x.parse::<f64>().map_err(|_| "invalid float attribute")?;
+                if !x.is_finite() {
+                    return Err("invalid float attribute");
+                }
+                AttrValue::Float(x)
+            }
+            Some(("b", b)) => AttrValue::Bool(b.parse().map_err(|_| "invalid bool attribute")?),
+            Some(("s", text)) => {
+                AttrValue::Text(unescape_field(text).ok_or("bad escape in attribute")?)
+            }
+            _ => return Err("unknown attribute type"),
+        };
+        if attrs.insert(key.to_string(), value).is_some() {
+            return Err("duplicate attribute");
+        }
+    }
+    Ok(attrs)
+}
+
+fn escape_field(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// 未知转义或结尾孤立的 `\` 返回 None，由调用方报告为文件损坏。
+fn unescape_field(raw: &str) -> Option<String> {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => out.push('\\'),
+            't' => out.push('\t'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::encode_log_record;
+    use crate::student::store::tests::sample_store;
+    use crate::student::{AttrValue, Op, StoreError, StudentStore};
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::ops::Deref;
+    use std::path::{Path, PathBuf};
+
+    fn snapshot(store: &StudentStore) -> Vec<u8> {
+        let mut buf = Vec::new();
+        store.write_snapshot(&mut buf).unwrap();
+        buf
+    }
+
+    // 测试用的数据文件路径，当 `&Path` 用；drop 时（包括断言失败 panic）删掉整个目录，
+    // 快照、日志、`.tmp`、`.journal` 一个不留。
+    pub(crate) struct TempDataPath {
+        dir: PathBuf,
+        path: PathBuf,
+    }
+
+    impl Deref for TempDataPath {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl AsRef<Path> for TempDataPath {
+        fn as_ref(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl Drop for TempDataPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    // 每个测试一个独立目录，避免并行测试互相踩文件。
+    pub(crate) fn temp_data_path(name: &str) -> TempDataPath {
+        let dir = std::env::temp_dir().join(format!("sms-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("students.sms");
+        TempDataPath { dir, path }
+    }
+
+    // 打开并建好 class1..class3 再 checkpoint：班级进快照，日志从空开始，lsn 从 3 起算。
+    fn open_with_classes(path: &Path) -> StudentStore {
+        let mut store = StudentStore::open(path).unwrap();
+        for name in ["class1", "class2", "class3"] {
+            store.add_class(name).unwrap();
+        }
+        store.checkpoint().unwrap();
+        store
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let store = sample_store();
+        let loaded = StudentStore::read_snapshot(snapshot(&store).as_slice()).unwrap();
+        assert_eq!(loaded.next_id, 4);
+        assert_eq!(loaded.ids, store.ids);
+        assert_eq!(loaded.indexes, store.indexes);
+        assert_eq!(loaded.get_by_id(3).unwrap().name, "tab\tand\\slash");
+    }
+
+    #[test]
+    fn test_attrs_survive_snapshot_and_wal_replay() {
+        let path = temp_data_path("attrs");
+        {
+            let mut store = open_with_classes(&path);
+            store.add("alice", 18, "class1").unwrap();
+            store
+                .set_attr(1, "note", AttrValue::infer("tab\there \\ ok"))
+                .unwrap();
+            store.set_attr(1, "gpa", AttrValue::infer("1.0")).unwrap();
+            store.checkpoint().unwrap();
+            store
+                .set_attr(1, "phone", AttrValue::infer("0123"))
+                .unwrap();
+            store
+                .set_attr(1, "active", AttrValue::infer("false"))
+                .unwrap();
+            store.set_attr(1, "rank", AttrValue::infer("-7")).unwrap();
+        }
+        let store = StudentStore::open(&path).unwrap();
+        let attrs = &store.get_by_id(1).unwrap().attrs;
+        let shown = attrs
+            .iter()
+            .map(|(k, v)| format!("{k}={v}:{}", v.type_name()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shown,
+            [
+                "active=false:bool",
+                "gpa=1.0:float",
+                "note=tab\there \\ ok:text",
+                "phone=0123:text",
+                "rank=-7:int",
+            ]
+        );
+
+        // 旧版本的学生行没有属性；新版本里坏掉的属性列算文件损坏。
+        let v2 = "sms-store\t2\nnext_id\t2\nlsn\t0\nstudent\t1\ta\t1\tc\nend\t1\n";
+        assert!(StudentStore::read_snapshot(v2.as_bytes()).is_ok());
+        for bad in [
+            "x",
+            "Key=s:v",
+            "k=q:v",
+            "k=i:1.5",
+            "k=f:inf",
+            "k=s:a\\",
+            "k=i:1\tk=i:2",
+        ] {
+            let raw =
+                format!("sms-store\t3\nnext_id\t2\nlsn\t0\nstudent\t1\ta\t1\tc\t{bad}\nend\t1\n");
+            assert!(
+                StudentStore::read_snapshot(raw.as_bytes()).is_err(),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_grades_survive_snapshot_and_wal_replay() {
+        let path = temp_data_path("grades");
+        {
+            let mut store = open_with_classes(&path);
+            store.add("alice", 18, "class1").unwrap();
+            store.add("bob", 19, "class2").unwrap();
+            store.set_grade(1, "math\tA", 90).unwrap();
+            store.set_grade(2, "math\tA", 70).unwrap();
+            store.checkpoint().unwrap();
+            store.set_grade(2, "art", 55).unwrap();
+            store.remove(1).unwrap();
+        }
+        let mut store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.grades_of(2), [("art", 55), ("math\tA", 70)]);
+        assert!(store.grades_of(1).is_empty());
+        // 级联删除整组写进了日志，回放后 id=1 的成绩也不在；checkpoint 出的快照能读回同样的成绩。
+        store.checkpoint().unwrap();
+        let reloaded = StudentStore::load(&path).unwrap();
+        assert_eq!(reloaded.courses(), ["art", "math\tA"]);
+
+        let base = "sms-store\t4\nnext_id\t2\nlsn\t0\nstudent\t1\ta\t1\tc\n";
+        let ok = format!("{base}grade\t1\tmath\t100\nend\t1\n");
+        assert!(StudentStore::read_snapshot(ok.as_bytes()).is_ok());
+        for bad in [
+            "grade\t2\tmath\t1",
+            "grade\t1\tmath\t101",
+            "grade\t1\t\t1",
+            "grade\t1\tmath\t1\ngrade\t1\tmath\t2",
+        ] {
+            let raw = format!("{base}{bad}\nend\t1\n");
+            assert!(
+                StudentStore::read_snapshot(raw.as_bytes()).is_err(),
+                "{bad}"
+            );
+        }
+        // 版本 3 文件里不能出现 grade 行。
+        let v3 = format!("sms-store\t3{}grade\t1\tmath\t1\nend\t1\n", &base[11..]);
+        assert!(StudentStore::read_snapshot(v3.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_classes_survive_snapshot_and_wal_replay() {
+        let path = temp_data_path("classes");
+        {
+            let mut store = open_with_classes(&path);
+            store.add("alice", 18, "class1").unwrap();
+            store.add_class("empty\tone").unwrap();
+            store.rename_class("class1", "A1").unwrap();
+            store.remove_class("class2", false).unwrap();
+        }
+        let mut store = StudentStore::open(&path).unwrap();
+        assert_eq!(
+            store.classes(),
+            [("A1", 1), ("class3", 0), ("empty\tone", 0)]
+        );
+        assert_eq!(store.get_by_id(1).unwrap().class_name, "A1");
+        store.checkpoint().unwrap();
+        assert_eq!(
+            StudentStore::load(&path).unwrap().classes(),
+            store.classes()
+        );
+
+        // 版本 5 的学生必须引用已登记的班级；旧版本没有班级表，从学生行推出来。
+        let v5 = "sms-store\t5\nnext_id\t2\nlsn\t0\nclass\tc\nstudent\t1\ta\t1\tc\nend\t1\n";
+        assert!(StudentStore::read_snapshot(v5.as_bytes()).is_ok());
+        for bad in [
+            v5.replace("class\tc\n", ""),
+            v5.replace("class\tc\n", "class\tc\nclass\tc\n"),
+            v5.replace(
+                "class\tc\nstudent\t1\ta\t1\tc\n",
+                "student\t1\ta\t1\tc\nclass\tc\n",
+            ),
+        ] {
+            assert!(
+                StudentStore::read_snapshot(bad.as_bytes()).is_err(),
+                "{bad}"
+            );
+        }
+        let v4 = v5.replace("\t5\n", "\t4\n").replace("class\tc\n", "");
+        let legacy = StudentStore::read_snapshot(v4.as_bytes()).unwrap();
+        assert_eq!(legacy.classes(), [("c", 1)]);
+
+        // 班级表之前写的日志：insert 引用的班级在回放时自动登记。
+        let path = temp_data_path("classes-legacy");
+        let student = crate::student::Student {
+            id: 1,
+            name: "bob".into(),
+            age: 9,
+            class_name: "old".into(),
+            attrs: Default::default(),
+        };
+        fs::write(
+            path.with_extension("sms.wal"),
+            encode_log_record(1, &Op::Insert(student)),
+        )
+        .unwrap();
+        let store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.classes(), [("old", 1)]);
+    }
+
+    #[test]
+    fn test_snapshot_truncated() {
+        let buf = snapshot(&sample_store());
+        for cut in 0..buf.len() {
+            assert!(
+                StudentStore::read_snapshot(&buf[..cut]).is_err(),
+                "cut={cut}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_snapshot_rejects_duplicate_id() {
+        let raw = "sms-store\t1\nnext_id\t3\nstudent\t1\ta\t1\tc\nstudent\t1\tb\t2\tc\nend\t2\n";
+        assert!(StudentStore::read_snapshot(raw.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_undo_is_logged_and_replayed() {
+        let path = temp_data_path("undo");
+        {
+            let mut store = open_with_classes(&path);
+            store.add("alice", 18, "class1").unwrap();
+            store.remove(1).unwrap();
+            store.undo().unwrap();
+        }
+        let store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.get_by_id(1).unwrap().name, "alice");
+        assert_eq!(store.lsn, 3 + 3);
+    }
+
+    #[test]
+    fn test_wal_drops_uncommitted_batch() {
+        let path = temp_data_path("txn");
+        {
+            let mut store = open_with_classes(&path);
+            store.add("alice", 18, "class1").unwrap();
+            store.begin().unwrap();
+            store.add("bob", 19, "class2").unwrap();
+            store.remove(1).unwrap();
+            store.commit_txn().unwrap();
+        }
+        let wal = path.with_extension("sms.wal");
+        let full = fs::read(&wal).unwrap();
+        assert_eq!(StudentStore::open(&path).unwrap().list_by_id().len(), 1);
+
+        // 截掉 commit 标记：整个批次都不应生效，只剩事务之前的 alice。
+        let cut = full[..full.len() - 1]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .unwrap()
+            + 1;
+        fs::write(&wal, &full[..cut]).unwrap();
+        let store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.list_by_id().len(), 1);
+        assert_eq!(store.get_by_id(1).unwrap().name, "alice");
+        assert_eq!(
+            fs::read(&wal).unwrap().len(),
+            full.iter().position(|b| *b == b'\n').unwrap() + 1
+        );
+    }
+
+    #[test]
+    fn test_wal_replay_ignores_torn_tail() {
+        let path = temp_data_path("torn");
+        {
+            let mut store = open_with_classes(&path);
+            store.add("alice", 18, "class1").unwrap();
+            store.add("bob", 19, "class2").unwrap();
+            store.modify(1, "alicia", 18, "class3").unwrap();
+            store.remove(2).unwrap();
+        }
+        // 模拟崩溃：最后一条记录只写出一半。
+        let torn = encode_log_record(3 + 5, &Op::Remove(1));
+        let mut log = OpenOptions::new()
+            .append(true)
+            .open(path.with_extension("sms.wal"))
+            .unwrap();
+        log.write_all(&torn.as_bytes()[..torn.len() / 2]).unwrap();
+
+        let mut store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.lsn, 3 + 4);
+        assert_eq!(store.next_id, 3);
+        assert_eq!(store.get_by_id(1).unwrap().name, "alicia");
+        assert!(store.get_by_id(2).is_none());
+        assert_eq!(store.search_by_name_exact("alicia").len(), 1);
+
+        // 残缺尾巴已被截掉，新记录可以正常追加并回放。
+        store.add("carol", 20, "class1").unwrap();
+        let store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.get_by_id(3).unwrap().name, "carol");
+    }
+
+    #[test]
+    fn test_checkpoint_then_stale_log_is_skipped() {
+        let path = temp_data_path("checkpoint");
+        let mut store = open_with_classes(&path);
+        store.add("alice", 18, "class1").unwrap();
+        let stale_log = fs::read(path.with_extension("sms.wal")).unwrap();
+        store.checkpoint().unwrap();
+        drop(store);
+
+        // 模拟“快照已写、日志未截断”时崩溃：旧日志记录的 lsn 不大于快照 lsn，应被跳过。
+        fs::write(path.with_extension("sms.wal"), stale_log).unwrap();
+        let store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.lsn, 3 + 1);
+    }
+
+    #[test]
+    fn test_id_exhaustion_is_rejected_before_logging() {
+        let path = temp_data_path("exhausted");
+        let raw = format!(
+            "sms-store\t5\nnext_id\t{}\nlsn\t0\nclass\tc\nend\t0\n",
+            u32::MAX - 1
+        );
+        fs::write(&path, raw).unwrap();
+        {
+            let mut store = StudentStore::open(&path).unwrap();
+            assert_eq!(store.add("last", 18, "c").unwrap(), u32::MAX - 1);
+            assert!(matches!(
+                store.add("one more", 18, "c"),
+                Err(StoreError::IdExhausted)
+            ));
+            assert_eq!(store.len(), 1);
+        }
+        // 失败的 add 没进日志，重开照常回放，id 仍然用尽。
+        let mut store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.lsn, 1);
+        assert!(matches!(
+            store.add("one more", 18, "c"),
+            Err(StoreError::IdExhausted)
+        ));
+    }
+
+    #[test]
+    fn test_replace_data_keeps_old_data_when_snapshot_fails() {
+        let path = temp_data_path("replace");
+        let mut store = open_with_classes(&path);
+        store.add("alice", 18, "class1").unwrap();
+
+        // `<path>.tmp` 是个目录，写快照必然失败：内存不能先换成新数据。
+        let tmp = path.with_extension("sms.tmp");
+        fs::create_dir(&tmp).unwrap();
+        assert!(store.replace_data(sample_store()).is_err());
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get_by_id(1).unwrap().name, "alice");
+        assert!(store.is_persistent());
+
+        fs::remove_dir(&tmp).unwrap();
+        store.replace_data(sample_store()).unwrap();
+        drop(store);
+        let store = StudentStore::open(&path).unwrap();
+        assert_eq!(store.len(), sample_store().len());
+        assert_eq!(store.get_by_id(3).unwrap().name, "tab\tand\\slash");
+    }
+
+    #[test]
+    fn test_wal_rejects_corrupt_middle_record() {
+        let path = temp_data_path("corrupt");
+        {
+            let mut store = open_with_classes(&path);
+            store.add("alice", 18, "class1").unwrap();
+            store.add("bob", 19, "class2").unwrap();
+        }
+        let wal = path.with_extension("sms.wal");
+        let mut raw = fs::read(&wal).unwrap();
+        raw[12] ^= 0x01;
+        fs::write(&wal, raw).unwrap();
+        assert!(StudentStore::open(&path).is_err());
+    }
+}
@@ -0,0 +1,605 @@
+//! `query` 小语言：
+//!
+//! ```text
+//! query [where <cond>] [order by <field> [asc|desc] {, ...}] [limit <n>] [offset <n>]
+//! cond := term {or term}      term := factor {and factor}
+//! factor := <field> <op> <value> | ( cond )
+//! field := id|name|age|class  op := = != < <= > >=
+//! ```
+//!
+//! 关键字不区分大小写；值可以是裸词，也可以用 '...' / "..." 包起来（可含空格）。
+//! 流程：lex_query（带列号的 token）→ [`QueryParser`]（AST）→ [`plan_query`]（选索引）
+//! → [`run_query`]。
+
+use std::fmt;
+use std::ops::Bound;
+
+use super::store::StudentStore;
+use super::{SortDirection, SortField, Student};
+
+/// 比较运算符。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    /// `=`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+/// 比较右侧的值；解析时已按字段类型转换好（id/age 是整数，name/class 是文本）。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// id / age 的值。
+    Int(u32),
+    /// name / class 的值。
+    Text(String),
+}
+
+/// where 条件的语法树。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// `<field> <op> <value>`
+    Cmp(SortField, CmpOp, Value),
+    /// `a and b`
+    And(Box<Expr>, Box<Expr>),
+    /// `a or b`
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// 解析后的一条查询。
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    /// where 条件；`None` 表示不过滤。
+    pub filter: Option<Expr>,
+    /// 依次比较的排序键；最后总以 id 升序兜底。
+    pub order: Vec<(SortField, SortDirection)>,
+    /// 最多返回多少行。
+    pub limit: Option<usize>,
+    /// 跳过前多少行。
+    pub offset: usize,
+}
+
+/// 查询解析失败：出错位置和原因。`Display` 形如 ``column 20: invalid age `x` ``。
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueryError {
+    /// 1-based，按字符计（中文一个字算一列），相对整行输入。
+    pub column: usize,
+    /// 出错原因。
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+fn query_error(column: usize, message: impl Into<String>) -> QueryError {
+    QueryError {
+        column,
+        message: message.into(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(CmpOp),
+    Comma,
+    LParen,
+    RParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "`{w}`"),
+            Token::Quoted(w) => write!(f, "\"{w}\""),
+            Token::Op(_) => write!(f, "operator"),
+            Token::Comma => write!(f, "`,`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+        }
+    }
+}
+
+// base_col：text 之前还有多少个字符（比如 `query` 这个命令名），用来把列号对齐到整行。
+fn lex_query(text: &str, base_col: usize) -> Result<Vec<(usize, Token)>, QueryError> {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = base_col + i + 1;
+        let c = chars[i];
+        let two = chars.get(i + 1) == Some(&'=');
+        let (token, len) = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ',' => (Token::Comma, 1),
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '=' => (Token::Op(CmpOp::Eq), 1),
+            '!' if two => (Token::Op(CmpOp::Ne), 2),
+            '!' => return Err(query_error(column, "expected `!=`")),
+            '<' if two => (Token::Op(CmpOp::Le), 2),
+            '<' => (Token::Op(CmpOp::Lt), 1),
+            '>' if two => (Token::Op(CmpOp::Ge), 2),
+            '>' => (Token::Op(CmpOp::Gt), 1),
+            '"' | '\'' => {
+                let Some(len) = chars[i + 1..].iter().position(|v| *v == c) else {
+                    return Err(query_error(column, "unterminated quoted value"));
+                };
+                let value = chars[i + 1..i + 1 + len].iter().collect::<String>();
+                (Token::Quoted(value), len + 2)
+            }
+            _ => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|v| v.is_whitespace() || "(),=!<>\"'".contains(*v))
+                    .unwrap_or(chars.len() - i);
+                (Token::Word(chars[i..i + len].iter().collect()), len)
+            }
+        };
+        tokens.push((column, token));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// 递归下降解析器，`and` 优先级高于 `or`。
+pub struct QueryParser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    // 输入结束位置的列号：“缺少 xxx”类错误指向这里。
+    end_column: usize,
+}
+
+impl QueryParser {
+    /// 解析 `query` 之后的文本。
+    ///
+    /// `base_col` 是 `text` 之前还有多少个字符（比如命令名 `query`），
+    /// 这样错误里的列号能直接对应到用户输入的整行。
+    pub fn parse(text: &str, base_col: usize) -> Result<Query, QueryError> {
+        let mut parser = QueryParser {
+            tokens: lex_query(text, base_col)?,
+            pos: 0,
+            end_column: base_col + text.chars().count() + 1,
+        };
+        parser.parse_query()
+    }
+
+    fn parse_query(&mut self) -> Result<Query, QueryError> {
+        let mut query = Query::default();
+        if self.eat_keyword("where") {
+            query.filter = Some(self.parse_or()?);
+        }
+        if self.eat_keyword("order") {
+            if !self.eat_keyword("by") {
+                return Err(self.error_here("expected `by` after `order`"));
+            }
+            loop {
+                let field = self.parse_field()?;
+                let direction = if self.eat_keyword("desc") {
+                    SortDirection::Desc
+                } else {
+                    self.eat_keyword("asc");
+                    SortDirection::Asc
+                };
+                query.order.push((field, direction));
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+        if self.eat_keyword("limit") {
+            query.limit = Some(self.parse_count("limit")?);
+        }
+        if self.eat_keyword("offset") {
+            query.offset = self.parse_count("offset")?;
+        }
+        if let Some((column, token)) = self.tokens.get(self.pos) {
+            return Err(query_error(*column, format!("unexpected {token}")));
+        }
+        Ok(query)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.parse_factor()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_factor()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expr, QueryError> {
+        if self.eat(&Token::LParen) {
+            let expr = self.parse_or()?;
+            if !self.eat(&Token::RParen) {
+                return Err(self.error_here("expected `)`"));
+            }
+            return Ok(expr);
+        }
+        let field = self.parse_field()?;
+        let op = match self.tokens.get(self.pos) {
+            Some((_, Token::Op(op))) => *op,
+            _ => return Err(self.error_here("expected comparison operator")),
+        };
+        self.pos += 1;
+        let (column, raw) = match self.tokens.get(self.pos) {
+            Some((column, Token::Word(v) | Token::Quoted(v))) => (*column, v.clone()),
+            _ => return Err(self.error_here("expected value")),
+        };
+        self.pos += 1;
+        let value = match field {
+            SortField::Id | SortField::Age => match raw.parse::<u32>() {
+                Ok(v) => Value::Int(v),
+                Err(_) => {
+                    let label = if matches!(field, SortField::Id) {
+                        "id"
+                    } else {
+                        "age"
+                    };
+                    return Err(query_error(column, format!("invalid {label} `{raw}`")));
+                }
+            },
+            SortField::Name | SortField::Class => Value::Text(raw),
+        };
+        Ok(Expr::Cmp(field, op, value))
+    }
+
+    fn parse_field(&mut self) -> Result<SortField, QueryError> {
+        let field = match self.tokens.get(self.pos) {
+            Some((_, Token::Word(w))) => match w.to_ascii_lowercase().as_str() {
+                "id" => Some(SortField::Id),
+                "name" => Some(SortField::Name),
+                "age" => Some(SortField::Age),
+                "class" => Some(SortField::Class),
+                _ => None,
+            },
+            _ => None,
+        };
+        match field {
+            Some(v) => {
+                self.pos += 1;
+                Ok(v)
+            }
+            None => Err(self.error_here("expected field (id|name|age|class)")),
+        }
+    }
+
+    fn parse_count(&mut self, keyword: &str) -> Result<usize, QueryError> {
+        match self.tokens.get(self.pos) {
+            Some((column, Token::Word(w))) => match w.parse::<usize>() {
+                Ok(v) => {
+                    self.pos += 1;
+                    Ok(v)
+                }
+                Err(_) => Err(query_error(*column, format!("invalid {keyword} `{w}`"))),
+            },
+            _ => Err(self.error_here(&format!("expected number after `{keyword}`"))),
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos).is_some_and(|(_, t)| t == token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let hit = matches!(
+            self.tokens.get(self.pos),
+            Some((_, Token::Word(w))) if w.eq_ignore_ascii_case(keyword)
+        );
+        if hit {
+            self.pos += 1;
+        }
+        hit
+    }
+
+    fn error_here(&self, message: &str) -> QueryError {
+        let column = self
+            .tokens
+            .get(self.pos)
+            .map_or(self.end_column, |(column, _)| *column);
+        query_error(column, message)
+    }
+}
+
+impl Expr {
+    /// 这条学生记录是否满足条件。
+    pub fn matches(&self, s: &Student) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(s) && b.matches(s),
+            Expr::Or(a, b) => a.matches(s) || b.matches(s),
+            Expr::Cmp(field, op, value) => {
+                let ord = match (field, value) {
+                    (SortField::Id, Value::Int(v)) => s.id.cmp(v),
+                    (SortField::Age, Value::Int(v)) => u32::from(s.age).cmp(v),
+                    (SortField::Name, Value::Text(v)) => s.name.as_str().cmp(v),
+                    (SortField::Class, Value::Text(v)) => s.class_name.as_str().cmp(v),
+                    // 解析阶段已经按字段类型产出 Value，不会出现类型错配。
+                    _ => return false,
+                };
+                match op {
+                    CmpOp::Eq => ord.is_eq(),
+                    CmpOp::Ne => ord.is_ne(),
+                    CmpOp::Lt => ord.is_lt(),
+                    CmpOp::Le => ord.is_le(),
+                    CmpOp::Gt => ord.is_gt(),
+                    CmpOp::Ge => ord.is_ge(),
+                }
+            }
+        }
+    }
+
+    // 顶层 and 链拆成合取项；顶层是 or 时只有它自己一项（不可拆）。
+    fn conjuncts(&self) -> Vec<&Expr> {
+        match self {
+            Expr::And(a, b) => {
+                let mut out = a.conjuncts();
+                out.extend(b.conjuncts());
+                out
+            }
+            _ => vec![self],
+        }
+    }
+}
+
+/// 查询计划：从哪里取候选行。
+///
+/// 候选行之后都会再用完整 where 条件过滤一遍，所以计划只需要保证“不漏”，不需要保证“不多”。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Plan {
+    /// 走有序 id 索引取一个区间。
+    IdRange((Bound<u32>, Bound<u32>)),
+    /// 走 name 索引取等值。
+    NameEq(String),
+    /// 走 class 索引取等值。
+    ClassEq(String),
+    /// 走 age 索引取一个区间。
+    AgeRange((Bound<u8>, Bound<u8>)),
+    /// 没有可用的过滤索引，但排序字段有索引：按索引顺序扫，省掉排序。
+    OrderedScan(SortField, SortDirection),
+    /// 按 id 顺序扫全部学生。
+    FullScan,
+}
+
+fn cmp_bounds<T: Copy>(op: CmpOp, v: T) -> Option<(Bound<T>, Bound<T>)> {
+    match op {
+        CmpOp::Eq => Some((Bound::Included(v), Bound::Included(v))),
+        CmpOp::Lt => Some((Bound::Unbounded, Bound::Excluded(v))),
+        CmpOp::Le => Some((Bound::Unbounded, Bound::Included(v))),
+        CmpOp::Gt => Some((Bound::Excluded(v), Bound::Unbounded)),
+        CmpOp::Ge => Some((Bound::Included(v), Bound::Unbounded)),
+        CmpOp::Ne => None,
+    }
+}
+
+/// 在 where 的合取项里挑一个能走索引的，优先级：id 等值 > name/class 等值 > id 区间 > age 区间。
+pub fn plan_query(query: &Query) -> Plan {
+    let mut best: Option<(u8, Plan)> = None;
+    for expr in query.filter.iter().flat_map(|f| f.conjuncts()) {
+        let Expr::Cmp(field, op, value) = expr else {
+            continue;
+        };
+        let candidate = match (field, value) {
+            (SortField::Id, Value::Int(v)) => cmp_bounds(*op, *v)
+                .map(|r| (if *op == CmpOp::Eq { 0 } else { 2 }, Plan::IdRange(r))),
+            (SortField::Name, Value::Text(v)) if *op == CmpOp::Eq => {
+                Some((1, Plan::NameEq(v.clone())))
+            }
+            (SortField::Class, Value::Text(v)) if *op == CmpOp::Eq => {
+                Some((1, Plan::ClassEq(v.clone())))
+            }
+            // age 超出 u8 的比较不走索引，交给逐行过滤处理。
+            (SortField::Age, Value::Int(v)) => u8::try_from(*v)
+                .ok()
+                .and_then(|v| cmp_bounds(*op, v))
+                .map(|r| (3, Plan::AgeRange(r))),
+            _ => None,
+        };
+        if let Some((rank, plan)) = candidate
+            && best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank)
+        {
+            best = Some((rank, plan));
+        }
+    }
+    if let Some((_, plan)) = best {
+        return plan;
+    }
+
+    // 索引顺序是 (key, id)，desc 时 id 也跟着降序。兜底的 id 排序是升序，
+    // 所以单个键只有 asc（或本身就是 id）能直接用；desc 要写成 `order by f desc, id desc`。
+    match query.order.as_slice() {
+        [(field, direction)] if *direction == SortDirection::Asc || *field == SortField::Id => {
+            Plan::OrderedScan(*field, *direction)
+        }
+        [(field, direction), (SortField::Id, id_direction)] if direction == id_direction => {
+            Plan::OrderedScan(*field, *direction)
+        }
+        _ => Plan::FullScan,
+    }
+}
+
+fn compare_by_keys(
+    a: &Student,
+    b: &Student,
+    keys: &[(SortField, SortDirection)],
+) -> std::cmp::Ordering {
+    for (field, direction) in keys {
+        let ord = match field {
+            SortField::Id => a.id.cmp(&b.id),
+            SortField::Name => a.name.cmp(&b.name),
+            SortField::Age => a.age.cmp(&b.age),
+            SortField::Class => a.class_name.cmp(&b.class_name),
+        };
+        let ord = match direction {
+            SortDirection::Asc => ord,
+            SortDirection::Desc => ord.reverse(),
+        };
+        if ord.is_ne() {
+            return ord;
+        }
+    }
+    // 兜底按 id 升序，保证结果确定。
+    a.id.cmp(&b.id)
+}
+
+/// 按 [`plan_query`] 选出的计划取候选行，再过滤、排序、分页。
+pub fn run_query<'a>(store: &'a StudentStore, query: &Query) -> Vec<&'a Student> {
+    let plan = plan_query(query);
+    let (mut rows, ordered) = match &plan {
+        Plan::IdRange(range) => (store.list_id_range(*range), false),
+        Plan::NameEq(name) => (store.search_by_name_exact(name), false),
+        Plan::ClassEq(class_name) => (store.search_by_class(class_name), false),
+        Plan::AgeRange(range) => (store.search_by_age(*range), false),
+        Plan::OrderedScan(field, direction) => (store.ordered(*field, *direction), true),
+        Plan::FullScan => (store.list_by_id(), false),
+    };
+    if let Some(filter) = &query.filter {
+        rows.retain(|s| filter.matches(s));
+    }
+    if !ordered {
+        rows.sort_by(|a, b| compare_by_keys(a, b, &query.order));
+    }
+    rows.into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CmpOp, Expr, Plan, Query, QueryParser, Value, plan_query, run_query};
+    use crate::student::{SortDirection, SortField, StudentStore};
+    use std::ops::Bound;
+
+    #[test]
+    fn test_query_parse_ast() {
+        let query = QueryParser::parse(
+            " where age >= 12 and class = A3 order by name desc, id limit 10 offset 20",
+            5,
+        )
+        .unwrap();
+        let expected = Query {
+            filter: Some(Expr::And(
+                Box::new(Expr::Cmp(SortField::Age, CmpOp::Ge, Value::Int(12))),
+                Box::new(Expr::Cmp(
+                    SortField::Class,
+                    CmpOp::Eq,
+                    Value::Text("A3".to_string()),
+                )),
+            )),
+            order: vec![
+                (SortField::Name, SortDirection::Desc),
+                (SortField::Id, SortDirection::Asc),
+            ],
+            limit: Some(10),
+            offset: 20,
+        };
+        assert_eq!(query, expected);
+    }
+
+    #[test]
+    fn test_query_error_columns() {
+        let column = |text: &str| QueryParser::parse(text, 5).unwrap_err().column;
+        // 列号相对整行 `query ...`，按字符计。
+        assert_eq!(column(" where age >= x"), 20);
+        assert_eq!(column(" where age >="), 19);
+        assert_eq!(column(" where 年龄 = 3"), 13);
+        assert_eq!(column(" where name = 张三 limit x"), 29);
+        assert_eq!(column(" where (age = 1"), 21);
+        assert_eq!(column(" order name"), 13);
+    }
+
+    #[test]
+    fn test_query_plan_uses_indexes() {
+        let plan = |text: &str| plan_query(&QueryParser::parse(text, 0).unwrap());
+        assert_eq!(
+            plan("where age > 3 and name = bob"),
+            Plan::NameEq("bob".to_string())
+        );
+        assert_eq!(
+            plan("where age > 3 and id = 7"),
+            Plan::IdRange((Bound::Included(7), Bound::Included(7)))
+        );
+        assert_eq!(
+            plan("where age < 10"),
+            Plan::AgeRange((Bound::Unbounded, Bound::Excluded(10)))
+        );
+        assert_eq!(
+            plan("where age < 300 order by id"),
+            Plan::OrderedScan(SortField::Id, SortDirection::Asc)
+        );
+        assert_eq!(plan("where age = 1 or id = 2"), Plan::FullScan);
+        // 同龄的按 id 升序兜底，索引的降序扫描给不出这个顺序。
+        assert_eq!(plan("order by age desc"), Plan::FullScan);
+        assert_eq!(
+            plan("order by id desc"),
+            Plan::OrderedScan(SortField::Id, SortDirection::Desc)
+        );
+        assert_eq!(
+            plan("order by class desc, id desc"),
+            Plan::OrderedScan(SortField::Class, SortDirection::Desc)
+        );
+    }
+
+    #[test]
+    fn test_query_results() {
+        let mut store = StudentStore::new();
+        store.add_class("A1").unwrap();
+        store.add_class("A3").unwrap();
+        for (name, age, class_name) in [
+            ("alice", 12, "A3"),
+            ("bob", 9, "A1"),
+            ("carol", 15, "A3"),
+            ("dan", 13, "A3"),
+        ] {
+            store.add(name, age, class_name).unwrap();
+        }
+        let ids = |text: &str| {
+            run_query(&store, &QueryParser::parse(text, 0).unwrap())
+                .iter()
+                .map(|s| s.id)
+                .collect::<Vec<u32>>()
+        };
+        assert_eq!(
+            ids("where age >= 12 and class = A3 order by name desc"),
+            vec![4, 3, 1]
+        );
+        assert_eq!(
+            ids("where class = A3 order by age limit 1 offset 1"),
+            vec![4]
+        );
+        assert_eq!(ids("where age < 10 or name = dan"), vec![2, 4]);
+        assert_eq!(
+            ids("where class != A3 or age > 14 order by age desc"),
+            vec![3, 2]
+        );
+        assert_eq!(ids("order by age desc limit 2"), vec![3, 4]);
+    }
+}
@@ -0,0 +1,187 @@
+//! `StudentRepository`：所有存储后端共有的最小操作集合。
+//!
+//! 读操作返回拥有所有权的 `Student`，而不是引用：分页后端的记录在磁盘上，
+//! 读出来的是一份拷贝，没有可以借出去的内存位置。
+
+use super::Student;
+use super::store::{StoreError, StudentStore};
+
+/// 可插拔的学生存储后端。
+///
+/// 目前有两个实现：
+/// - [`StudentStore`]：全部记录和索引都在内存里（可选快照 + 日志持久化）。
+/// - [`PagedStore`](super::PagedStore)：记录按固定大小的页放在文件里，内存只留 id -> 槽位。
+///
+/// 约定（所有后端都要满足，见 conformance 测试）：
+/// - id 从 1 开始递增，删除后也不复用；
+/// - `list_by_id` / `search_by_name_exact` 的结果按 id 升序；
+/// - 删除 / 修改不存在的 id 返回 [`StoreError::NotFound`]，且不改变任何数据。
+pub trait StudentRepository {
+    /// 新增学生，返回分配的 id。
+    fn add(&mut self, name: &str, age: u8, class_name: &str) -> Result<u32, StoreError>;
+
+    /// 按 id 查找。
+    fn get_by_id(&self, id: u32) -> Result<Option<Student>, StoreError>;
+
+    /// 全部学生，按 id 升序。
+    fn list_by_id(&self) -> Result<Vec<Student>, StoreError>;
+
+    /// 名字精确匹配，结果按 id 升序。
+    fn search_by_name_exact(&self, name: &str) -> Result<Vec<Student>, StoreError>;
+
+    /// 按 id 删除。
+    fn remove(&mut self, id: u32) -> Result<(), StoreError>;
+
+    /// 按 id 整条修改。
+    fn modify(&mut self, id: u32, name: &str, age: u8, class_name: &str) -> Result<(), StoreError>;
+}
+
+// 直接转发给同名的固有方法（固有方法优先，所以这里写全路径避免看起来像递归）。
+impl StudentRepository for StudentStore {
+    fn add(&mut self, name: &str, age: u8, class_name: &str) -> Result<u32, StoreError> {
+        StudentStore::add(self, name, age, class_name)
+    }
+
+    fn get_by_id(&self, id: u32) -> Result<Option<Student>, StoreError> {
+        Ok(StudentStore::get_by_id(self, id).cloned())
+    }
+
+    fn list_by_id(&self) -> Result<Vec<Student>, StoreError> {
+        Ok(StudentStore::list_by_id(self)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    fn search_by_name_exact(&self, name: &str) -> Result<Vec<Student>, StoreError> {
+        Ok(StudentStore::search_by_name_exact(self, name)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    fn remove(&mut self, id: u32) -> Result<(), StoreError> {
+        StudentStore::remove(self, id)
+    }
+
+    fn modify(&mut self, id: u32, name: &str, age: u8, class_name: &str) -> Result<(), StoreError> {
+        StudentStore::modify(self, id, name, age, class_name)
+    }
+}
+
+// 所有后端共用的一致性测试：每个检查函数只依赖 trait，
+// 由 conformance_suite! 为每个后端各生成一组 #[test]。
+#[cfg(test)]
+mod tests {
+    use super::StudentRepository;
+    use crate::student::persist::tests::{TempDataPath, temp_data_path};
+    use crate::student::{PagedStore, StoreError, StudentStore};
+
+    fn ids(rows: Vec<crate::student::Student>) -> Vec<u32> {
+        rows.iter().map(|s| s.id).collect()
+    }
+
+    fn check_add_and_get(repo: &mut dyn StudentRepository) {
+        assert_eq!(repo.add("alice", 12, "A3").unwrap(), 1);
+        assert_eq!(repo.add("张三", 9, "一班").unwrap(), 2);
+        let s = repo.get_by_id(2).unwrap().unwrap();
+        assert_eq!(
+            (s.name.as_str(), s.age, s.class_name.as_str()),
+            ("张三", 9, "一班")
+        );
+        assert!(repo.get_by_id(3).unwrap().is_none());
+        assert_eq!(ids(repo.list_by_id().unwrap()), vec![1, 2]);
+    }
+
+    fn check_remove_and_modify(repo: &mut dyn StudentRepository) {
+        for name in ["a", "b", "c"] {
+            repo.add(name, 10, "x").unwrap();
+        }
+        repo.remove(2).unwrap();
+        repo.modify(3, "cc", 11, "y").unwrap();
+        assert!(matches!(repo.remove(2), Err(StoreError::NotFound(2))));
+        assert!(matches!(
+            repo.modify(9, "z", 1, "z"),
+            Err(StoreError::NotFound(9))
+        ));
+        assert!(repo.get_by_id(2).unwrap().is_none());
+        assert_eq!(repo.get_by_id(3).unwrap().unwrap().name, "cc");
+        assert_eq!(ids(repo.list_by_id().unwrap()), vec![1, 3]);
+    }
+
+    fn check_ids_are_not_reused(repo: &mut dyn StudentRepository) {
+        repo.add("a", 10, "x").unwrap();
+        repo.add("b", 10, "x").unwrap();
+        repo.remove(2).unwrap();
+        repo.remove(1).unwrap();
+        assert_eq!(repo.add("c", 10, "x").unwrap(), 3);
+        assert_eq!(ids(repo.list_by_id().unwrap()), vec![3]);
+    }
+
+    fn check_search_by_name_is_id_ordered(repo: &mut dyn StudentRepository) {
+        for name in ["bob", "amy", "bob", "bob"] {
+            repo.add(name, 10, "x").unwrap();
+        }
+        // 删掉再加：分页后端会复用空出来的槽位，结果仍要按 id 排。
+        repo.remove(1).unwrap();
+        repo.add("bob", 11, "y").unwrap();
+        repo.modify(3, "amy", 10, "x").unwrap();
+        assert_eq!(ids(repo.search_by_name_exact("bob").unwrap()), vec![4, 5]);
+        assert_eq!(ids(repo.search_by_name_exact("amy").unwrap()), vec![2, 3]);
+        assert!(repo.search_by_name_exact("Bob").unwrap().is_empty());
+    }
+
+    macro_rules! conformance_suite {
+        ($backend:ident, $make:expr) => {
+            mod $backend {
+                use super::*;
+
+                #[test]
+                fn add_and_get() {
+                    let (mut repo, _dir) = $make("add");
+                    check_add_and_get(&mut repo);
+                }
+
+                #[test]
+                fn remove_and_modify() {
+                    let (mut repo, _dir) = $make("remove");
+                    check_remove_and_modify(&mut repo);
+                }
+
+                #[test]
+                fn ids_are_not_reused() {
+                    let (mut repo, _dir) = $make("ids");
+                    check_ids_are_not_reused(&mut repo);
+                }
+
+                #[test]
+                fn search_by_name_is_id_ordered() {
+                    let (mut repo, _dir) = $make("search");
+                    check_search_by_name_is_id_ordered(&mut repo);
+                }
+            }
+        };
+    }
+
+    // 内存后端要求班级先存在；分页后端的班级是自由文本，不需要这一步。
+    fn with_classes(mut store: StudentStore) -> StudentStore {
+        for name in ["A3", "一班", "x", "y"] {
+            store.add_class(name).unwrap();
+        }
+        store
+    }
+
+    // 构造函数同时返回临时目录的守卫，测试跑完才删目录。
+    conformance_suite!(memory, |_| (
+        with_classes(StudentStore::new()),
+        None::<TempDataPath>
+    ));
+    conformance_suite!(memory_logged, |name| {
+        let path = temp_data_path(&format!("conf-mem-{name}"));
+        (with_classes(StudentStore::open(&path).unwrap()), Some(path))
+    });
+    conformance_suite!(paged, |name| {
+        let path = temp_data_path(&format!("conf-paged-{name}"));
+        (PagedStore::open(&path).unwrap(), Some(path))
+    });
+}
@@ -0,0 +1,1275 @@
+//! `StudentStore`：主存、索引、写路径（日志 + undo/redo + 事务）与各类查询。
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::ops::Bound;
+
+use super::index::{ClassStats, Indexes, edit_distance, fold_name, range_is_valid};
+use super::persist::Wal;
+use super::{AttrValue, Attrs, GradeBands, GroupField, SortDirection, SortField, Student};
+
+/// 学生存储：一份主存加多份只存 id 的索引。
+///
+/// 存储设计总览：
+/// - 主数据只放在 `by_id`（HashMap）里，这是唯一完整记录存储。
+/// - `ids`/`indexes` 是轻量索引层，只保存 id（主键）来加速查询/排序。
+/// - 可以理解为“一份主存 + 多份索引”，而不是复制多份 Student 全量数据。
+/// - 思路上有点像 arena 的“句柄化访问”（用 id 回主存取值），
+///   但这里本质是索引化存储，不是 arena allocator。
+/// - 所有修改都先变成一条 [`Op`]，写日志后再应用到内存。
+///
+/// 用 [`StudentStore::new`] 得到纯内存的 store；用 [`StudentStore::open`] 打开数据文件，
+/// 之后每次修改都会先追加到预写日志。
+#[derive(Debug)]
+pub struct StudentStore {
+    // 主索引：按 id O(1) 查找。
+    pub(super) by_id: HashMap<u32, Student>,
+    // 有序 id 索引：便于稳定 list（默认按 id 升序）。
+    pub(super) ids: BTreeSet<u32>,
+    // 二级索引：name/class/age -> id 集合，支撑 search 和 order。
+    pub(super) indexes: Indexes,
+    // 班级表：学生的 class_name 必须在这里（`apply` 保证），删除时不能还有学生引用。
+    pub(super) classes: BTreeSet<String>,
+    // 成绩：课程 -> (id -> 分数)。按课程分组，`ranking` 只看一门课；删学生时级联删除。
+    pub(super) grades: BTreeMap<String, BTreeMap<u32, u8>>,
+    // 分数到等级的分档，只影响显示，不进快照。
+    pub(super) grade_bands: GradeBands,
+    pub(super) next_id: u32,
+    // 已应用的最后一条日志序号（log sequence number），快照里也会记下。
+    pub(super) lsn: u64,
+    // 只有打开了数据文件时才有；None 表示纯内存态。
+    pub(super) wal: Option<Wal>,
+    // undo 日志：每项是一组逆操作（已按回放顺序排好），最旧的在队头，超出上限时丢弃。
+    pub(super) undo_stack: VecDeque<Vec<Op>>,
+    // redo 日志：undo 时产生；任何新的修改都会清空它。
+    pub(super) redo_stack: Vec<Vec<Op>>,
+    // undo 最多保留多少步；0 表示关闭 undo。
+    pub(super) history_limit: usize,
+    // `begin` 之后到 `commit`/`rollback` 之前的事务状态。
+    pub(super) txn: Option<Txn>,
+}
+
+// 事务内的修改直接作用在内存上（事务内的读因此能看到），
+// 同时记下正向操作（commit 时整批写日志）和逆操作（rollback 时倒序执行）。
+#[derive(Debug, Default)]
+pub(super) struct Txn {
+    ops: Vec<Op>,
+    inverses: Vec<Op>,
+    // 逆操作不会回退 next_id，begin 时单独记下，rollback 时恢复。
+    next_id: u32,
+}
+
+/// undo 默认保留的步数。
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+/// 模糊查询不指定距离时允许的最大编辑距离。
+pub const DEFAULT_FUZZY_DISTANCE: usize = 2;
+
+/// 一次修改的完整描述：既用于写日志，也用于回放和 undo/redo。
+///
+/// `Insert` 带上 id，回放时不依赖 next_id 的推进顺序；
+/// 这样 undo 一次删除时可以用原 id 原样插回去。
+#[derive(Debug, Clone)]
+pub enum Op {
+    /// 按记录自带的 id 插入。
+    Insert(Student),
+    /// 按 id 删除。
+    Remove(u32),
+    /// 按 id 整条替换。
+    Update(Student),
+    /// 新建班级；已存在时不适用。
+    AddClass(String),
+    /// 删除班级；还有学生引用时不适用。
+    RemoveClass(String),
+    /// 班级改名，所有引用它的学生一起改；新名字必须还没被占用。
+    RenameClass {
+        /// 原来的名字。
+        from: String,
+        /// 新名字。
+        to: String,
+    },
+    /// 设置（`Some`）或删除（`None`）一门课的成绩；学生必须存在。
+    Grade {
+        /// 学生 id。
+        id: u32,
+        /// 课程名。
+        course: String,
+        /// 分数；`None` 表示删除这门课的成绩。
+        score: Option<u8>,
+    },
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Insert(s) => write!(f, "add id={} {} {} {}", s.id, s.name, s.age, s.class_name),
+            Op::Remove(id) => write!(f, "remove id={id}"),
+            Op::Update(s) => write!(f, "mod id={} {} {} {}", s.id, s.name, s.age, s.class_name),
+            Op::AddClass(name) => write!(f, "class add {name}"),
+            Op::RemoveClass(name) => write!(f, "class remove {name}"),
+            Op::RenameClass { from, to } => write!(f, "class rename {from} {to}"),
+            Op::Grade {
+                id,
+                course,
+                score: Some(score),
+            } => write!(f, "grade id={id} {course} {score}"),
+            Op::Grade {
+                id,
+                course,
+                score: None,
+            } => write!(f, "ungrade id={id} {course}"),
+        }
+    }
+}
+
+/// 存储操作（修改、undo/redo、事务，以及各后端的读写）的错误。
+#[derive(Debug)]
+pub enum StoreError {
+    /// 写日志失败；内存保持修改之前的样子。
+    Io(io::Error),
+    /// 后端数据文件读写失败（如分页文件损坏）。
+    Storage(io::Error),
+    /// 字段超出后端的长度限制。
+    FieldTooLong {
+        /// 字段名。
+        field: &'static str,
+        /// 最多允许的字节数。
+        max: usize,
+    },
+    /// 要删除 / 修改的 id 不存在。
+    NotFound(u32),
+    /// 班级不存在（学生引用了没建的班级，或者要删除 / 改名的班级不存在）。
+    UnknownClass(String),
+    /// 要新建 / 改成的班级名已经存在。
+    ClassExists(String),
+    /// 班级里还有学生，不能直接删除。
+    ClassInUse {
+        /// 班级名。
+        name: String,
+        /// 引用它的学生人数。
+        students: usize,
+    },
+    /// 要删除的属性不存在。
+    AttrNotFound {
+        /// 学生 id。
+        id: u32,
+        /// 属性 key。
+        key: String,
+    },
+    /// id 已经分配到头：`u32::MAX` 留作哨兵不分配，`next_id` 到了它就不能再新增学生。
+    IdExhausted,
+    /// 修改对当前数据不适用（如 id 冲突、记录不存在）；这组修改一条都没有生效，也没有写日志。
+    NotApplicable(Op),
+    /// 已经在事务里（不支持嵌套）。
+    TransactionOpen,
+    /// 没有打开的事务。
+    NoTransaction,
+    /// 这个操作不能在事务里做，参数是操作名（如 `"undo"`）。
+    InTransaction(&'static str),
+    /// 没有可撤销的修改。
+    NothingToUndo,
+    /// 没有可重做的修改。
+    NothingToRedo,
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "log write failed: {e}"),
+            StoreError::Storage(e) => write!(f, "storage error: {e}"),
+            StoreError::FieldTooLong { field, max } => {
+                write!(f, "{field} is longer than {max} bytes")
+            }
+            StoreError::NotFound(id) => write!(f, "id={id} not found"),
+            StoreError::UnknownClass(name) => write!(f, "class `{name}` does not exist"),
+            StoreError::ClassExists(name) => write!(f, "class `{name}` already exists"),
+            StoreError::ClassInUse { name, students } => {
+                write!(f, "class `{name}` still has {students} students")
+            }
+            StoreError::AttrNotFound { id, key } => {
+                write!(f, "id={id} has no attribute `{key}`")
+            }
+            StoreError::IdExhausted => write!(f, "no student ids left"),
+            StoreError::NotApplicable(op) => write!(f, "`{op}` does not apply to the current data"),
+            StoreError::TransactionOpen => write!(f, "transaction already open"),
+            StoreError::NoTransaction => write!(f, "no open transaction"),
+            StoreError::InTransaction(op) => {
+                write!(f, "`{op}` is not allowed inside a transaction")
+            }
+            StoreError::NothingToUndo => write!(f, "nothing to undo"),
+            StoreError::NothingToRedo => write!(f, "nothing to redo"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl Default for StudentStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StudentStore {
+    /// 空的纯内存 store：不写日志，undo 深度为 [`DEFAULT_HISTORY_LIMIT`]。
+    pub fn new() -> Self {
+        Self {
+            by_id: HashMap::new(),
+            ids: BTreeSet::new(),
+            indexes: Indexes::new(),
+            classes: BTreeSet::new(),
+            grades: BTreeMap::new(),
+            grade_bands: GradeBands::default(),
+            next_id: 1,
+            lsn: 0,
+            wal: None,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            txn: None,
+        }
+    }
+
+    /// 新增学生，返回分配的 id；班级必须已经用 [`add_class`](Self::add_class) 建好，
+    /// 否则返回 [`StoreError::UnknownClass`]。
+    pub fn add(&mut self, name: &str, age: u8, class_name: &str) -> Result<u32, StoreError> {
+        self.add_with_attrs(name, age, class_name, Attrs::new())
+    }
+
+    /// 新增带自定义属性的学生，返回分配的 id；和 [`add`](Self::add) 一样只是一条修改。
+    pub fn add_with_attrs(
+        &mut self,
+        name: &str,
+        age: u8,
+        class_name: &str,
+        attrs: Attrs,
+    ) -> Result<u32, StoreError> {
+        self.check_class(class_name)?;
+        if self.next_id == u32::MAX {
+            return Err(StoreError::IdExhausted);
+        }
+        let id = self.next_id;
+        self.commit(Op::Insert(Student {
+            id,
+            name: name.to_string(),
+            age,
+            class_name: class_name.to_string(),
+            attrs,
+        }))?;
+        Ok(id)
+    }
+
+    /// 按 id 查找，平均 O(1)。
+    pub fn get_by_id(&self, id: u32) -> Option<&Student> {
+        self.by_id.get(&id)
+    }
+
+    /// 全部学生，按 id 升序。
+    pub fn list_by_id(&self) -> Vec<&Student> {
+        self.ids
+            .iter()
+            .filter_map(|id| self.by_id.get(id))
+            .collect::<Vec<&Student>>()
+    }
+
+    /// 名字精确匹配，结果按 id 升序。
+    pub fn search_by_name_exact(&self, name: &str) -> Vec<&Student> {
+        self.resolve(self.indexes.name.get(name))
+    }
+
+    /// 班级精确匹配，结果按 id 升序。
+    pub fn search_by_class(&self, class_name: &str) -> Vec<&Student> {
+        self.resolve(self.indexes.class.get(class_name))
+    }
+
+    /// 不区分大小写/全半角/重音的前缀匹配，结果按折叠后的名字再按 id 升序。
+    pub fn search_by_name_prefix(&self, prefix: &str) -> Vec<&Student> {
+        self.resolve(self.indexes.name_folded.prefix(&fold_name(prefix)))
+    }
+
+    /// 编辑距离不超过 `max_dist` 的名字（按字符计，同样先折叠大小写/全半角/重音）。
+    ///
+    /// 结果按 (距离, 折叠名, id) 升序，距离最近的排在最前。
+    pub fn search_by_name_fuzzy(&self, name: &str, max_dist: usize) -> Vec<&Student> {
+        // 只对去重后的（折叠）名字算编辑距离，同名学生共享一次计算。
+        let target = fold_name(name).chars().collect::<Vec<char>>();
+        let mut hits = Vec::new();
+        for (key, ids) in &self.indexes.name_folded.entries {
+            let key = key.chars().collect::<Vec<char>>();
+            if let Some(dist) = edit_distance(&target, &key, max_dist) {
+                hits.push((dist, ids));
+            }
+        }
+        // 稳定排序：同距离时保留索引里的 (名字, id) 顺序。
+        hits.sort_by_key(|(dist, _)| *dist);
+        self.resolve(hits.into_iter().flat_map(|(_, ids)| ids.iter().copied()))
+    }
+
+    /// 属性 `key` 等于 `value`（类型也要相同）的学生，按 id 升序。
+    ///
+    /// 属性没有索引，是一次全表扫描：O(n)。
+    pub fn search_by_attr(&self, key: &str, value: &AttrValue) -> Vec<&Student> {
+        self.list_by_id()
+            .into_iter()
+            .filter(|s| s.attrs.get(key) == Some(value))
+            .collect()
+    }
+
+    /// 年龄区间查询，结果按 (age, id) 升序；起点大于终点时为空。
+    pub fn search_by_age(&self, range: (Bound<u8>, Bound<u8>)) -> Vec<&Student> {
+        self.resolve(self.indexes.age.range(range))
+    }
+
+    /// id 区间查询，结果按 id 升序；起点大于终点时为空。
+    pub fn list_id_range(&self, range: (Bound<u32>, Bound<u32>)) -> Vec<&Student> {
+        if !range_is_valid(&range) {
+            return Vec::new();
+        }
+        self.resolve(self.ids.range(range).copied())
+    }
+
+    /// 按字段排序的全部学生；同值按 id 升序，desc 时整体反转（同值按 id 降序）。
+    pub fn ordered(&self, field: SortField, direction: SortDirection) -> Vec<&Student> {
+        // 直接按索引顺序遍历，不需要排序。
+        let ids: Box<dyn Iterator<Item = u32> + '_> = match (field, direction) {
+            (SortField::Id, SortDirection::Asc) => Box::new(self.ids.iter().copied()),
+            (SortField::Id, SortDirection::Desc) => Box::new(self.ids.iter().rev().copied()),
+            (SortField::Name, _) => self.indexes.name.ids(direction),
+            (SortField::Age, _) => self.indexes.age.ids(direction),
+            (SortField::Class, _) => self.indexes.class.ids(direction),
+        };
+        self.resolve(ids)
+    }
+
+    /// 增量维护的人数 / 年龄统计，读取是 O(1)。
+    pub fn stats(&self) -> &ClassStats {
+        &self.indexes.stats
+    }
+
+    /// 按班级或年龄分组，组按 key 升序，组内按 id 升序。
+    pub fn groups(&self, field: GroupField) -> Vec<(String, Vec<&Student>)> {
+        // 直接沿对应索引的桶走。
+        match field {
+            GroupField::Class => self
+                .indexes
+                .class
+                .entries
+                .iter()
+                .map(|(key, ids)| (key.clone(), self.resolve(ids.iter().copied())))
+                .collect(),
+            GroupField::Age => self
+                .indexes
+                .age
+                .entries
+                .iter()
+                .map(|(key, ids)| (key.to_string(), self.resolve(ids.iter().copied())))
+                .collect(),
+        }
+    }
+
+    // 索引只存 id，统一在这里回主存取记录。
+    pub(super) fn resolve(&self, ids: impl Iterator<Item = u32>) -> Vec<&Student> {
+        ids.filter_map(|id| self.by_id.get(&id))
+            .collect::<Vec<&Student>>()
+    }
+
+    /// 按 id 删除，连同这个学生的全部成绩；id 不存在时返回 [`StoreError::NotFound`]，且不写日志。
+    ///
+    /// 级联删除是显式的：先逐门课删成绩（[`Op::Grade`] 带 `None`），最后删记录，
+    /// 整组一起写日志、算一步 undo，撤销时学生和成绩一起回来。
+    pub fn remove(&mut self, id: u32) -> Result<(), StoreError> {
+        if !self.by_id.contains_key(&id) {
+            return Err(StoreError::NotFound(id));
+        }
+        let group = self.remove_ops(id);
+        self.commit_group(group)
+    }
+
+    // 删除一个学生要执行的操作：先删它的每门成绩，最后删记录。
+    fn remove_ops(&self, id: u32) -> Vec<Op> {
+        let mut ops = self
+            .grades_of(id)
+            .into_iter()
+            .map(|(course, _)| Op::Grade {
+                id,
+                course: course.to_string(),
+                score: None,
+            })
+            .collect::<Vec<Op>>();
+        ops.push(Op::Remove(id));
+        ops
+    }
+
+    /// 新建班级；已存在时返回 [`StoreError::ClassExists`]。
+    pub fn add_class(&mut self, name: &str) -> Result<(), StoreError> {
+        if self.classes.contains(name) {
+            return Err(StoreError::ClassExists(name.to_string()));
+        }
+        self.commit(Op::AddClass(name.to_string()))
+    }
+
+    /// 删除班级，返回一起删掉的学生人数。
+    ///
+    /// 还有学生时：`force` 为 false 返回 [`StoreError::ClassInUse`]；为 true 时连同这些学生
+    /// （和他们的成绩）一起删，整组一次写日志、算一步 undo。
+    pub fn remove_class(&mut self, name: &str, force: bool) -> Result<usize, StoreError> {
+        self.check_class(name)?;
+        let ids = self.indexes.class.get(name).collect::<Vec<u32>>();
+        if !ids.is_empty() && !force {
+            return Err(StoreError::ClassInUse {
+                name: name.to_string(),
+                students: ids.len(),
+            });
+        }
+        let mut group = ids
+            .iter()
+            .flat_map(|&id| self.remove_ops(id))
+            .collect::<Vec<Op>>();
+        group.push(Op::RemoveClass(name.to_string()));
+        self.commit_group(group)?;
+        Ok(ids.len())
+    }
+
+    /// 班级改名，返回跟着改的学生人数。
+    ///
+    /// 是一条 [`Op::RenameClass`]：所有学生在同一次 `apply` 里改完，日志里也只有一条，
+    /// 不会出现“改了一半”的状态。原名不存在返回 [`StoreError::UnknownClass`]，
+    /// 新名已存在返回 [`StoreError::ClassExists`]。
+    pub fn rename_class(&mut self, from: &str, to: &str) -> Result<usize, StoreError> {
+        self.check_class(from)?;
+        if self.classes.contains(to) {
+            return Err(StoreError::ClassExists(to.to_string()));
+        }
+        let moved = self.indexes.class.get(from).count();
+        self.commit(Op::RenameClass {
+            from: from.to_string(),
+            to: to.to_string(),
+        })?;
+        Ok(moved)
+    }
+
+    /// 全部班级及各自的学生人数，按班级名排序（包括还没有学生的班级）。
+    pub fn classes(&self) -> Vec<(&str, usize)> {
+        self.classes
+            .iter()
+            .map(|name| (name.as_str(), self.indexes.class.get(name.as_str()).count()))
+            .collect()
+    }
+
+    /// 班级是否存在。
+    pub fn has_class(&self, name: &str) -> bool {
+        self.classes.contains(name)
+    }
+
+    fn check_class(&self, name: &str) -> Result<(), StoreError> {
+        if self.classes.contains(name) {
+            Ok(())
+        } else {
+            Err(StoreError::UnknownClass(name.to_string()))
+        }
+    }
+
+    /// 按 id 修改固定字段，自定义属性保持不变；id 不存在时返回 [`StoreError::NotFound`]，
+    /// 新班级不存在时返回 [`StoreError::UnknownClass`]。
+    pub fn modify(
+        &mut self,
+        id: u32,
+        name: &str,
+        age: u8,
+        class_name: &str,
+    ) -> Result<(), StoreError> {
+        let old = self.by_id.get(&id).ok_or(StoreError::NotFound(id))?;
+        self.check_class(class_name)?;
+        self.commit(Op::Update(Student {
+            id,
+            name: name.to_string(),
+            age,
+            class_name: class_name.to_string(),
+            attrs: old.attrs.clone(),
+        }))
+    }
+
+    /// 设置一个自定义属性（已有则覆盖）；id 不存在时返回 [`StoreError::NotFound`]。
+    ///
+    /// 和 `modify` 一样是一次整条替换（[`Op::Update`]），所以同样写日志、能 undo、能放进事务。
+    /// key 的合法性由调用方用 [`validate_attr_key`](super::validate_attr_key) 检查。
+    pub fn set_attr(&mut self, id: u32, key: &str, value: AttrValue) -> Result<(), StoreError> {
+        let mut student = self.by_id.get(&id).ok_or(StoreError::NotFound(id))?.clone();
+        student.attrs.insert(key.to_string(), value);
+        self.commit(Op::Update(student))
+    }
+
+    /// 删除一个自定义属性，返回删掉的值；没有这个属性时返回 [`StoreError::AttrNotFound`]。
+    pub fn unset_attr(&mut self, id: u32, key: &str) -> Result<AttrValue, StoreError> {
+        let mut student = self.by_id.get(&id).ok_or(StoreError::NotFound(id))?.clone();
+        let Some(old) = student.attrs.remove(key) else {
+            return Err(StoreError::AttrNotFound {
+                id,
+                key: key.to_string(),
+            });
+        };
+        self.commit(Op::Update(student))?;
+        Ok(old)
+    }
+
+    /// 记录一门课的成绩（已有则覆盖），返回原来的分数；id 不存在时返回 [`StoreError::NotFound`]。
+    ///
+    /// 分数范围（`0..=`[`MAX_SCORE`](super::MAX_SCORE)）和课程名由调用方检查。
+    pub fn set_grade(
+        &mut self,
+        id: u32,
+        course: &str,
+        score: u8,
+    ) -> Result<Option<u8>, StoreError> {
+        if !self.by_id.contains_key(&id) {
+            return Err(StoreError::NotFound(id));
+        }
+        let old = self.grades.get(course).and_then(|g| g.get(&id)).copied();
+        self.commit(Op::Grade {
+            id,
+            course: course.to_string(),
+            score: Some(score),
+        })?;
+        Ok(old)
+    }
+
+    /// 一个学生的全部成绩，按课程名排序；O(课程数)。
+    pub fn grades_of(&self, id: u32) -> Vec<(&str, u8)> {
+        self.grades
+            .iter()
+            .filter_map(|(course, scores)| Some((course.as_str(), *scores.get(&id)?)))
+            .collect()
+    }
+
+    /// 一门课的排名：分数从高到低，同分按 id 升序。
+    pub fn ranking(&self, course: &str) -> Vec<(&Student, u8)> {
+        let Some(scores) = self.grades.get(course) else {
+            return Vec::new();
+        };
+        let mut rows = scores
+            .iter()
+            .filter_map(|(id, score)| Some((self.by_id.get(id)?, *score)))
+            .collect::<Vec<(&Student, u8)>>();
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.id.cmp(&b.0.id)));
+        rows
+    }
+
+    /// 所有有成绩的课程名，按字典序。
+    pub fn courses(&self) -> Vec<&str> {
+        self.grades.keys().map(String::as_str).collect()
+    }
+
+    /// 当前的分数分档，默认 [`GradeBands::default`]。
+    pub fn grade_bands(&self) -> &GradeBands {
+        &self.grade_bands
+    }
+
+    /// 换一套分数分档；只影响等级的显示，不写日志。
+    pub fn set_grade_bands(&mut self, bands: GradeBands) {
+        self.grade_bands = bands;
+    }
+
+    /// 学生人数。
+    pub fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    /// 是否一个学生都没有。
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+
+    /// 已应用的最后一条日志序号；纯内存 store 也会计数。
+    pub fn lsn(&self) -> u64 {
+        self.lsn
+    }
+
+    /// 是否打开了数据文件（即每次修改都会写日志）。
+    pub fn is_persistent(&self) -> bool {
+        self.wal.is_some()
+    }
+
+    /// 设置 undo 最多保留的步数；0 表示关闭 undo。已有历史超出上限的部分丢弃最旧的。
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        while self.undo_stack.len() > limit {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    // 用户发起的新修改：写日志 + 应用，并把逆操作记进 undo 日志。
+    // 事务内只改内存并暂存，日志和 undo 都等到 commit_txn 时整批处理。
+    fn commit(&mut self, op: Op) -> Result<(), StoreError> {
+        self.commit_group(vec![op])
+    }
+
+    // 一组必须一起生效的修改（如删除学生时级联删成绩）：一次写日志、一步 undo。
+    fn commit_group(&mut self, group: Vec<Op>) -> Result<(), StoreError> {
+        if let Some(mut txn) = self.txn.take() {
+            // 不适用时这组修改已经退回，事务里之前的修改不受影响，事务保持打开。
+            let applied = self.apply_all(&group, self.next_id).map(|inverses| {
+                txn.ops.extend(group);
+                txn.inverses.extend(inverses);
+            });
+            self.txn = Some(txn);
+            return applied;
+        }
+        let inverses = self.write_group(group)?;
+        self.push_undo(inverses);
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    // 先在内存里整组应用，有一条不适用就整组退回、不写日志，坏操作永远进不了日志；
+    // 全部适用之后再写日志（fsync），写失败同样退回，内存保持原样。
+    // undo/redo 也走这里，所以它们同样会进日志、能被回放，且一组要么全生效要么全不生效。
+    // 返回整组的逆操作（已逆序排列，直接按顺序执行即可撤销）。
+    fn write_group(&mut self, group: Vec<Op>) -> Result<Vec<Op>, StoreError> {
+        let next_id = self.next_id;
+        let mut inverses = self.apply_all(&group, next_id)?;
+        if let Some(wal) = &mut self.wal
+            && let Err(e) = wal.append(self.lsn + 1, &group)
+        {
+            self.revert(inverses, next_id)?;
+            return Err(e.into());
+        }
+        self.lsn += group.len() as u64;
+        inverses.reverse();
+        Ok(inverses)
+    }
+
+    // 按顺序应用一组修改，返回按应用顺序排列的逆操作；
+    // 中途有一条不适用时先退回已经应用的部分，再返回 NotApplicable。
+    fn apply_all(&mut self, group: &[Op], next_id: u32) -> Result<Vec<Op>, StoreError> {
+        let mut inverses = Vec::with_capacity(group.len());
+        for op in group {
+            match self.apply(op.clone()) {
+                Some(inverse) => inverses.push(inverse),
+                None => {
+                    self.revert(inverses, next_id)?;
+                    return Err(StoreError::NotApplicable(op.clone()));
+                }
+            }
+        }
+        Ok(inverses)
+    }
+
+    // 倒序执行逆操作（按应用顺序传入），再把 next_id 恢复成应用之前的值。
+    // 刚生成的逆操作总能应用；真不能时返回错误而不是 panic，内存停在中途，要靠 repair 收拾。
+    fn revert(&mut self, inverses: Vec<Op>, next_id: u32) -> Result<(), StoreError> {
+        self.next_id = next_id;
+        for inverse in inverses.into_iter().rev() {
+            if self.apply(inverse.clone()).is_none() {
+                return Err(StoreError::NotApplicable(inverse));
+            }
+        }
+        Ok(())
+    }
+
+    // 只改内存，不碰日志：write_group 和日志回放共用这一条路径。
+    // 成功时返回能把这次修改撤销掉的逆操作；op 不适用（id 冲突/不存在）时返回 None。
+    pub(super) fn apply(&mut self, op: Op) -> Option<Op> {
+        match op {
+            Op::Insert(student) => {
+                let id = student.id;
+                if !self.classes.contains(&student.class_name) || !self.insert_record(student) {
+                    return None;
+                }
+                self.next_id = self.next_id.max(id.saturating_add(1));
+                Some(Op::Remove(id))
+            }
+            Op::Remove(id) => self.remove_record(id).map(Op::Insert),
+            Op::Update(student) => {
+                if !self.classes.contains(&student.class_name) {
+                    return None;
+                }
+                self.update_record(student).map(Op::Update)
+            }
+            Op::AddClass(name) => {
+                if !self.classes.insert(name.clone()) {
+                    return None;
+                }
+                Some(Op::RemoveClass(name))
+            }
+            Op::RemoveClass(name) => {
+                if self.indexes.class.get(name.as_str()).next().is_some()
+                    || !self.classes.remove(&name)
+                {
+                    return None;
+                }
+                Some(Op::AddClass(name))
+            }
+            Op::RenameClass { from, to } => {
+                if self.classes.contains(&to) || !self.classes.remove(&from) {
+                    return None;
+                }
+                self.classes.insert(to.clone());
+                for id in self.indexes.class.get(from.as_str()).collect::<Vec<u32>>() {
+                    let mut student = self.by_id[&id].clone();
+                    student.class_name = to.clone();
+                    self.update_record(student);
+                }
+                Some(Op::RenameClass { from: to, to: from })
+            }
+            Op::Grade { id, course, score } => {
+                let old = self.apply_grade(id, &course, score)?;
+                Some(Op::Grade {
+                    id,
+                    course,
+                    score: old,
+                })
+            }
+        }
+    }
+
+    // 设置 / 删除一门成绩，返回原来的分数（外层 Option 表示能否应用）。
+    // 学生不存在、或要删的成绩本来就没有时不适用。
+    pub(super) fn apply_grade(
+        &mut self,
+        id: u32,
+        course: &str,
+        score: Option<u8>,
+    ) -> Option<Option<u8>> {
+        if !self.by_id.contains_key(&id) {
+            return None;
+        }
+        match score {
+            Some(score) => Some(
+                self.grades
+                    .entry(course.to_string())
+                    .or_default()
+                    .insert(id, score),
+            ),
+            None => {
+                let scores = self.grades.get_mut(course)?;
+                let old = scores.remove(&id)?;
+                if scores.is_empty() {
+                    self.grades.remove(course);
+                }
+                Some(Some(old))
+            }
+        }
+    }
+
+    fn push_undo(&mut self, group: Vec<Op>) {
+        if self.history_limit == 0 {
+            return;
+        }
+        self.undo_stack.push_back(group);
+        while self.undo_stack.len() > self.history_limit {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    /// 撤销最近一次修改（或一个已提交的事务），返回实际执行的逆操作。
+    ///
+    /// undo 本身也写日志，所以重启回放后结果一致。事务内返回 [`StoreError::InTransaction`]。
+    pub fn undo(&mut self) -> Result<Vec<Op>, StoreError> {
+        if self.txn.is_some() {
+            return Err(StoreError::InTransaction("undo"));
+        }
+        let Some(group) = self.undo_stack.pop_back() else {
+            return Err(StoreError::NothingToUndo);
+        };
+        match self.write_group(group.clone()) {
+            Ok(redo) => {
+                self.redo_stack.push(redo);
+                Ok(group)
+            }
+            Err(e) => {
+                self.undo_stack.push_back(group);
+                Err(e)
+            }
+        }
+    }
+
+    /// 重做最近一次被撤销的修改，返回实际执行的操作；任何新修改都会清空 redo。
+    pub fn redo(&mut self) -> Result<Vec<Op>, StoreError> {
+        if self.txn.is_some() {
+            return Err(StoreError::InTransaction("redo"));
+        }
+        let Some(group) = self.redo_stack.pop() else {
+            return Err(StoreError::NothingToRedo);
+        };
+        match self.write_group(group.clone()) {
+            Ok(undo) => {
+                self.push_undo(undo);
+                Ok(group)
+            }
+            Err(e) => {
+                self.redo_stack.push(group);
+                Err(e)
+            }
+        }
+    }
+
+    /// 开始事务：之后的修改立即可见，但要等 [`commit_txn`](Self::commit_txn)
+    /// 才写日志；不支持嵌套。
+    pub fn begin(&mut self) -> Result<(), StoreError> {
+        if self.txn.is_some() {
+            return Err(StoreError::TransactionOpen);
+        }
+        self.txn = Some(Txn {
+            next_id: self.next_id,
+            ..Txn::default()
+        });
+        Ok(())
+    }
+
+    /// 是否有打开的事务。
+    pub fn in_transaction(&self) -> bool {
+        self.txn.is_some()
+    }
+
+    /// 打开的事务里暂存了多少条修改；没有事务时为 `None`。
+    pub fn pending_changes(&self) -> Option<usize> {
+        self.txn.as_ref().map(|txn| txn.ops.len())
+    }
+
+    /// 提交事务：整批写日志（一次 fsync），整批算作一步 undo。返回提交的修改条数。
+    ///
+    /// 写日志失败时事务保持打开，可以重试 commit 或 rollback。
+    pub fn commit_txn(&mut self) -> Result<usize, StoreError> {
+        let Some(txn) = self.txn.take() else {
+            return Err(StoreError::NoTransaction);
+        };
+        let n = txn.ops.len();
+        if n == 0 {
+            return Ok(0);
+        }
+        if let Some(wal) = &mut self.wal
+            && let Err(e) = wal.append(self.lsn + 1, &txn.ops)
+        {
+            self.txn = Some(txn);
+            return Err(e.into());
+        }
+        self.lsn += n as u64;
+        let mut inverses = txn.inverses;
+        inverses.reverse();
+        self.push_undo(inverses);
+        self.redo_stack.clear();
+        Ok(n)
+    }
+
+    /// 回滚事务，返回撤销的修改条数。
+    ///
+    /// 倒序执行逆操作并恢复 next_id，内存状态与 begin 时完全一致。
+    /// 逆操作不适用时返回 [`StoreError::NotApplicable`]，事务照样结束。
+    pub fn rollback(&mut self) -> Result<usize, StoreError> {
+        let txn = self.txn.take().ok_or(StoreError::NoTransaction)?;
+        let n = txn.inverses.len();
+        self.revert(txn.inverses, txn.next_id)?;
+        Ok(n)
+    }
+
+    // 按记录自带的 id 原样插入，并同步全部索引；load 重建索引也走这里。
+    // id 已存在时返回 false，不覆盖旧记录。
+    pub(super) fn insert_record(&mut self, student: Student) -> bool {
+        let id = student.id;
+        if self.by_id.contains_key(&id) {
+            return false;
+        }
+        self.ids.insert(id);
+        self.indexes.insert(&student);
+        self.by_id.insert(id, student);
+        true
+    }
+
+    // 还有成绩的学生不能直接删（成绩会变成孤儿）：`remove` 总是先生成删成绩的操作。
+    fn remove_record(&mut self, id: u32) -> Option<Student> {
+        if self.grades.values().any(|scores| scores.contains_key(&id)) {
+            return None;
+        }
+        let removed = self.by_id.remove(&id)?;
+        self.ids.remove(&id);
+        self.indexes.remove(&removed);
+        Some(removed)
+    }
+
+    // 返回修改前的旧记录。
+    fn update_record(&mut self, student: Student) -> Option<Student> {
+        let id = student.id;
+        let slot = self.by_id.get_mut(&id)?;
+        let old = std::mem::replace(slot, student);
+        // 先换主存再更新索引：`indexes` 和 `by_id` 是不同字段，可以同时借用。
+        self.indexes.remove(&old);
+        self.indexes.insert(&self.by_id[&id]);
+        Some(old)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Op, StoreError, StudentStore};
+    use crate::student::{AttrValue, GroupField, SortDirection, SortField, Student};
+    use std::ops::Bound;
+
+    // 建好班级的空 store；建班级不算进 undo 历史，测试只关心之后的修改。
+    pub(crate) fn store_with_classes(classes: &[&str]) -> StudentStore {
+        let mut store = StudentStore::new();
+        for name in classes {
+            store.add_class(name).unwrap();
+        }
+        store.undo_stack.clear();
+        store
+    }
+
+    pub(crate) fn sample_store() -> StudentStore {
+        let mut store = store_with_classes(&["class1", "class2", "class9"]);
+        store.add("alice", 18, "class1").unwrap();
+        store.add("bob", 19, "class2").unwrap();
+        store.add("tab\tand\\slash", 20, "class1").unwrap();
+        store.remove(2).unwrap();
+        store
+    }
+
+    #[test]
+    fn test_secondary_indexes_follow_mutations() {
+        let mut store = sample_store();
+        store.modify(1, "alice", 12, "class2").unwrap();
+        let ids = |rows: Vec<&Student>| rows.iter().map(|s| s.id).collect::<Vec<u32>>();
+
+        assert_eq!(ids(store.search_by_class("class1")), vec![3]);
+        assert_eq!(ids(store.search_by_class("class2")), vec![1]);
+        assert_eq!(
+            ids(store.search_by_age((Bound::Included(10), Bound::Excluded(20)))),
+            vec![1]
+        );
+        assert_eq!(
+            ids(store.search_by_age((Bound::Included(12), Bound::Included(20)))),
+            vec![1, 3]
+        );
+        // 起点大于终点：空结果而不是 panic。
+        assert!(
+            store
+                .search_by_age((Bound::Included(18), Bound::Excluded(10)))
+                .is_empty()
+        );
+        assert!(
+            store
+                .list_id_range((Bound::Excluded(2), Bound::Excluded(2)))
+                .is_empty()
+        );
+        assert_eq!(
+            ids(store.list_id_range((Bound::Included(2), Bound::Unbounded))),
+            vec![3]
+        );
+
+        store.remove(3).unwrap();
+        assert!(store.search_by_class("class1").is_empty());
+        assert!(!store.indexes.class.entries.contains_key("class1"));
+    }
+
+    #[test]
+    fn test_prefix_and_fuzzy_search() {
+        let mut store = store_with_classes(&["x"]);
+        for name in ["Alice", "alina", "Bob", "José", "张三", "张三丰", "李四"] {
+            store.add(name, 10, "x").unwrap();
+        }
+        let names = |rows: Vec<&Student>| {
+            rows.iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(",")
+        };
+        assert_eq!(names(store.search_by_name_prefix("AL")), "Alice,alina");
+        assert_eq!(names(store.search_by_name_prefix("张")), "张三,张三丰");
+        assert_eq!(names(store.search_by_name_fuzzy("alise", 2)), "Alice,alina");
+        assert_eq!(names(store.search_by_name_fuzzy("jose", 0)), "José");
+        assert_eq!(
+            names(store.search_by_name_fuzzy("张三丰", 1)),
+            "张三丰,张三"
+        );
+    }
+
+    #[test]
+    fn test_ordered_matches_sort_with_id_tiebreak() {
+        let mut store = store_with_classes(&["x"]);
+        for (name, age) in [("b", 10), ("a", 12), ("b", 9), ("a", 12)] {
+            store.add(name, age, "x").unwrap();
+        }
+        let ids = |field, direction| {
+            store
+                .ordered(field, direction)
+                .iter()
+                .map(|s| s.id)
+                .collect::<Vec<u32>>()
+        };
+        assert_eq!(ids(SortField::Name, SortDirection::Asc), vec![2, 4, 1, 3]);
+        assert_eq!(ids(SortField::Name, SortDirection::Desc), vec![3, 1, 4, 2]);
+        assert_eq!(ids(SortField::Age, SortDirection::Desc), vec![4, 2, 1, 3]);
+        assert_eq!(ids(SortField::Id, SortDirection::Desc), vec![4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_stats_and_groups_follow_mutations() {
+        let mut store = store_with_classes(&["A1", "A3"]);
+        store.add("alice", 12, "A3").unwrap();
+        store.add("bob", 9, "A1").unwrap();
+        store.add("carol", 15, "A3").unwrap();
+        store.modify(2, "bob", 10, "A3").unwrap();
+        store.remove(3).unwrap();
+
+        let stats = store.stats();
+        assert_eq!(stats.total().count(), 2);
+        assert!(stats.class("A1").is_none());
+        let a3 = stats.class("A3").unwrap();
+        assert_eq!((a3.count(), a3.min(), a3.max()), (2, Some(10), Some(12)));
+        assert_eq!(a3.average(), Some(11.0));
+
+        let groups = store
+            .groups(GroupField::Age)
+            .into_iter()
+            .map(|(key, rows)| (key, rows.iter().map(|s| s.id).collect::<Vec<u32>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![("10".to_string(), vec![2]), ("12".to_string(), vec![1])]
+        );
+
+        store.undo().unwrap();
+        assert_eq!(store.stats().class("A3").unwrap().max(), Some(15));
+    }
+
+    #[test]
+    fn test_undo_redo_remove_and_modify() {
+        let mut store = sample_store();
+        store.modify(1, "alicia", 21, "class9").unwrap();
+        store.remove(3).unwrap();
+
+        store.undo().unwrap();
+        assert_eq!(store.get_by_id(3).unwrap().name, "tab\tand\\slash");
+        assert_eq!(store.search_by_name_exact("tab\tand\\slash").len(), 1);
+        store.undo().unwrap();
+        assert_eq!(store.get_by_id(1).unwrap().name, "alice");
+        assert!(store.search_by_name_exact("alicia").is_empty());
+
+        store.redo().unwrap();
+        assert_eq!(store.get_by_id(1).unwrap().age, 21);
+        // 新修改会清空 redo。
+        store.add("dave", 22, "class2").unwrap();
+        assert!(matches!(store.redo(), Err(StoreError::NothingToRedo)));
+        assert_eq!(store.next_id, 5);
+    }
+
+    #[test]
+    fn test_undo_history_is_bounded() {
+        let mut store = store_with_classes(&["x"]);
+        store.history_limit = 2;
+        for name in ["a", "b", "c"] {
+            store.add(name, 10, "x").unwrap();
+        }
+        assert!(store.undo().is_ok());
+        assert!(store.undo().is_ok());
+        assert!(matches!(store.undo(), Err(StoreError::NothingToUndo)));
+        assert_eq!(store.list_by_id().len(), 1);
+    }
+
+    #[test]
+    fn test_rollback_restores_exact_state() {
+        let mut store = sample_store();
+        let (by_id, ids, indexes, next_id) = (
+            store.by_id.clone(),
+            store.ids.clone(),
+            store.indexes.clone(),
+            store.next_id,
+        );
+
+        store.begin().unwrap();
+        store.add("dave", 22, "class2").unwrap();
+        store.modify(1, "dave", 30, "class9").unwrap();
+        store.remove(3).unwrap();
+        // 事务内的读能看到暂存的修改。
+        assert_eq!(store.search_by_name_exact("dave").len(), 2);
+        assert_eq!(store.rollback().unwrap(), 3);
+
+        assert_eq!(store.by_id, by_id);
+        assert_eq!(store.ids, ids);
+        assert_eq!(store.indexes, indexes);
+        assert_eq!(store.next_id, next_id);
+    }
+
+    #[test]
+    fn test_group_that_does_not_apply_changes_nothing() {
+        let mut store = sample_store();
+        let (by_id, next_id, lsn) = (store.by_id.clone(), store.next_id, store.lsn);
+        let mut dave = store.by_id[&1].clone();
+        dave.id = 10;
+        let group = vec![Op::Insert(dave), Op::Remove(2)];
+        assert!(matches!(
+            store.write_group(group.clone()),
+            Err(StoreError::NotApplicable(Op::Remove(2)))
+        ));
+        assert_eq!(
+            (&store.by_id, store.next_id, store.lsn),
+            (&by_id, next_id, lsn)
+        );
+
+        // 事务里同样整组退回，事务保持打开。
+        store.begin().unwrap();
+        assert!(store.commit_group(group).is_err());
+        assert_eq!(store.pending_changes(), Some(0));
+        assert_eq!((&store.by_id, store.next_id), (&by_id, next_id));
+    }
+
+    #[test]
+    fn test_commit_is_one_undo_step() {
+        let mut store = sample_store();
+        store.begin().unwrap();
+        store.add("dave", 22, "class2").unwrap();
+        store.remove(1).unwrap();
+        assert_eq!(store.commit_txn().unwrap(), 2);
+        store.undo().unwrap();
+        assert!(store.get_by_id(1).is_some());
+        assert!(store.get_by_id(4).is_none());
+    }
+
+    #[test]
+    fn test_attrs_set_unset_search_and_undo() {
+        let mut store = sample_store();
+        store
+            .set_attr(1, "phone", AttrValue::infer("0123"))
+            .unwrap();
+        store.set_attr(3, "phone", AttrValue::Int(123)).unwrap();
+        store.set_attr(1, "gpa", AttrValue::Float(3.5)).unwrap();
+        assert!(matches!(
+            store.set_attr(2, "phone", AttrValue::Bool(true)),
+            Err(StoreError::NotFound(2))
+        ));
+
+        // 类型不同就不相等：文本 "0123" 不等于整数 123。
+        let text = AttrValue::Text("0123".to_string());
+        let ids = |rows: Vec<&Student>| rows.iter().map(|s| s.id).collect::<Vec<u32>>();
+        assert_eq!(ids(store.search_by_attr("phone", &text)), vec![1]);
+        assert_eq!(
+            ids(store.search_by_attr("phone", &AttrValue::Int(123))),
+            vec![3]
+        );
+
+        // mod 只改固定字段，属性保留。
+        store.modify(1, "alicia", 19, "class2").unwrap();
+        assert_eq!(store.get_by_id(1).unwrap().attrs.len(), 2);
+
+        assert_eq!(store.unset_attr(1, "gpa").unwrap(), AttrValue::Float(3.5));
+        assert!(matches!(
+            store.unset_attr(1, "gpa"),
+            Err(StoreError::AttrNotFound { id: 1, .. })
+        ));
+        store.undo().unwrap();
+        assert_eq!(
+            store.get_by_id(1).unwrap().attrs.get("gpa"),
+            Some(&AttrValue::Float(3.5))
+        );
+
+        // 删除后 undo，属性跟着记录一起回来；事务回滚也一样。
+        store.remove(1).unwrap();
+        store.undo().unwrap();
+        assert_eq!(store.get_by_id(1).unwrap().attrs.len(), 2);
+        store.begin().unwrap();
+        store.unset_attr(1, "phone").unwrap();
+        store.rollback().unwrap();
+        assert_eq!(store.search_by_attr("phone", &text).len(), 1);
+    }
+
+    #[test]
+    fn test_grades_ranking_and_cascading_remove() {
+        let mut store = sample_store();
+        assert_eq!(store.set_grade(1, "math", 80).unwrap(), None);
+        store.set_grade(3, "math", 92).unwrap();
+        store.set_grade(1, "art", 60).unwrap();
+        assert_eq!(store.set_grade(1, "math", 92).unwrap(), Some(80));
+        assert!(matches!(
+            store.set_grade(2, "math", 1),
+            Err(StoreError::NotFound(2))
+        ));
+
+        // 同分按 id 升序。
+        let rank = |store: &StudentStore, course| {
+            let rows = store.ranking(course);
+            rows.iter()
+                .map(|(s, score)| (s.id, *score))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rank(&store, "math"), [(1, 92), (3, 92)]);
+        assert_eq!(store.grades_of(1), [("art", 60), ("math", 92)]);
+        assert_eq!(store.courses(), ["art", "math"]);
+
+        // 删学生连同成绩；只剩空课程时课程也消失。undo 一步全部恢复。
+        store.remove(1).unwrap();
+        assert_eq!(rank(&store, "math"), [(3, 92)]);
+        assert_eq!(store.courses(), ["math"]);
+        let undone = store.undo().unwrap();
+        assert_eq!(undone.len(), 3);
+        assert_eq!(store.grades_of(1), [("art", 60), ("math", 92)]);
+
+        // 事务里删除再回滚，成绩同样回来。
+        store.begin().unwrap();
+        store.remove(1).unwrap();
+        assert_eq!(store.pending_changes(), Some(3));
+        store.rollback().unwrap();
+        assert_eq!(store.grades_of(1).len(), 2);
+
+        // 还有成绩的记录不能被单独的 Remove 删掉。
+        assert!(store.apply(Op::Remove(1)).is_none());
+    }
+
+    #[test]
+    fn test_class_table_checks_and_cascades() {
+        let mut store = sample_store();
+        assert!(matches!(
+            store.add("dan", 9, "nope"),
+            Err(StoreError::UnknownClass(c)) if c == "nope"
+        ));
+        assert!(matches!(
+            store.modify(1, "alice", 18, "nope"),
+            Err(StoreError::UnknownClass(_))
+        ));
+        assert!(matches!(
+            store.add_class("class1"),
+            Err(StoreError::ClassExists(_))
+        ));
+        assert!(matches!(
+            store.remove_class("class1", false),
+            Err(StoreError::ClassInUse { students: 2, .. })
+        ));
+        assert_eq!(
+            store.classes(),
+            [("class1", 2), ("class2", 0), ("class9", 0)]
+        );
+
+        // 改名一步改完所有学生，索引跟着走；undo 一步改回来。
+        assert_eq!(store.rename_class("class1", "A1").unwrap(), 2);
+        assert!(!store.has_class("class1"));
+        assert_eq!(store.search_by_class("A1").len(), 2);
+        assert!(store.search_by_class("class1").is_empty());
+        assert!(matches!(
+            store.rename_class("A1", "class2"),
+            Err(StoreError::ClassExists(_))
+        ));
+        assert_eq!(store.undo().unwrap().len(), 1);
+        assert_eq!(store.search_by_class("class1").len(), 2);
+
+        // --force 连学生带成绩一起删，整组一步 undo。
+        store.set_grade(1, "math", 90).unwrap();
+        assert_eq!(store.remove_class("class1", true).unwrap(), 2);
+        assert!(store.is_empty());
+        assert!(store.courses().is_empty());
+        assert_eq!(store.undo().unwrap().len(), 4);
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.grades_of(1), [("math", 90)]);
+
+        // 空班级可以直接删；还有学生的班级不能被单独的 RemoveClass 删掉。
+        assert_eq!(store.remove_class("class9", false).unwrap(), 0);
+        assert!(store.apply(Op::RemoveClass("class1".into())).is_none());
+        assert!(matches!(
+            store.remove_class("class9", false),
+            Err(StoreError::UnknownClass(_))
+        ));
+    }
+}
@@ -0,0 +1,437 @@
+//! 一致性检查（fsck）：核对 `ids`、各个二级索引、成绩和班级表是否和主存 `by_id` 对得上，必要时从主存修复。
+//!
+//! 正常情况下所有修改都经过 `apply`，索引和主存一起变，这里永远查不出问题；
+//! 它防的是将来改代码时漏掉某条维护路径（比如新加的 `Op` 忘了同步某个索引）。
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io;
+
+use super::index::{ClassStats, Indexes, SecondaryIndex, StudentIndex};
+use super::{PersistError, StoreError, Student, StudentStore};
+
+/// [`StudentStore::verify`] 发现的一处不一致。`index` 是索引名：`ids`、`name`、`name_folded`、`class`、`age`，
+/// 以及成绩表 `grades`（key 是课程名）。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// 索引里的 id 在主存里没有记录。
+    DanglingId {
+        /// 索引名。
+        index: &'static str,
+        /// 悬空的 id。
+        id: u32,
+    },
+    /// 主存里的记录在索引里找不到。
+    MissingEntry {
+        /// 索引名。
+        index: &'static str,
+        /// 缺失的 id。
+        id: u32,
+    },
+    /// 索引把 id 挂在了和记录字段不符的 key 下。
+    StaleEntry {
+        /// 索引名。
+        index: &'static str,
+        /// 索引里的 key。
+        key: String,
+        /// 挂错的 id。
+        id: u32,
+    },
+    /// 索引里留着一个没有 id 的空 key。
+    EmptyBucket {
+        /// 索引名。
+        index: &'static str,
+        /// 空桶的 key。
+        key: String,
+    },
+    /// 学生的班级不在班级表里。
+    UnknownClass {
+        /// 学生 id。
+        id: u32,
+        /// 表里没有的班级名。
+        class: String,
+    },
+    /// 增量维护的 `stats` 和按主存重算的结果不一致。
+    StatsMismatch,
+    /// `next_id` 不大于已有的最大 id，之后分配的 id 会和已有记录撞上。
+    NextIdTooLow {
+        /// 当前的 `next_id`。
+        next_id: u32,
+        /// 主存里最大的 id。
+        max_id: u32,
+    },
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inconsistency::DanglingId { index, id } => {
+                write!(f, "{index} index: dangling id={id} (no such record)")
+            }
+            Inconsistency::MissingEntry { index, id } => {
+                write!(f, "{index} index: id={id} is missing")
+            }
+            Inconsistency::StaleEntry { index, key, id } => {
+                write!(f, "{index} index: id={id} is filed under stale key `{key}`")
+            }
+            Inconsistency::EmptyBucket { index, key } => {
+                write!(f, "{index} index: empty bucket `{key}`")
+            }
+            Inconsistency::UnknownClass { id, class } => {
+                write!(f, "id={id} is in unknown class `{class}`")
+            }
+            Inconsistency::StatsMismatch => write!(f, "stats differ from the records"),
+            Inconsistency::NextIdTooLow { next_id, max_id } => {
+                write!(f, "next_id={next_id} is not above max id={max_id}")
+            }
+        }
+    }
+}
+
+// 一个二级索引的三个方向：索引 -> 主存（悬空、挂错、空桶），主存 -> 索引（缺失）。
+// `records` 按 id 升序，报告的顺序因此是确定的。
+fn check_index<K: Ord + fmt::Display>(
+    index: &'static str,
+    secondary: &SecondaryIndex<K>,
+    by_id: &HashMap<u32, Student>,
+    records: &[&Student],
+    found: &mut Vec<Inconsistency>,
+) {
+    for (key, ids) in &secondary.entries {
+        if ids.is_empty() {
+            found.push(Inconsistency::EmptyBucket {
+                index,
+                key: key.to_string(),
+            });
+        }
+        for &id in ids {
+            match by_id.get(&id) {
+                None => found.push(Inconsistency::DanglingId { index, id }),
+                Some(s) if secondary.key(s) != *key => found.push(Inconsistency::StaleEntry {
+                    index,
+                    key: key.to_string(),
+                    id,
+                }),
+                Some(_) => {}
+            }
+        }
+    }
+    for s in records {
+        if secondary.get(&secondary.key(s)).all(|id| id != s.id) {
+            found.push(Inconsistency::MissingEntry { index, id: s.id });
+        }
+    }
+}
+
+impl StudentStore {
+    /// 核对主存和全部索引，返回发现的每一处不一致；空表示一切正常。只读，不改任何东西。
+    ///
+    /// ```
+    /// use rust_notes::student::StudentStore;
+    ///
+    /// let mut store = StudentStore::new();
+    /// store.add_class("A1").unwrap();
+    /// store.add("alice", 12, "A1").unwrap();
+    /// assert!(store.verify().is_empty());
+    /// ```
+    pub fn verify(&self) -> Vec<Inconsistency> {
+        let mut records = self.by_id.values().collect::<Vec<&Student>>();
+        records.sort_by_key(|s| s.id);
+        let mut found = Vec::new();
+
+        for &id in &self.ids {
+            if !self.by_id.contains_key(&id) {
+                found.push(Inconsistency::DanglingId { index: "ids", id });
+            }
+        }
+        for s in &records {
+            if !self.ids.contains(&s.id) {
+                found.push(Inconsistency::MissingEntry {
+                    index: "ids",
+                    id: s.id,
+                });
+            }
+        }
+        let indexes = &self.indexes;
+        check_index("name", &indexes.name, &self.by_id, &records, &mut found);
+        check_index(
+            "name_folded",
+            &indexes.name_folded,
+            &self.by_id,
+            &records,
+            &mut found,
+        );
+        check_index("class", &indexes.class, &self.by_id, &records, &mut found);
+        check_index("age", &indexes.age, &self.by_id, &records, &mut found);
+
+        // 成绩挂在学生 id 上：删学生时会级联删成绩，课程没人了就整个删掉。
+        let mut orphans = BTreeSet::new();
+        for (course, scores) in &self.grades {
+            if scores.is_empty() {
+                found.push(Inconsistency::EmptyBucket {
+                    index: "grades",
+                    key: course.clone(),
+                });
+            }
+            orphans.extend(scores.keys().filter(|id| !self.by_id.contains_key(id)));
+        }
+        for id in orphans {
+            found.push(Inconsistency::DanglingId {
+                index: "grades",
+                id,
+            });
+        }
+        for s in &records {
+            if !self.classes.contains(&s.class_name) {
+                found.push(Inconsistency::UnknownClass {
+                    id: s.id,
+                    class: s.class_name.clone(),
+                });
+            }
+        }
+
+        let mut stats = ClassStats::default();
+        for s in &records {
+            stats.insert(s);
+        }
+        if stats != indexes.stats {
+            found.push(Inconsistency::StatsMismatch);
+        }
+
+        if let Some(max_id) = records.last().map(|s| s.id)
+            && self.next_id <= max_id
+        {
+            found.push(Inconsistency::NextIdTooLow {
+                next_id: self.next_id,
+                max_id,
+            });
+        }
+        found
+    }
+
+    /// 从主存 `by_id` 重建 `ids` 和全部二级索引，删掉学生已经不存在的成绩，
+    /// 把学生引用了、表里却没有的班级补进班级表，并把过小的 `next_id` 调到最大 id 之后。
+    /// 返回修复之前 [`verify`](Self::verify) 发现的问题。
+    ///
+    /// 主存是唯一的真相，修复不会丢学生记录；事务里不能修复，返回 [`StoreError::InTransaction`]。
+    /// undo/redo 历史是针对修复前的数据生成的，一并清空。
+    /// 修复没法写成日志里的操作（重建索引、调 `next_id` 都没有对应的 `Op`），
+    /// 所以打开了数据文件时直接 checkpoint，失败返回 [`StoreError::Storage`]。
+    ///
+    /// 有学生占了 `u32::MAX`（保留不分配的哨兵）时 `next_id` 没法调到它之后：
+    /// 其余问题照常修好，但不 checkpoint（快照加载会拒绝这份数据），返回 [`StoreError::IdExhausted`]。
+    pub fn repair(&mut self) -> Result<Vec<Inconsistency>, StoreError> {
+        if self.txn.is_some() {
+            return Err(StoreError::InTransaction("repair"));
+        }
+        let found = self.verify();
+        let mut indexes = Indexes::new();
+        for s in self.by_id.values() {
+            indexes.insert(s);
+        }
+        self.indexes = indexes;
+        self.ids = self.by_id.keys().copied().collect::<BTreeSet<u32>>();
+        let by_id = &self.by_id;
+        self.grades.retain(|_, scores| {
+            scores.retain(|id, _| by_id.contains_key(id));
+            !scores.is_empty()
+        });
+        for s in self.by_id.values() {
+            if !self.classes.contains(&s.class_name) {
+                self.classes.insert(s.class_name.clone());
+            }
+        }
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        if let Some(&max_id) = self.ids.last() {
+            if max_id == u32::MAX {
+                return Err(StoreError::IdExhausted);
+            }
+            self.next_id = self.next_id.max(max_id + 1);
+        }
+        if self.is_persistent() {
+            self.checkpoint().map_err(|e| match e {
+                PersistError::Io(e) => StoreError::Storage(e),
+                e => StoreError::Storage(io::Error::other(e)),
+            })?;
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Inconsistency;
+    use crate::student::index::{Indexes, StudentIndex};
+    use crate::student::persist::tests::temp_data_path;
+    use crate::student::store::tests::sample_store;
+    use crate::student::{Op, StoreError, StudentStore};
+    use std::fs;
+
+    #[test]
+    fn test_verify_reports_every_kind_and_repair_fixes_them() {
+        let mut store = sample_store();
+        assert!(store.verify().is_empty());
+
+        // 手动把索引弄坏：每种问题各来一处。
+        store.ids.insert(9);
+        store.ids.remove(&1);
+        store
+            .indexes
+            .name
+            .entries
+            .entry("ghost".into())
+            .or_default();
+        store
+            .indexes
+            .name
+            .entries
+            .get_mut("alice")
+            .unwrap()
+            .remove(&1);
+        store
+            .indexes
+            .class
+            .entries
+            .entry("class2".into())
+            .or_default()
+            .insert(3);
+        let alice = store.by_id[&1].clone();
+        store.indexes.stats.remove(&alice);
+        store.next_id = 3;
+
+        let found = store.verify();
+        let expected = [
+            Inconsistency::DanglingId {
+                index: "ids",
+                id: 9,
+            },
+            Inconsistency::MissingEntry {
+                index: "ids",
+                id: 1,
+            },
+            Inconsistency::EmptyBucket {
+                index: "name",
+                key: "alice".into(),
+            },
+            Inconsistency::EmptyBucket {
+                index: "name",
+                key: "ghost".into(),
+            },
+            Inconsistency::MissingEntry {
+                index: "name",
+                id: 1,
+            },
+            Inconsistency::StaleEntry {
+                index: "class",
+                key: "class2".into(),
+                id: 3,
+            },
+            Inconsistency::StatsMismatch,
+            Inconsistency::NextIdTooLow {
+                next_id: 3,
+                max_id: 3,
+            },
+        ];
+        assert_eq!(found, expected);
+        assert_eq!(
+            found[5].to_string(),
+            "class index: id=3 is filed under stale key `class2`"
+        );
+
+        assert_eq!(store.repair().unwrap(), expected);
+        assert!(store.verify().is_empty());
+        assert_eq!(store.next_id, 4);
+        assert_eq!(store.search_by_name_exact("alice").len(), 1);
+        assert!(store.search_by_class("class2").is_empty());
+
+        store.begin().unwrap();
+        assert!(store.repair().is_err());
+    }
+
+    #[test]
+    fn test_verify_checks_grades_and_class_table() {
+        let mut store = sample_store();
+        store.set_grade(1, "math", 90).unwrap();
+        store.set_grade(3, "math", 80).unwrap();
+        assert!(store.verify().is_empty());
+
+        // 绕过 apply 直接删学生，成绩就成了孤儿；再造一个空课程和一个不在班级表里的班级。
+        store.by_id.remove(&3);
+        store.ids.remove(&3);
+        store.indexes = Indexes::new();
+        for s in store.by_id.values() {
+            store.indexes.insert(s);
+        }
+        store.grades.insert("art".into(), Default::default());
+        store.classes.remove("class1");
+
+        let found = store.verify();
+        let expected = [
+            Inconsistency::EmptyBucket {
+                index: "grades",
+                key: "art".into(),
+            },
+            Inconsistency::DanglingId {
+                index: "grades",
+                id: 3,
+            },
+            Inconsistency::UnknownClass {
+                id: 1,
+                class: "class1".into(),
+            },
+        ];
+        assert_eq!(found, expected);
+        assert_eq!(found[2].to_string(), "id=1 is in unknown class `class1`");
+
+        assert_eq!(store.repair().unwrap(), expected);
+        assert!(store.verify().is_empty());
+        assert_eq!(store.grades_of(1), [("math", 90)]);
+        assert_eq!(store.courses(), ["math"]);
+        assert!(store.has_class("class1"));
+    }
+
+    #[test]
+    fn test_repair_reports_exhausted_id_space() {
+        let mut store = sample_store();
+        let mut last = store.by_id[&3].clone();
+        last.id = u32::MAX - 1;
+        assert!(store.apply(Op::Insert(last.clone())).is_some());
+        store.next_id = 1;
+        store.repair().unwrap();
+        assert_eq!(store.next_id, u32::MAX);
+        assert!(store.verify().is_empty());
+
+        // 有学生占了哨兵 id：next_id 没有合法值，修不好就报错，不假装修好了。
+        last.id = u32::MAX;
+        assert!(store.apply(Op::Insert(last)).is_some());
+        store.ids.remove(&1);
+        assert!(matches!(store.repair(), Err(StoreError::IdExhausted)));
+        assert_eq!(
+            store.verify(),
+            [Inconsistency::NextIdTooLow {
+                next_id: u32::MAX,
+                max_id: u32::MAX,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_repair_checkpoints_and_clears_history() {
+        let path = temp_data_path("repair");
+        let mut store = StudentStore::open(&path).unwrap();
+        store.add_class("c").unwrap();
+        store.add("alice", 18, "c").unwrap();
+        store.grades.entry("math".into()).or_default().insert(9, 80);
+        store.next_id = 1;
+
+        assert_eq!(store.repair().unwrap().len(), 2);
+        assert!(matches!(store.undo(), Err(StoreError::NothingToUndo)));
+        // 修复结果已经进了快照，日志是空的：重开看到的就是修好的数据。
+        assert!(fs::read(path.with_extension("sms.wal")).unwrap().is_empty());
+        let reopened = StudentStore::open(&path).unwrap();
+        assert!(reopened.verify().is_empty());
+        assert_eq!(reopened.next_id, 2);
+        assert!(reopened.courses().is_empty());
+    }
+}
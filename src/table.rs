@@ -0,0 +1,276 @@
+//! 按终端显示宽度对齐的文本表格。只用标准库。
+//!
+//! `format!("{:<12}")` 按 `char` 个数补空格，而一个汉字在终端里占两列、组合附加符号占零列，
+//! 所以中文名字会把列撑歪。这里用 [`display_width`] 算每个单元格实际占几列：
+//!
+//! - 东亚宽字符（CJK 汉字、假名、谚文、全角符号、常见 emoji）占 2 列；
+//! - 组合附加符号、零宽字符、变体选择符和控制字符占 0 列；
+//! - 其余占 1 列（UAX #11 里宽度不确定的 “Ambiguous” 字符也按 1 列算）。
+//!
+//! 只覆盖常用区段，是 UAX #11 / `wcwidth` 的近似，够表格对齐用。
+//!
+//! [`Table`] 按数据自动决定列宽，超过上限的单元格截断并以 `…` 结尾：
+//!
+//! ```
+//! use rust_notes::table::Table;
+//!
+//! let mut table = Table::new(&["id", "name"]).max_cell_width(8);
+//! table.push_row(vec!["1".to_string(), "张三".to_string()]);
+//! table.push_row(vec!["2".to_string(), "Bartholomew".to_string()]);
+//! assert_eq!(
+//!     table.to_string(),
+//!     "id  name\n1   张三\n2   Barthol…\n"
+//! );
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// 截断时补在末尾的省略号，占 1 列。
+pub const ELLIPSIS: char = '…';
+
+// 占 0 列的区段：组合附加符号、零宽字符、变体选择符等。
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x2060, 0x2064),
+    (0x20D0, 0x20FF),
+    (0x3099, 0x309A),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0xFEFF, 0xFEFF),
+    (0xE0100, 0xE01EF),
+];
+
+// 占 2 列的区段：East Asian Wide / Fullwidth 里常用的部分。
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x2329, 0x232A),
+    (0x23E9, 0x23EC),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x2753, 0x2757),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xA960, 0xA97F),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE10, 0xFE19),
+    (0xFE30, 0xFE6F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F680, 0x1F6FF),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+fn in_ranges(ranges: &[(u32, u32)], c: char) -> bool {
+    let c = u32::from(c);
+    // 区段按起点升序排好，二分找最后一个起点 <= c 的区段。
+    let i = ranges.partition_point(|&(start, _)| start <= c);
+    i > 0 && c <= ranges[i - 1].1
+}
+
+/// 一个字符在终端里占几列：0、1 或 2。
+pub fn char_width(c: char) -> usize {
+    if c.is_control() || in_ranges(ZERO_WIDTH, c) {
+        0
+    } else if in_ranges(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// 一段文本在终端里占几列。
+///
+/// ```
+/// use rust_notes::table::display_width;
+///
+/// assert_eq!(display_width("bob"), 3);
+/// assert_eq!(display_width("张三"), 4);
+/// assert_eq!(display_width("e\u{301}"), 1); // e + 组合重音符
+/// ```
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// 把文本截到最多 `max` 列：放不下时保留尽量多的前缀并以 [`ELLIPSIS`] 结尾；放得下时原样返回。
+/// 宽字符不会被劈开，所以结果可能比 `max` 少一列。
+pub fn truncate(text: &str, max: usize) -> Cow<'_, str> {
+    if display_width(text) <= max {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let width = char_width(c);
+        if used + width + 1 > max {
+            break;
+        }
+        used += width;
+        out.push(c);
+    }
+    if max > 0 {
+        out.push(ELLIPSIS);
+    }
+    Cow::Owned(out)
+}
+
+/// 自动列宽的文本表格：第一行是表头，列之间隔两个空格，最后一列不补空格。
+///
+/// 用 `Display` 输出，每行以 `\n` 结尾。
+#[derive(Debug, Clone)]
+pub struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+    max_cell_width: usize,
+    // 单独设置过上限的列：(列号, 最多几列)。
+    column_limits: Vec<(usize, usize)>,
+}
+
+impl Table {
+    /// 只有表头的空表；单元格默认不截断。
+    pub fn new(header: &[&str]) -> Self {
+        Self {
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+            max_cell_width: usize::MAX,
+            column_limits: Vec::new(),
+        }
+    }
+
+    /// 单元格最多占几列，超出的截断（见 [`truncate`]）；表头不截断。
+    pub fn max_cell_width(mut self, width: usize) -> Self {
+        self.max_cell_width = width;
+        self
+    }
+
+    /// 单独给第 `column` 列（从 0 开始）设上限，覆盖 [`max_cell_width`](Self::max_cell_width)。
+    pub fn column_max_width(mut self, column: usize, width: usize) -> Self {
+        self.column_limits.retain(|&(c, _)| c != column);
+        self.column_limits.push((column, width));
+        self
+    }
+
+    fn limit(&self, column: usize) -> usize {
+        self.column_limits
+            .iter()
+            .find(|&&(c, _)| c == column)
+            .map_or(self.max_cell_width, |&(_, width)| width)
+    }
+
+    /// 追加一行。单元格比表头少时缺的当空，多出的忽略。
+    pub fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = self.header.len();
+        // 表头不截断。
+        let cell = |row: &[String], i: usize| -> String {
+            let text = row.get(i).map_or("", String::as_str);
+            truncate(text, self.limit(i)).into_owned()
+        };
+        let lines = std::iter::once(self.header.clone())
+            .chain(
+                self.rows
+                    .iter()
+                    .map(|row| (0..columns).map(|i| cell(row, i)).collect()),
+            )
+            .collect::<Vec<Vec<String>>>();
+
+        let mut widths = vec![0; columns];
+        for line in &lines {
+            for (width, text) in widths.iter_mut().zip(line) {
+                *width = (*width).max(display_width(text));
+            }
+        }
+
+        for line in &lines {
+            let mut out = String::new();
+            for (i, text) in line.iter().enumerate() {
+                out.push_str(text);
+                if i + 1 < columns {
+                    let pad = widths[i] - display_width(text) + 2;
+                    out.extend(std::iter::repeat_n(' ', pad));
+                }
+            }
+            writeln!(f, "{}", out.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Table, char_width, display_width, truncate};
+
+    #[test]
+    fn test_widths_and_truncation() {
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('中'), 2);
+        assert_eq!(char_width('ア'), 2);
+        assert_eq!(char_width('한'), 2);
+        assert_eq!(char_width('Ａ'), 2);
+        assert_eq!(char_width('\u{301}'), 0);
+        assert_eq!(char_width('\u{200d}'), 0);
+        assert_eq!(char_width('é'), 1);
+        assert_eq!(char_width('😀'), 2);
+        assert_eq!(display_width("Zoë 李"), 6);
+
+        assert_eq!(truncate("bob", 3), "bob");
+        assert_eq!(truncate("bobby", 4), "bob…");
+        // 宽字符放不下半个：4 列只能放一个汉字加省略号。
+        assert_eq!(truncate("张三丰", 4), "张…");
+        assert_eq!(truncate("张三丰", 5), "张三…");
+        // 组合符号跟着前面的字符走，不单独占位。
+        assert_eq!(truncate("e\u{301}e\u{301}e\u{301}", 2), "e\u{301}…");
+        assert_eq!(truncate("abc", 0), "");
+    }
+
+    #[test]
+    fn test_table_aligns_by_display_width() {
+        let mut table = Table::new(&["id", "name", "class"])
+            .max_cell_width(6)
+            .column_max_width(0, 1);
+        table.push_row(vec!["1".into(), "张三".into(), "一班".into()]);
+        table.push_row(vec!["12".into(), "bob".into()]);
+        table.push_row(vec!["3".into(), "Zoe\u{308}y".into(), "Grade 10".into()]);
+        let expected = concat!(
+            "id  name  class\n",
+            "1   张三  一班\n",
+            "…   bob\n",
+            "3   Zoe\u{308}y  Grade…\n",
+        );
+        assert_eq!(table.to_string(), expected);
+    }
+}